@@ -0,0 +1,303 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        point::{Point2f, Point3f},
+        ray::Ray,
+        vector::Vector3f,
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::Float,
+    sampling::sample_cdf,
+    shape::Shape,
+};
+
+const MAX_SHAPES_IN_NODE: usize = 4;
+
+struct BvhShapeInfo {
+    shape_index: usize,
+    bounds: Bounds3f,
+    centroid: Point3f,
+}
+
+// LinearBvhNode
+// leaves hold n_shapes > 0 shapes starting at offset, interior nodes have
+// their first child right after them and the second one at offset
+#[derive(Debug, Copy, Clone)]
+pub struct LinearBvhNode {
+    pub bounds: Bounds3f,
+    pub offset: usize,
+    pub n_shapes: usize,
+    pub axis: usize,
+}
+
+// BvhAccel
+// bounding volume hierarchy over the world bounds of shapes, split at the
+// centroid median along the widest axis. Nodes are stored flattened in depth
+// first order
+pub struct BvhAccel {
+    pub shapes: Vec<Arc<dyn Shape>>,
+    pub nodes: Vec<LinearBvhNode>,
+    // running sums of the shape areas, computed the first time the area is
+    // needed
+    area_cdf: OnceLock<Vec<Float>>,
+}
+
+impl BvhAccel {
+    pub fn new(shapes: Vec<Arc<dyn Shape>>) -> BvhAccel {
+        let mut shape_info: Vec<BvhShapeInfo> = shapes
+            .iter()
+            .enumerate()
+            .map(|(shape_index, shape)| {
+                let bounds = shape.world_bound();
+                BvhShapeInfo {
+                    shape_index,
+                    bounds,
+                    centroid: bounds.lerp(&Point3f::new(0.5, 0.5, 0.5)),
+                }
+            })
+            .collect();
+        let mut bvh = BvhAccel {
+            shapes: Vec::with_capacity(shapes.len()),
+            nodes: Vec::with_capacity(2 * shapes.len()),
+            area_cdf: OnceLock::new(),
+        };
+        if !shapes.is_empty() {
+            bvh.build(&mut shape_info, &shapes);
+        }
+        bvh
+    }
+
+    fn area_cdf(&self) -> &[Float] {
+        self.area_cdf.get_or_init(|| {
+            self.shapes
+                .iter()
+                .scan(0.0, |area, shape| {
+                    *area += shape.area();
+                    Some(*area)
+                })
+                .collect()
+        })
+    }
+
+    // appends the subtree over shape_info to nodes, leaves take their
+    // shapes over into self.shapes in order
+    fn build(&mut self, shape_info: &mut [BvhShapeInfo], shapes: &[Arc<dyn Shape>]) {
+        let node = self.nodes.len();
+        let bounds = shape_info
+            .iter()
+            .fold(Bounds3f::default(), |b, info| b.union(&info.bounds));
+        self.nodes.push(LinearBvhNode {
+            bounds,
+            offset: 0,
+            n_shapes: 0,
+            axis: 0,
+        });
+
+        // choose split dimension from the centroid bounds
+        let centroid_bounds = shape_info.iter().fold(Bounds3f::default(), |b, info| {
+            b.union_point3f(&info.centroid)
+        });
+        let axis = centroid_bounds.maximum_extend();
+        if shape_info.len() <= MAX_SHAPES_IN_NODE
+            || centroid_bounds.p_max[axis] == centroid_bounds.p_min[axis]
+        {
+            // create leaf node
+            self.nodes[node].offset = self.shapes.len();
+            self.nodes[node].n_shapes = shape_info.len();
+            for info in shape_info.iter() {
+                self.shapes.push(shapes[info.shape_index].clone());
+            }
+            return;
+        }
+
+        // partition shapes into equally sized subsets
+        let mid = shape_info.len() / 2;
+        shape_info
+            .select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        let (left, right) = shape_info.split_at_mut(mid);
+        self.build(left, shapes);
+        self.nodes[node].offset = self.nodes.len();
+        self.nodes[node].axis = axis;
+        self.build(right, shapes);
+    }
+
+    // visits the shapes in the leaves the ray passes through, near to far,
+    // until visit returns true
+    fn traverse<F>(&self, ray: &Ray, mut visit: F)
+    where
+        F: FnMut(&Arc<dyn Shape>) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv_dir = Vector3f::new(1.0 / ray.d.x, 1.0 / ray.d.y, 1.0 / ray.d.z);
+        let dir_is_neg = [
+            (inv_dir.x < 0.0) as u8,
+            (inv_dir.y < 0.0) as u8,
+            (inv_dir.z < 0.0) as u8,
+        ];
+        // follow ray through BVH nodes to find shape intersections
+        let mut nodes_to_visit = Vec::with_capacity(64);
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounds.intersect_p(ray, &inv_dir, &dir_is_neg) {
+                if node.n_shapes > 0 {
+                    // intersect ray with shapes in leaf BVH node
+                    for shape in &self.shapes[node.offset..node.offset + node.n_shapes] {
+                        if visit(shape) {
+                            return;
+                        }
+                    }
+                } else {
+                    // put far BVH node on nodes_to_visit stack, advance to
+                    // near node
+                    if dir_is_neg[node.axis] == 1 {
+                        nodes_to_visit.push(current + 1);
+                        current = node.offset;
+                    } else {
+                        nodes_to_visit.push(node.offset);
+                        current += 1;
+                    }
+                    continue;
+                }
+            }
+            match nodes_to_visit.pop() {
+                Some(next) => current = next,
+                None => return,
+            }
+        }
+    }
+}
+
+impl Shape for BvhAccel {
+    fn object_bound(&self) -> Bounds3f {
+        self.world_bound()
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.nodes
+            .first()
+            .map_or(Bounds3f::default(), |node| node.bounds)
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        // shrink t_max of a copy of the ray as closer hits are found
+        let r = ray.clone();
+        let mut closest = None;
+        self.traverse(&r, |shape| {
+            if let Some((t_hit, isect)) = shape.intersect(&r) {
+                r.t_max.set(t_hit);
+                closest = Some((t_hit, isect));
+            }
+            false
+        });
+        closest
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        let mut hit = false;
+        self.traverse(ray, |shape| {
+            hit = shape.intersect_p(ray);
+            hit
+        });
+        hit
+    }
+
+    fn area(&self) -> Float {
+        self.area_cdf().last().copied().unwrap_or(0.0)
+    }
+
+    // picks a shape proportionally to its area and samples it, returns a
+    // zero pdf if there is nothing to sample
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let area = self.area();
+        if area == 0.0 {
+            return (Interaction::default(), 0.0);
+        }
+        let (i, ux) = sample_cdf(self.area_cdf(), u.x);
+        let shape = &self.shapes[i];
+        let (it, pdf) = shape.sample_area(&Point2f::new(ux, u.y));
+        (it, pdf * shape.area() / area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        core::{geometry::transform::Transform, pbrt::PI},
+        shapes::sphere::Sphere,
+    };
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    // unit spheres centered at x = 0, 3, 6, ... along the x axis
+    fn spheres(n: usize) -> Vec<Arc<dyn Shape>> {
+        (0..n)
+            .map(|i| {
+                let object_to_world =
+                    Transform::translate(&Vector3f::new(3.0 * i as Float, 0.0, 0.0));
+                let world_to_object = object_to_world.inverse();
+                Arc::new(Sphere::new(
+                    Arc::new(object_to_world),
+                    Arc::new(world_to_object),
+                    false,
+                    1.0,
+                    -1.0,
+                    1.0,
+                    360.0,
+                )) as Arc<dyn Shape>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_closest_hit() {
+        let bvh = BvhAccel::new(spheres(20));
+        assert_eq!(bvh.shapes.len(), 20);
+        // along the row of spheres from either end
+        let r = ray(Point3f::new(-5.0, 0.0, 0.0), Vector3f::new(1.0, 0.0, 0.0));
+        assert!((bvh.intersect(&r).unwrap().0 - 4.0).abs() < 1e-5);
+        let r = ray(Point3f::new(70.0, 0.0, 0.0), Vector3f::new(-1.0, 0.0, 0.0));
+        assert!((bvh.intersect(&r).unwrap().0 - 12.0).abs() < 1e-5);
+        // down onto the sphere at x = 30
+        let r = ray(Point3f::new(30.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t_hit, si) = bvh.intersect(&r).unwrap();
+        assert!((t_hit - 4.0).abs() < 1e-5);
+        assert!((si.p() - Point3f::new(30.0, 0.0, 1.0)).length() < 1e-4);
+        let miss = ray(Point3f::new(1.5, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!(!bvh.intersect_p(&miss));
+        assert!(bvh.intersect_p(&r));
+    }
+
+    #[test]
+    fn samples_shapes_by_area() {
+        let bvh = BvhAccel::new(spheres(8));
+        assert!((bvh.area() - 8.0 * 4.0 * PI).abs() < 1e-3);
+        for i in 0..8 {
+            let u = Point2f::new((i as Float + 0.5) / 8.0, 0.3);
+            let (it, pdf) = bvh.sample_area(&u);
+            assert!((pdf * bvh.area() - 1.0).abs() < 1e-4);
+            // every stratum of u.x lands on its own sphere
+            let center = Point3f::new(3.0 * (it.p().x / 3.0).round(), 0.0, 0.0);
+            assert!(((it.p() - center).length() - 1.0).abs() < 1e-4);
+        }
+        let empty = BvhAccel::new(Vec::new());
+        assert_eq!(empty.area(), 0.0);
+        assert_eq!(empty.sample_area(&Point2f::new(0.5, 0.5)).1, 0.0);
+        assert!(!empty.intersect_p(&ray(Point3f::default(), Vector3f::new(1.0, 0.0, 0.0))));
+    }
+}
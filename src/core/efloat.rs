@@ -0,0 +1,215 @@
+use std::ops;
+
+use super::pbrt::{next_float_down, next_float_up, Float};
+
+// EFloat
+// a floating-point value together with a conservative interval that is
+// guaranteed to contain the value an exact computation would have produced
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EFloat {
+    v: Float,
+    low: Float,
+    high: Float,
+}
+
+impl EFloat {
+    pub fn new(v: Float, err: Float) -> EFloat {
+        if err == 0.0 {
+            EFloat { v, low: v, high: v }
+        } else {
+            // compute conservative bounds by rounding the endpoints away
+            // from the middle
+            EFloat {
+                v,
+                low: next_float_down(v - err),
+                high: next_float_up(v + err),
+            }
+        }
+    }
+
+    pub fn value(&self) -> Float {
+        self.v
+    }
+
+    pub fn upper_bound(&self) -> Float {
+        self.high
+    }
+
+    pub fn lower_bound(&self) -> Float {
+        self.low
+    }
+
+    pub fn absolute_error(&self) -> Float {
+        next_float_up((self.high - self.v).abs().max((self.v - self.low).abs()))
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    pub fn relative_error(&self, precise: f64) -> f64 {
+        ((precise - self.v as f64) / precise).abs()
+    }
+
+    pub fn sqrt(&self) -> EFloat {
+        EFloat {
+            v: self.v.sqrt(),
+            low: next_float_down(self.low.sqrt()),
+            high: next_float_up(self.high.sqrt()),
+        }
+    }
+
+    pub fn abs(&self) -> EFloat {
+        if self.low >= 0.0 {
+            // the entire interval is greater than zero
+            *self
+        } else if self.high <= 0.0 {
+            // the entire interval is less than zero
+            -*self
+        } else {
+            // the interval straddles zero
+            EFloat {
+                v: self.v.abs(),
+                low: 0.0,
+                high: (-self.low).max(self.high),
+            }
+        }
+    }
+}
+
+impl From<Float> for EFloat {
+    fn from(v: Float) -> EFloat {
+        EFloat::new(v, 0.0)
+    }
+}
+
+impl From<EFloat> for Float {
+    fn from(ef: EFloat) -> Float {
+        ef.v
+    }
+}
+
+impl PartialEq for EFloat {
+    fn eq(&self, ef: &EFloat) -> bool {
+        self.v == ef.v
+    }
+}
+
+impl_op_ex!(+ |a: &EFloat, b: &EFloat| -> EFloat {
+    // interval arithmetic addition, with the result rounded away from
+    // the value v in order to be conservative
+    EFloat {
+        v: a.v + b.v,
+        low: next_float_down(a.low + b.low),
+        high: next_float_up(a.high + b.high),
+    }
+});
+
+impl_op_ex!(-|a: &EFloat, b: &EFloat| -> EFloat {
+    EFloat {
+        v: a.v - b.v,
+        low: next_float_down(a.low - b.high),
+        high: next_float_up(a.high - b.low),
+    }
+});
+
+impl_op_ex!(*|a: &EFloat, b: &EFloat| -> EFloat {
+    let prod = [
+        a.low * b.low,
+        a.high * b.low,
+        a.low * b.high,
+        a.high * b.high,
+    ];
+    EFloat {
+        v: a.v * b.v,
+        low: next_float_down(prod[0].min(prod[1]).min(prod[2].min(prod[3]))),
+        high: next_float_up(prod[0].max(prod[1]).max(prod[2].max(prod[3]))),
+    }
+});
+
+impl_op_ex!(/ |a: &EFloat, b: &EFloat| -> EFloat {
+    if b.low < 0.0 && b.high > 0.0 {
+        // the interval we're dividing by straddles zero so just return an
+        // interval of everything
+        return EFloat {
+            v: a.v / b.v,
+            low: -Float::INFINITY,
+            high: Float::INFINITY,
+        };
+    }
+    let div = [
+        a.low / b.low,
+        a.high / b.low,
+        a.low / b.high,
+        a.high / b.high,
+    ];
+    EFloat {
+        v: a.v / b.v,
+        low: next_float_down(div[0].min(div[1]).min(div[2].min(div[3]))),
+        high: next_float_up(div[0].max(div[1]).max(div[2].max(div[3]))),
+    }
+});
+
+impl_op_ex!(-|a: &EFloat| -> EFloat {
+    EFloat {
+        v: -a.v,
+        low: -a.high,
+        high: -a.low,
+    }
+});
+
+impl_op_ex!(+ |a: &EFloat, b: Float| -> EFloat { a + EFloat::from(b) });
+
+impl_op_ex!(+ |a: Float, b: &EFloat| -> EFloat { EFloat::from(a) + b });
+
+impl_op_ex!(-|a: &EFloat, b: Float| -> EFloat { a - EFloat::from(b) });
+
+impl_op_ex!(-|a: Float, b: &EFloat| -> EFloat { EFloat::from(a) - b });
+
+impl_op_ex!(*|a: &EFloat, b: Float| -> EFloat { a * EFloat::from(b) });
+
+impl_op_ex!(*|a: Float, b: &EFloat| -> EFloat { EFloat::from(a) * b });
+
+impl_op_ex!(/ |a: &EFloat, b: Float| -> EFloat { a / EFloat::from(b) });
+
+impl_op_ex!(/ |a: Float, b: &EFloat| -> EFloat { EFloat::from(a) / b });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::pbrt::quadratic_efloat;
+
+    fn contains(ef: &EFloat, v: Float) -> bool {
+        ef.lower_bound() <= v && v <= ef.upper_bound()
+    }
+
+    #[test]
+    fn bounds_contain_exact_results() {
+        let a = EFloat::new(0.1, 0.0);
+        let b = EFloat::new(0.2, 0.0);
+        // 0.1 + 0.2 rounds up, the exact sum of the two floats is below it
+        let sum = a + b;
+        assert!(sum.lower_bound() < sum.value() && sum.value() < sum.upper_bound());
+        let x = EFloat::new(2.0, 1e-3);
+        assert!(contains(&(x * x), 2.001 * 2.001));
+        assert!(contains(&(x * x), 1.999 * 1.999));
+        assert!(contains(&x.sqrt(), (2.0 as Float).sqrt()));
+        assert!(contains(&(1.0 / x), 0.5));
+        let straddle = EFloat::new(0.0, 1.0);
+        assert_eq!((x / straddle).upper_bound(), Float::INFINITY);
+    }
+
+    #[test]
+    fn quadratic_roots_are_bracketed() {
+        // (t - 1) (t - 3) with a slightly uncertain constant term
+        let (t0, t1) = quadratic_efloat(
+            EFloat::from(1.0),
+            EFloat::from(-4.0),
+            EFloat::new(3.0, 1e-6),
+        )
+        .unwrap();
+        assert!(contains(&t0, 1.0));
+        assert!(contains(&t1, 3.0));
+        assert!(t0.absolute_error() < 1e-5 && t1.absolute_error() < 1e-5);
+        assert!(
+            quadratic_efloat(EFloat::from(1.0), EFloat::from(0.0), EFloat::from(1.0)).is_none()
+        );
+    }
+}
@@ -1,21 +1,48 @@
 use std::ops::{Index, IndexMut};
 
-use crate::core::pbrt::Float;
+use crate::core::pbrt::{gamma, Float};
 
 use super::{
     misc::{point2f_inside_bounds2f, point3f_inside_bounds3f},
-    point::{Point2f, Point3f},
+    point::{Point2, Point2f, Point2i, Point3, Point3f, Point3i},
     ray::Ray,
-    vector::{Vector2f, Vector3f},
+    vector::{Vector2f, Vector2i, Vector3f, Vector3i},
 };
 
-// Bounds2f
-#[derive(Debug, Copy, Clone)]
-pub struct Bounds2f {
-    pub p_min: Point2f,
-    pub p_max: Point2f,
+// Bounds2
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bounds2<T> {
+    pub p_min: Point2<T>,
+    pub p_max: Point2<T>,
+}
+
+pub type Bounds2f = Bounds2<Float>;
+pub type Bounds2i = Bounds2<i32>;
+
+impl<T> Index<usize> for Bounds2<T> {
+    type Output = Point2<T>;
+
+    fn index(&self, i: usize) -> &Point2<T> {
+        match i {
+            0 => &self.p_min,
+            1 => &self.p_max,
+            _ => panic!("Bounds2 index out of bounds"),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Bounds2<T> {
+    fn index_mut(&mut self, i: usize) -> &mut Point2<T> {
+        match i {
+            0 => &mut self.p_min,
+            1 => &mut self.p_max,
+            _ => panic!("Bounds2 index out of bounds"),
+        }
+    }
 }
 
+// Bounds2f
 impl Bounds2f {
     pub fn new_with_points(p1: Point2f, p2: Point2f) -> Bounds2f {
         Bounds2f {
@@ -68,7 +95,7 @@ impl Bounds2f {
 
     pub fn bounding_sphere(&self) -> (Point2f, Float) {
         let center = (self.p_min + self.p_max) * 0.5;
-        let radius = if point2f_inside_bounds2f(&center, &self) {
+        let radius = if point2f_inside_bounds2f(&center, self) {
             center.distance(&self.p_max)
         } else {
             0.0
@@ -95,35 +122,167 @@ impl From<Point2f> for Bounds2f {
     }
 }
 
-impl Index<usize> for Bounds2f {
-    type Output = Point2f;
+impl From<Bounds2i> for Bounds2f {
+    fn from(b: Bounds2i) -> Bounds2f {
+        Bounds2f {
+            p_min: Point2f::from(b.p_min),
+            p_max: Point2f::from(b.p_max),
+        }
+    }
+}
+
+// Bounds2i
+impl Bounds2i {
+    pub fn new_with_points(p1: Point2i, p2: Point2i) -> Bounds2i {
+        Bounds2i {
+            p_min: p1.min(&p2),
+            p_max: p1.max(&p2),
+        }
+    }
+
+    pub fn diagonal(&self) -> Vector2i {
+        self.p_max - self.p_min
+    }
+
+    // zero for empty bounds, in u128 since the extent of large bounds
+    // overflows i32
+    pub fn area(&self) -> u128 {
+        if self.is_empty() {
+            return 0;
+        }
+        extent_i32(self.p_min.x, self.p_max.x) * extent_i32(self.p_min.y, self.p_max.y)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.p_min.x >= self.p_max.x || self.p_min.y >= self.p_max.y
+    }
+
+    pub fn union_point2i(&self, p: &Point2i) -> Bounds2i {
+        Bounds2i {
+            p_min: self.p_min.min(p),
+            p_max: self.p_max.max(p),
+        }
+    }
+
+    pub fn union(&self, b: &Bounds2i) -> Bounds2i {
+        Bounds2i {
+            p_min: self.p_min.min(&b.p_min),
+            p_max: self.p_max.max(&b.p_max),
+        }
+    }
+
+    pub fn intersect(&self, b: &Bounds2i) -> Bounds2i {
+        Bounds2i {
+            p_min: self.p_min.max(&b.p_min),
+            p_max: self.p_max.min(&b.p_max),
+        }
+    }
+}
+
+impl Default for Bounds2i {
+    fn default() -> Bounds2i {
+        Bounds2i {
+            p_min: Point2i::new(i32::MAX, i32::MAX),
+            p_max: Point2i::new(i32::MIN, i32::MIN),
+        }
+    }
+}
+
+impl From<Point2i> for Bounds2i {
+    fn from(p: Point2i) -> Bounds2i {
+        Bounds2i { p_min: p, p_max: p }
+    }
+}
+
+// iterates over all points p with p_min <= p < p_max, x varying fastest
+impl IntoIterator for &Bounds2i {
+    type Item = Point2i;
+    type IntoIter = Bounds2iIterator;
+
+    fn into_iter(self) -> Bounds2iIterator {
+        Bounds2iIterator {
+            p: self.p_min,
+            bounds: *self,
+        }
+    }
+}
+
+pub struct Bounds2iIterator {
+    p: Point2i,
+    bounds: Bounds2i,
+}
+
+impl Iterator for Bounds2iIterator {
+    type Item = Point2i;
 
-    fn index(&self, i: usize) -> &Point2f {
+    fn next(&mut self) -> Option<Point2i> {
+        if self.bounds.is_empty() || self.p.y >= self.bounds.p_max.y {
+            return None;
+        }
+        let p = self.p;
+        self.p.x += 1;
+        if self.p.x == self.bounds.p_max.x {
+            self.p.x = self.bounds.p_min.x;
+            self.p.y += 1;
+        }
+        Some(p)
+    }
+
+    // the full i32 range has more points than fit in a usize
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if self.bounds.is_empty() || self.p.y >= self.bounds.p_max.y {
+            0
+        } else {
+            let width = extent_i32(self.bounds.p_min.x, self.bounds.p_max.x);
+            let rows = extent_i32(self.p.y, self.bounds.p_max.y);
+            rows * width - extent_i32(self.bounds.p_min.x, self.p.x)
+        };
+        match usize::try_from(remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+// number of integers in [low, high), which may not fit in an i32
+fn extent_i32(low: i32, high: i32) -> u128 {
+    (high as i64 - low as i64).max(0) as u128
+}
+
+// Bounds3
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bounds3<T> {
+    pub p_min: Point3<T>,
+    pub p_max: Point3<T>,
+}
+
+pub type Bounds3f = Bounds3<Float>;
+pub type Bounds3i = Bounds3<i32>;
+
+impl<T> Index<usize> for Bounds3<T> {
+    type Output = Point3<T>;
+
+    fn index(&self, i: usize) -> &Point3<T> {
         match i {
             0 => &self.p_min,
             1 => &self.p_max,
-            _ => panic!("Bounds2f index out of bounds"),
+            _ => panic!("Bounds3 index out of bounds"),
         }
     }
 }
 
-impl IndexMut<usize> for Bounds2f {
-    fn index_mut(&mut self, i: usize) -> &mut Point2f {
+impl<T> IndexMut<usize> for Bounds3<T> {
+    fn index_mut(&mut self, i: usize) -> &mut Point3<T> {
         match i {
             0 => &mut self.p_min,
             1 => &mut self.p_max,
-            _ => panic!("Bounds2f index out of bounds"),
+            _ => panic!("Bounds3 index out of bounds"),
         }
     }
 }
 
 // Bounds3f
-#[derive(Debug, Copy, Clone)]
-pub struct Bounds3f {
-    pub p_min: Point3f,
-    pub p_max: Point3f,
-}
-
 impl Bounds3f {
     pub fn new_with_points(p1: Point3f, p2: Point3f) -> Bounds3f {
         Bounds3f {
@@ -230,7 +389,7 @@ impl Bounds3f {
 
     pub fn bounding_sphere(&self) -> (Point3f, Float) {
         let center = (self.p_min + self.p_max) * 0.5;
-        let radius = if point3f_inside_bounds3f(&center, &self) {
+        let radius = if point3f_inside_bounds3f(&center, self) {
             center.distance(&self.p_max)
         } else {
             0.0
@@ -269,11 +428,51 @@ impl Bounds3f {
     }
 
     pub fn intersect_b(&self, ray: &Ray, hitt0: &mut Float, hitt1: &mut Float) -> bool {
-        unimplemented!()
+        let mut t0: Float = 0.0;
+        let mut t1 = ray.t_max.get();
+        for i in 0..3 {
+            // update interval for i-th bounding box slab
+            let inv_ray_dir = 1.0 / ray.d[i];
+            let mut t_near = (self.p_min[i] - ray.o[i]) * inv_ray_dir;
+            let mut t_far = (self.p_max[i] - ray.o[i]) * inv_ray_dir;
+
+            // update parametric interval from slab intersection t values
+            if t_near > t_far {
+                std::mem::swap(&mut t_near, &mut t_far);
+            }
+            // update t_far to ensure robust ray-bounds intersection
+            t_far *= 1.0 + 2.0 * gamma(3);
+            t0 = if t_near > t0 { t_near } else { t0 };
+            t1 = if t_far < t1 { t_far } else { t1 };
+            if t0 > t1 {
+                return false;
+            }
+        }
+        *hitt0 = t0;
+        *hitt1 = t1;
+        true
     }
 
     pub fn intersect_p(&self, ray: &Ray, inv_dir: &Vector3f, dir_is_neg: &[u8; 3]) -> bool {
-        unimplemented!()
+        let mut t_min: Float = 0.0;
+        let mut t_max = ray.t_max.get();
+        for i in 0..3 {
+            // pick the near and far planes of the i-th slab from the ray
+            // direction
+            let t_near = (self[dir_is_neg[i] as usize][i] - ray.o[i]) * inv_dir[i];
+            let mut t_far = (self[1 - dir_is_neg[i] as usize][i] - ray.o[i]) * inv_dir[i];
+
+            // update t_far to ensure robust bounds intersection
+            t_far *= 1.0 + 2.0 * gamma(3);
+            // a ray lying in a slab plane gives NaN, which leaves the
+            // interval unchanged
+            t_min = if t_near > t_min { t_near } else { t_min };
+            t_max = if t_far < t_max { t_far } else { t_max };
+            if t_min > t_max {
+                return false;
+            }
+        }
+        true
     }
 }
 
@@ -294,24 +493,354 @@ impl From<Point3f> for Bounds3f {
     }
 }
 
-impl Index<usize> for Bounds3f {
-    type Output = Point3f;
+impl From<Bounds3i> for Bounds3f {
+    fn from(b: Bounds3i) -> Bounds3f {
+        Bounds3f {
+            p_min: Point3f::from(b.p_min),
+            p_max: Point3f::from(b.p_max),
+        }
+    }
+}
 
-    fn index(&self, i: usize) -> &Point3f {
-        match i {
-            0 => &self.p_min,
-            1 => &self.p_max,
-            _ => panic!("Bounds3f index out of bounds"),
+// Bounds3i
+impl Bounds3i {
+    pub fn new_with_points(p1: Point3i, p2: Point3i) -> Bounds3i {
+        Bounds3i {
+            p_min: p1.min(&p2),
+            p_max: p1.max(&p2),
+        }
+    }
+
+    pub fn diagonal(&self) -> Vector3i {
+        self.p_max - self.p_min
+    }
+
+    // zero for empty bounds, in u128 like Bounds2i::area
+    pub fn volume(&self) -> u128 {
+        if self.is_empty() {
+            return 0;
+        }
+        extent_i32(self.p_min.x, self.p_max.x)
+            * extent_i32(self.p_min.y, self.p_max.y)
+            * extent_i32(self.p_min.z, self.p_max.z)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.p_min.x >= self.p_max.x || self.p_min.y >= self.p_max.y || self.p_min.z >= self.p_max.z
+    }
+
+    pub fn union_point3i(&self, p: &Point3i) -> Bounds3i {
+        Bounds3i {
+            p_min: self.p_min.min(p),
+            p_max: self.p_max.max(p),
+        }
+    }
+
+    pub fn union(&self, b: &Bounds3i) -> Bounds3i {
+        Bounds3i {
+            p_min: self.p_min.min(&b.p_min),
+            p_max: self.p_max.max(&b.p_max),
+        }
+    }
+
+    pub fn intersect(&self, b: &Bounds3i) -> Bounds3i {
+        Bounds3i {
+            p_min: self.p_min.max(&b.p_min),
+            p_max: self.p_max.min(&b.p_max),
         }
     }
 }
 
-impl IndexMut<usize> for Bounds3f {
-    fn index_mut(&mut self, i: usize) -> &mut Point3f {
-        match i {
-            0 => &mut self.p_min,
-            1 => &mut self.p_max,
-            _ => panic!("Bounds3f index out of bounds"),
+impl Default for Bounds3i {
+    fn default() -> Bounds3i {
+        Bounds3i {
+            p_min: Point3i::new(i32::MAX, i32::MAX, i32::MAX),
+            p_max: Point3i::new(i32::MIN, i32::MIN, i32::MIN),
+        }
+    }
+}
+
+impl From<Point3i> for Bounds3i {
+    fn from(p: Point3i) -> Bounds3i {
+        Bounds3i { p_min: p, p_max: p }
+    }
+}
+
+// Bounds3fx4
+// four boxes in structure-of-arrays layout so that a single ray can be
+// tested against all of them at once during BVH traversal
+#[derive(Debug, Copy, Clone)]
+pub struct Bounds3fx4 {
+    pub p_min: [[Float; 4]; 3],
+    pub p_max: [[Float; 4]; 3],
+}
+
+impl Bounds3fx4 {
+    pub fn new(bounds: &[Bounds3f; 4]) -> Bounds3fx4 {
+        let mut b = Bounds3fx4 {
+            p_min: [[0.0; 4]; 3],
+            p_max: [[0.0; 4]; 3],
+        };
+        for (lane, bound) in bounds.iter().enumerate() {
+            for axis in 0..3 {
+                b.p_min[axis][lane] = bound.p_min[axis];
+                b.p_max[axis][lane] = bound.p_max[axis];
+            }
+        }
+        b
+    }
+
+    pub fn bounds(&self, lane: usize) -> Bounds3f {
+        Bounds3f {
+            p_min: Point3f::new(
+                self.p_min[0][lane],
+                self.p_min[1][lane],
+                self.p_min[2][lane],
+            ),
+            p_max: Point3f::new(
+                self.p_max[0][lane],
+                self.p_max[1][lane],
+                self.p_max[2][lane],
+            ),
+        }
+    }
+
+    // same test as Bounds3f::intersect_p, evaluated for all four boxes
+    pub fn intersect_p(&self, ray: &Ray, inv_dir: &Vector3f, dir_is_neg: &[u8; 3]) -> [bool; 4] {
+        let mut t_min = [0.0 as Float; 4];
+        let mut t_max = [ray.t_max.get(); 4];
+        for axis in 0..3 {
+            let (near, far) = if dir_is_neg[axis] == 0 {
+                (&self.p_min[axis], &self.p_max[axis])
+            } else {
+                (&self.p_max[axis], &self.p_min[axis])
+            };
+            for lane in 0..4 {
+                let t_near = (near[lane] - ray.o[axis]) * inv_dir[axis];
+                // update t_far to ensure robust bounds intersection
+                let t_far = (far[lane] - ray.o[axis]) * inv_dir[axis] * (1.0 + 2.0 * gamma(3));
+                t_min[lane] = if t_near > t_min[lane] {
+                    t_near
+                } else {
+                    t_min[lane]
+                };
+                t_max[lane] = if t_far < t_max[lane] {
+                    t_far
+                } else {
+                    t_max[lane]
+                };
+            }
+        }
+        let mut hits = [false; 4];
+        for lane in 0..4 {
+            hits[lane] = t_min[lane] <= t_max[lane];
+        }
+        hits
+    }
+}
+
+impl Default for Bounds3fx4 {
+    fn default() -> Bounds3fx4 {
+        Bounds3fx4::new(&[Bounds3f::default(); 4])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Bounds3f {
+        Bounds3f::new_with_points(Point3f::new(0.0, 0.0, 0.0), Point3f::new(1.0, 1.0, 1.0))
+    }
+
+    fn ray(o: Point3f, d: Vector3f, t_max: Float) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: std::cell::Cell::new(t_max),
+            ..Default::default()
         }
     }
+
+    // runs all three slab tests and checks that they agree
+    fn hits(b: &Bounds3f, r: &Ray) -> bool {
+        let inv_dir = Vector3f::new(1.0 / r.d.x, 1.0 / r.d.y, 1.0 / r.d.z);
+        let dir_is_neg = [
+            (inv_dir.x < 0.0) as u8,
+            (inv_dir.y < 0.0) as u8,
+            (inv_dir.z < 0.0) as u8,
+        ];
+        let (mut t0, mut t1) = (0.0, 0.0);
+        let hit_b = b.intersect_b(r, &mut t0, &mut t1);
+        let hit_p = b.intersect_p(r, &inv_dir, &dir_is_neg);
+        let hit_x4 = Bounds3fx4::new(&[*b; 4]).intersect_p(r, &inv_dir, &dir_is_neg);
+        assert_eq!(hit_b, hit_p, "intersect_b and intersect_p disagree");
+        assert_eq!(hit_x4, [hit_p; 4], "Bounds3fx4 and intersect_p disagree");
+        hit_p
+    }
+
+    #[test]
+    fn ray_in_face_plane_hits() {
+        let b = unit_box();
+        let r = ray(
+            Point3f::new(0.0, 0.5, -1.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Float::INFINITY,
+        );
+        assert!(hits(&b, &r));
+        let r = ray(
+            Point3f::new(0.5, 1.0, -1.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Float::INFINITY,
+        );
+        assert!(hits(&b, &r));
+        // parallel to a slab but outside of it
+        let r = ray(
+            Point3f::new(1.5, 0.5, -1.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Float::INFINITY,
+        );
+        assert!(!hits(&b, &r));
+    }
+
+    #[test]
+    fn ray_along_or_through_edge_hits() {
+        let b = unit_box();
+        let r = ray(
+            Point3f::new(1.0, 1.0, 2.0),
+            Vector3f::new(0.0, 0.0, -1.0),
+            Float::INFINITY,
+        );
+        assert!(hits(&b, &r));
+        // touches the edge x = 0, y = 1 at a single point
+        let r = ray(
+            Point3f::new(-0.5, 0.5, 0.5),
+            Vector3f::new(1.0, 1.0, 0.0),
+            Float::INFINITY,
+        );
+        assert!(hits(&b, &r));
+        let r = ray(
+            Point3f::new(-0.5, 0.51, 0.5),
+            Vector3f::new(1.0, 1.0, 0.0),
+            Float::INFINITY,
+        );
+        assert!(!hits(&b, &r));
+    }
+
+    #[test]
+    fn ray_from_inside_hits() {
+        let b = unit_box();
+        let r = ray(
+            Point3f::new(0.5, 0.5, 0.5),
+            Vector3f::new(0.3, -1.0, 0.2),
+            1e-3,
+        );
+        assert!(hits(&b, &r));
+        let r = ray(
+            Point3f::new(0.5, 0.5, 0.5),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Float::INFINITY,
+        );
+        assert!(hits(&b, &r));
+    }
+
+    #[test]
+    fn respects_ray_extent() {
+        let b = unit_box();
+        let r = ray(
+            Point3f::new(0.5, 0.5, -2.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            1.5,
+        );
+        assert!(!hits(&b, &r));
+        r.t_max.set(2.5);
+        assert!(hits(&b, &r));
+        // box behind the ray origin
+        let r = ray(
+            Point3f::new(0.5, 0.5, 2.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Float::INFINITY,
+        );
+        assert!(!hits(&b, &r));
+    }
+
+    #[test]
+    fn four_wide_test_matches_per_box_test() {
+        let boxes = [
+            unit_box(),
+            Bounds3f::new_with_points(Point3f::new(2.0, 0.0, 0.0), Point3f::new(3.0, 1.0, 1.0)),
+            Bounds3f::new_with_points(Point3f::new(0.0, 0.5, 0.0), Point3f::new(1.0, 0.5, 1.0)),
+            Bounds3f::new_with_points(Point3f::new(-1.0, -1.0, 4.0), Point3f::new(0.0, 0.0, 5.0)),
+        ];
+        let bx4 = Bounds3fx4::new(&boxes);
+        let mut seed: u32 = 1;
+        let mut rand = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as Float / u32::MAX as Float * 8.0 - 4.0
+        };
+        for _ in 0..1000 {
+            let r = ray(
+                Point3f::new(rand(), rand(), rand()),
+                Vector3f::new(rand(), rand(), rand()),
+                rand().abs() * 2.0,
+            );
+            let inv_dir = Vector3f::new(1.0 / r.d.x, 1.0 / r.d.y, 1.0 / r.d.z);
+            let dir_is_neg = [
+                (inv_dir.x < 0.0) as u8,
+                (inv_dir.y < 0.0) as u8,
+                (inv_dir.z < 0.0) as u8,
+            ];
+            let lanes = bx4.intersect_p(&r, &inv_dir, &dir_is_neg);
+            for (lane, b) in boxes.iter().enumerate() {
+                assert_eq!(lanes[lane], hits(b, &r));
+                assert_eq!(bx4.bounds(lane).p_min, b.p_min);
+                assert_eq!(bx4.bounds(lane).p_max, b.p_max);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_bounds_measure_without_overflow() {
+        let full2 = Bounds2i::new_with_points(
+            Point2i::new(i32::MIN, i32::MIN),
+            Point2i::new(i32::MAX, i32::MAX),
+        );
+        let extent = u32::MAX as u128;
+        assert_eq!(full2.area(), extent * extent);
+        let full3 = Bounds3i::new_with_points(
+            Point3i::new(i32::MIN, i32::MIN, i32::MIN),
+            Point3i::new(i32::MAX, i32::MAX, i32::MAX),
+        );
+        assert_eq!(full3.volume(), extent * extent * extent);
+        let large = Bounds3i::new_with_points(
+            Point3i::new(-2_000_000, -2_000_000, -2_000_000),
+            Point3i::new(2_000_000, 2_000_000, 2_000_000),
+        );
+        assert_eq!(large.volume(), 4_000_000u128.pow(3));
+        assert_eq!(Bounds2i::default().area(), 0);
+        assert_eq!(Bounds3i::default().volume(), 0);
+        let (low, high) = full2.into_iter().size_hint();
+        match usize::try_from(full2.area()) {
+            Ok(area) => assert_eq!((low, high), (area, Some(area))),
+            Err(_) => assert_eq!((low, high), (usize::MAX, None)),
+        }
+    }
+
+    #[test]
+    fn iterates_over_integer_points() {
+        let b = Bounds2i::new_with_points(Point2i::new(-1, 2), Point2i::new(2, 4));
+        let points: Vec<Point2i> = b.into_iter().collect();
+        assert_eq!(points.len() as u128, b.area());
+        assert_eq!(points[0], Point2i::new(-1, 2));
+        assert_eq!(points[1], Point2i::new(0, 2));
+        assert_eq!(points[3], Point2i::new(-1, 3));
+        assert_eq!(points[5], Point2i::new(1, 3));
+        let mut it = b.into_iter();
+        it.next();
+        assert_eq!(it.size_hint(), (5, Some(5)));
+        let empty = Bounds2i::new_with_points(Point2i::new(0, 0), Point2i::new(3, 0));
+        assert_eq!(empty.into_iter().count(), 0);
+    }
 }
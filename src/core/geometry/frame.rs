@@ -0,0 +1,134 @@
+use super::{
+    normal::Normal3f,
+    transform::{Matrix4x4, Transform},
+    vector::{coordinate_system, Vector3f},
+};
+
+// Frame
+// an orthonormal basis, to_local maps x, y and z onto the coordinate axes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frame {
+    pub x: Vector3f,
+    pub y: Vector3f,
+    pub z: Vector3f,
+}
+
+impl Frame {
+    pub fn new(x: Vector3f, y: Vector3f, z: Vector3f) -> Frame {
+        Frame { x, y, z }
+    }
+
+    pub fn from_xz(x: &Vector3f, z: &Vector3f) -> Frame {
+        Frame::new(*x, z.cross(x), *z)
+    }
+
+    pub fn from_xy(x: &Vector3f, y: &Vector3f) -> Frame {
+        Frame::new(*x, *y, x.cross(y))
+    }
+
+    pub fn from_x(x: &Vector3f) -> Frame {
+        let (y, z) = coordinate_system(x);
+        Frame::new(*x, y, z)
+    }
+
+    pub fn from_y(y: &Vector3f) -> Frame {
+        let (z, x) = coordinate_system(y);
+        Frame::new(x, *y, z)
+    }
+
+    pub fn from_z(z: &Vector3f) -> Frame {
+        let (x, y) = coordinate_system(z);
+        Frame::new(x, y, *z)
+    }
+
+    pub fn from_normal3f(n: &Normal3f) -> Frame {
+        Frame::from_z(&Vector3f::from(*n))
+    }
+
+    pub fn to_local(&self, v: &Vector3f) -> Vector3f {
+        Vector3f {
+            x: v.dot(&self.x),
+            y: v.dot(&self.y),
+            z: v.dot(&self.z),
+        }
+    }
+
+    pub fn to_local_normal3f(&self, n: &Normal3f) -> Normal3f {
+        let v = Vector3f::from(*n);
+        Normal3f {
+            x: v.dot(&self.x),
+            y: v.dot(&self.y),
+            z: v.dot(&self.z),
+        }
+    }
+
+    pub fn from_local(&self, v: &Vector3f) -> Vector3f {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+
+    pub fn from_local_normal3f(&self, n: &Normal3f) -> Normal3f {
+        Normal3f::from(self.x * n.x + self.y * n.y + self.z * n.z)
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Frame {
+        Frame {
+            x: Vector3f::new(1.0, 0.0, 0.0),
+            y: Vector3f::new(0.0, 1.0, 0.0),
+            z: Vector3f::new(0.0, 0.0, 1.0),
+        }
+    }
+}
+
+// transform from world space into the frame's local space
+impl From<Frame> for Transform {
+    fn from(f: Frame) -> Transform {
+        let m = Matrix4x4::new([
+            [f.x.x, f.x.y, f.x.z, 0.0],
+            [f.y.x, f.y.y, f.y.z, 0.0],
+            [f.z.x, f.z.y, f.z.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new_with_inverse(m, m.transpose())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_orthonormal() {
+        for z in [
+            Vector3f::new(0.0, 1.0, 0.0),
+            Vector3f::new(0.0, -1.0, 0.0),
+            Vector3f::new(0.0, 0.0, -1.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Vector3f::new(1.0, 2.0, -3.0).normalize(),
+        ] {
+            let f = Frame::from_z(&z);
+            for (a, b) in [(f.x, f.y), (f.y, f.z), (f.z, f.x)] {
+                assert!(a.dot(&b).abs() < 1e-6);
+                assert!((a.length() - 1.0).abs() < 1e-6);
+            }
+            assert!((f.x.cross(&f.y) - f.z).length() < 1e-6);
+            assert!((f.z - z).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn local_round_trip() {
+        let f = Frame::from_xz(
+            &Vector3f::new(1.0, 1.0, 0.0).normalize(),
+            &Vector3f::new(0.0, 0.0, 1.0),
+        );
+        let v = Vector3f::new(0.3, -2.0, 5.0);
+        assert!((f.from_local(&f.to_local(&v)) - v).length() < 1e-5);
+        let local = f.to_local(&Vector3f::new(0.0, 0.0, 2.0));
+        assert!((local - Vector3f::new(0.0, 0.0, 2.0)).length() < 1e-6);
+        let n = Normal3f::new(0.0, 1.0, 0.0);
+        let back = f.from_local_normal3f(&f.to_local_normal3f(&n));
+        assert!((Vector3f::from(back) - Vector3f::from(n)).length() < 1e-6);
+    }
+}
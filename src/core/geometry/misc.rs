@@ -1,6 +1,6 @@
 use super::{
-    bounds::{Bounds2f, Bounds3f},
-    point::{Point2f, Point3f},
+    bounds::{Bounds2f, Bounds2i, Bounds3f, Bounds3i},
+    point::{Point2f, Point2i, Point3f, Point3i},
     vector::{Vector2f, Vector3f},
 };
 
@@ -44,3 +44,16 @@ pub fn point3f_inside_exclusive_bounds3f(p: &Point3f, b: &Bounds3f) -> bool {
         && p.z >= b.p_min.z
         && p.z < b.p_max.z
 }
+
+pub fn point2i_inside_exclusive_bounds2i(p: &Point2i, b: &Bounds2i) -> bool {
+    p.x >= b.p_min.x && p.x < b.p_max.x && p.y >= b.p_min.y && p.y < b.p_max.y
+}
+
+pub fn point3i_inside_exclusive_bounds3i(p: &Point3i, b: &Bounds3i) -> bool {
+    p.x >= b.p_min.x
+        && p.x < b.p_max.x
+        && p.y >= b.p_min.y
+        && p.y < b.p_max.y
+        && p.z >= b.p_min.z
+        && p.z < b.p_max.z
+}
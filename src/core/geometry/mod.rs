@@ -1,6 +1,10 @@
 pub mod bounds;
+pub mod frame;
 pub mod misc;
 pub mod normal;
 pub mod point;
+pub mod quaternion;
 pub mod ray;
+pub mod spherical;
+pub mod transform;
 pub mod vector;
@@ -6,6 +6,7 @@ use super::vector::Vector3f;
 
 // Normal3f
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Normal3f {
     pub x: Float,
     pub y: Float,
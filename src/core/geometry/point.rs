@@ -1,49 +1,71 @@
 use std::ops;
 use std::ops::{Index, IndexMut};
 
+use crate::core::interval::Interval;
 use crate::core::pbrt::Float;
 
-use super::vector::{Vector2f, Vector3f};
+use super::vector::{Vector2f, Vector2i, Vector3f, Vector3fi, Vector3i};
 
-// Point2f
+// Point2
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
-pub struct Point2f {
-    pub x: Float,
-    pub y: Float,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point2<T> {
+    pub x: T,
+    pub y: T,
 }
 
-impl Point2f {
-    pub fn new(x: Float, y: Float) -> Point2f {
-        Point2f { x, y }
-    }
-
-    pub fn has_nans(&self) -> bool {
-        self.x.is_nan() || self.y.is_nan()
-    }
+pub type Point2f = Point2<Float>;
+pub type Point2i = Point2<i32>;
 
-    pub fn distance(&self, p: &Point2f) -> Float {
-        (*self - *p).length()
+impl<T> Point2<T> {
+    pub fn new(x: T, y: T) -> Point2<T> {
+        Point2 { x, y }
     }
 }
 
-impl Index<usize> for Point2f {
-    type Output = Float;
+impl<T> Index<usize> for Point2<T> {
+    type Output = T;
 
-    fn index(&self, i: usize) -> &Float {
+    fn index(&self, i: usize) -> &T {
         match i {
             0 => &self.x,
             1 => &self.y,
-            _ => panic!("Point2f index out of bounds"),
+            _ => panic!("Point2 index out of bounds"),
         }
     }
 }
 
-impl IndexMut<usize> for Point2f {
-    fn index_mut(&mut self, i: usize) -> &mut Float {
+impl<T> IndexMut<usize> for Point2<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
         match i {
             0 => &mut self.x,
             1 => &mut self.y,
-            _ => panic!("Point2f index out of bounds"),
+            _ => panic!("Point2 index out of bounds"),
+        }
+    }
+}
+
+// Point2f
+impl Point2f {
+    pub fn has_nans(&self) -> bool {
+        self.x.is_nan() || self.y.is_nan()
+    }
+
+    pub fn distance(&self, p: &Point2f) -> Float {
+        (*self - *p).length()
+    }
+
+    pub fn floor(&self) -> Point2f {
+        Point2f {
+            x: self.x.floor(),
+            y: self.y.floor(),
+        }
+    }
+
+    pub fn ceil(&self) -> Point2f {
+        Point2f {
+            x: self.x.ceil(),
+            y: self.y.ceil(),
         }
     }
 }
@@ -54,6 +76,15 @@ impl From<Point3f> for Point2f {
     }
 }
 
+impl From<Point2i> for Point2f {
+    fn from(p: Point2i) -> Point2f {
+        Point2f {
+            x: p.x as Float,
+            y: p.y as Float,
+        }
+    }
+}
+
 impl_op_ex!(+ |a: &Point2f, b: &Point2f| -> Point2f {
     Point2f {
         x: a.x + b.x,
@@ -109,20 +140,109 @@ impl_op_ex!(/= |a: &mut Point2f, b: Float| {
     a.y *= inv;
 });
 
-// Point3f
+// Point2i
+impl Point2i {
+    pub fn min(&self, p: &Point2i) -> Point2i {
+        Point2i {
+            x: self.x.min(p.x),
+            y: self.y.min(p.y),
+        }
+    }
+
+    pub fn max(&self, p: &Point2i) -> Point2i {
+        Point2i {
+            x: self.x.max(p.x),
+            y: self.y.max(p.y),
+        }
+    }
+}
 
+// truncates towards zero, use floor() or ceil() first to round
+impl From<Point2f> for Point2i {
+    fn from(p: Point2f) -> Point2i {
+        Point2i {
+            x: p.x as i32,
+            y: p.y as i32,
+        }
+    }
+}
+
+impl_op_ex!(+ |a: &Point2i, b: &Vector2i| -> Point2i {
+    Point2i {
+        x: a.x + b.x,
+        y: a.y + b.y,
+    }
+});
+
+impl_op_ex!(+= |a: &mut Point2i, b: &Vector2i| {
+    a.x += b.x;
+    a.y += b.y;
+});
+
+impl_op_ex!(-|a: &Point2i, b: &Point2i| -> Vector2i {
+    Vector2i {
+        x: a.x - b.x,
+        y: a.y - b.y,
+    }
+});
+
+impl_op_ex!(-|a: &Point2i, b: &Vector2i| -> Point2i {
+    Point2i {
+        x: a.x - b.x,
+        y: a.y - b.y,
+    }
+});
+
+impl_op_ex!(-= |a: &mut Point2i, b: &Vector2i| {
+    a.x -= b.x;
+    a.y -= b.y;
+});
+
+// Point3
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
-pub struct Point3f {
-    pub x: Float,
-    pub y: Float,
-    pub z: Float,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
 }
 
-impl Point3f {
-    pub fn new(x: Float, y: Float, z: Float) -> Point3f {
-        Point3f { x, y, z }
+pub type Point3f = Point3<Float>;
+pub type Point3i = Point3<i32>;
+pub type Point3fi = Point3<Interval>;
+
+impl<T> Point3<T> {
+    pub fn new(x: T, y: T, z: T) -> Point3<T> {
+        Point3 { x, y, z }
+    }
+}
+
+impl<T> Index<usize> for Point3<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Point3 index out of bounds"),
+        }
     }
+}
+
+impl<T> IndexMut<usize> for Point3<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        match i {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Point3 index out of bounds"),
+        }
+    }
+}
 
+// Point3f
+impl Point3f {
     pub fn has_nans(&self) -> bool {
         self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
     }
@@ -194,26 +314,12 @@ impl From<Vector3f> for Point3f {
     }
 }
 
-impl Index<usize> for Point3f {
-    type Output = Float;
-
-    fn index(&self, i: usize) -> &Float {
-        match i {
-            0 => &self.x,
-            1 => &self.y,
-            2 => &self.z,
-            _ => panic!("Point3f index out of bounds"),
-        }
-    }
-}
-
-impl IndexMut<usize> for Point3f {
-    fn index_mut(&mut self, i: usize) -> &mut Float {
-        match i {
-            0 => &mut self.x,
-            1 => &mut self.y,
-            2 => &mut self.z,
-            _ => panic!("Point3f index out of bounds"),
+impl From<Point3i> for Point3f {
+    fn from(p: Point3i) -> Point3f {
+        Point3f {
+            x: p.x as Float,
+            y: p.y as Float,
+            z: p.z as Float,
         }
     }
 }
@@ -316,6 +422,171 @@ impl_op_ex!(+= |a: &mut Point2f, b: &Vector2f| {
     a.y += b.y;
 });
 
+// Point3i
+impl Point3i {
+    pub fn min(&self, p: &Point3i) -> Point3i {
+        Point3i {
+            x: self.x.min(p.x),
+            y: self.y.min(p.y),
+            z: self.z.min(p.z),
+        }
+    }
+
+    pub fn max(&self, p: &Point3i) -> Point3i {
+        Point3i {
+            x: self.x.max(p.x),
+            y: self.y.max(p.y),
+            z: self.z.max(p.z),
+        }
+    }
+}
+
+// truncates towards zero, use floor() or ceil() first to round
+impl From<Point3f> for Point3i {
+    fn from(p: Point3f) -> Point3i {
+        Point3i {
+            x: p.x as i32,
+            y: p.y as i32,
+            z: p.z as i32,
+        }
+    }
+}
+
+impl_op_ex!(+ |a: &Point3i, b: &Vector3i| -> Point3i {
+    Point3i {
+        x: a.x + b.x,
+        y: a.y + b.y,
+        z: a.z + b.z,
+    }
+});
+
+impl_op_ex!(+= |a: &mut Point3i, b: &Vector3i| {
+    a.x += b.x;
+    a.y += b.y;
+    a.z += b.z;
+});
+
+impl_op_ex!(-|a: &Point3i, b: &Point3i| -> Vector3i {
+    Vector3i {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+});
+
+impl_op_ex!(-|a: &Point3i, b: &Vector3i| -> Point3i {
+    Point3i {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+});
+
+// Point3fi
+impl Point3fi {
+    pub fn new_with_error(p: &Point3f, e: &Vector3f) -> Point3fi {
+        Point3fi {
+            x: Interval::from_value_and_error(p.x, e.x),
+            y: Interval::from_value_and_error(p.y, e.y),
+            z: Interval::from_value_and_error(p.z, e.z),
+        }
+    }
+
+    // half the width of each component's interval
+    pub fn error(&self) -> Vector3f {
+        self.width() / 2.0
+    }
+
+    pub fn width(&self) -> Vector3f {
+        Vector3f {
+            x: self.x.width(),
+            y: self.y.width(),
+            z: self.z.width(),
+        }
+    }
+
+    pub fn midpoint(&self) -> Point3f {
+        Point3f {
+            x: self.x.midpoint(),
+            y: self.y.midpoint(),
+            z: self.z.midpoint(),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.x.width() == 0.0 && self.y.width() == 0.0 && self.z.width() == 0.0
+    }
+}
+
+impl From<Point3f> for Point3fi {
+    fn from(p: Point3f) -> Point3fi {
+        Point3fi {
+            x: Interval::from(p.x),
+            y: Interval::from(p.y),
+            z: Interval::from(p.z),
+        }
+    }
+}
+
+// collapses each component to its midpoint
+impl From<Point3fi> for Point3f {
+    fn from(p: Point3fi) -> Point3f {
+        p.midpoint()
+    }
+}
+
+impl_op_ex!(+ |a: &Point3fi, b: &Vector3fi| -> Point3fi {
+    Point3fi {
+        x: a.x + b.x,
+        y: a.y + b.y,
+        z: a.z + b.z,
+    }
+});
+
+impl_op_ex!(+= |a: &mut Point3fi, b: &Vector3fi| {
+    a.x += b.x;
+    a.y += b.y;
+    a.z += b.z;
+});
+
+impl_op_ex!(-|a: &Point3fi, b: &Point3fi| -> Vector3fi {
+    Vector3fi {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+});
+
+impl_op_ex!(-|a: &Point3fi, b: &Vector3fi| -> Point3fi {
+    Point3fi {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+});
+
+impl_op_ex!(-= |a: &mut Point3fi, b: &Vector3fi| {
+    a.x -= b.x;
+    a.y -= b.y;
+    a.z -= b.z;
+});
+
+impl_op_ex!(*|a: &Point3fi, b: &Interval| -> Point3fi {
+    Point3fi {
+        x: a.x * b,
+        y: a.y * b,
+        z: a.z * b,
+    }
+});
+
+impl_op_ex!(*|a: &Point3fi, b: Float| -> Point3fi {
+    Point3fi {
+        x: a.x * b,
+        y: a.y * b,
+        z: a.z * b,
+    }
+});
+
 pub fn lerp(t: Float, p0: &Point3f, p1: &Point3f) -> Point3f {
     (1.0 as Float - t) * *p0 + t * *p1
 }
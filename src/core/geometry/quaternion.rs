@@ -0,0 +1,300 @@
+use std::ops;
+
+use crate::core::pbrt::{clamp, radians, safe_asin, Float, PI};
+
+use super::{
+    transform::{Matrix4x4, Transform},
+    vector::Vector3f,
+};
+
+// Quaternion
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    pub v: Vector3f,
+    pub w: Float,
+}
+
+impl Quaternion {
+    pub fn new(v: Vector3f, w: Float) -> Quaternion {
+        Quaternion { v, w }
+    }
+
+    // rotation of theta degrees about axis, matching Transform::rotate
+    pub fn from_axis_angle(theta: Float, axis: &Vector3f) -> Quaternion {
+        let (sin_half, cos_half) = (radians(theta) * 0.5).sin_cos();
+        Quaternion {
+            v: axis.normalize() * sin_half,
+            w: cos_half,
+        }
+    }
+
+    pub fn dot(&self, q: &Quaternion) -> Float {
+        self.v.dot(&q.v) + self.w * q.w
+    }
+
+    pub fn length_squared(&self) -> Float {
+        self.dot(self)
+    }
+
+    pub fn length(&self) -> Float {
+        self.length_squared().sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        *self / self.length()
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            v: -self.v,
+            w: self.w,
+        }
+    }
+
+    pub fn inverse(&self) -> Quaternion {
+        self.conjugate() / self.length_squared()
+    }
+
+    pub fn angle_between(&self, q: &Quaternion) -> Float {
+        if self.dot(q) < 0.0 {
+            PI - 2.0 * safe_asin((*self + *q).length() / 2.0)
+        } else {
+            2.0 * safe_asin((*q - *self).length() / 2.0)
+        }
+    }
+
+    pub fn rotate_vector3f(&self, v: &Vector3f) -> Vector3f {
+        (*self * Quaternion::new(*v, 0.0) * self.conjugate()).v
+    }
+
+    pub fn slerp(t: Float, q1: &Quaternion, q2: &Quaternion) -> Quaternion {
+        let cos_theta = q1.dot(q2);
+        if cos_theta > 0.9995 {
+            // nearly parallel, fall back to linear interpolation
+            (*q1 * (1.0 - t) + *q2 * t).normalize()
+        } else {
+            let theta = clamp(cos_theta, -1.0, 1.0).acos();
+            let thetap = theta * t;
+            let qperp = (*q2 - *q1 * cos_theta).normalize();
+            *q1 * thetap.cos() + qperp * thetap.sin()
+        }
+    }
+
+    pub fn to_matrix(&self) -> Matrix4x4 {
+        let (x, y, z, w) = (self.v.x, self.v.y, self.v.z, self.w);
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (x * w, y * w, z * w);
+
+        let mut m = Matrix4x4::default();
+        m.m[0][0] = 1.0 - 2.0 * (yy + zz);
+        m.m[0][1] = 2.0 * (xy + wz);
+        m.m[0][2] = 2.0 * (xz - wy);
+        m.m[1][0] = 2.0 * (xy - wz);
+        m.m[1][1] = 1.0 - 2.0 * (xx + zz);
+        m.m[1][2] = 2.0 * (yz + wx);
+        m.m[2][0] = 2.0 * (xz + wy);
+        m.m[2][1] = 2.0 * (yz - wx);
+        m.m[2][2] = 1.0 - 2.0 * (xx + yy);
+
+        // transpose since we are left-handed
+        m.transpose()
+    }
+
+    pub fn to_transform(&self) -> Transform {
+        let m = self.to_matrix();
+        Transform::new_with_inverse(m, m.transpose())
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Quaternion {
+        Quaternion {
+            v: Vector3f::default(),
+            w: 1.0,
+        }
+    }
+}
+
+impl From<Transform> for Quaternion {
+    fn from(t: Transform) -> Quaternion {
+        Quaternion::from(t.m)
+    }
+}
+
+// assumes the upper 3x3 of m is a pure rotation
+impl From<Matrix4x4> for Quaternion {
+    fn from(m: Matrix4x4) -> Quaternion {
+        let m = &m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > 0.0 {
+            // compute w from matrix trace, then xyz
+            let mut s = (trace + 1.0).sqrt();
+            let w = s / 2.0;
+            s = 0.5 / s;
+            Quaternion {
+                v: Vector3f {
+                    x: (m[2][1] - m[1][2]) * s,
+                    y: (m[0][2] - m[2][0]) * s,
+                    z: (m[1][0] - m[0][1]) * s,
+                },
+                w,
+            }
+        } else {
+            // compute largest of x, y, or z, then remaining components
+            let nxt = [1, 2, 0];
+            let mut q = [0.0 as Float; 3];
+            let mut i = 0;
+            if m[1][1] > m[0][0] {
+                i = 1;
+            }
+            if m[2][2] > m[i][i] {
+                i = 2;
+            }
+            let j = nxt[i];
+            let k = nxt[j];
+            let mut s = ((m[i][i] - (m[j][j] + m[k][k])) + 1.0).sqrt();
+            q[i] = s * 0.5;
+            if s != 0.0 {
+                s = 0.5 / s;
+            }
+            let w = (m[k][j] - m[j][k]) * s;
+            q[j] = (m[j][i] + m[i][j]) * s;
+            q[k] = (m[k][i] + m[i][k]) * s;
+            Quaternion {
+                v: Vector3f {
+                    x: q[0],
+                    y: q[1],
+                    z: q[2],
+                },
+                w,
+            }
+        }
+    }
+}
+
+impl_op_ex!(+ |a: &Quaternion, b: &Quaternion| -> Quaternion {
+    Quaternion {
+        v: a.v + b.v,
+        w: a.w + b.w,
+    }
+});
+
+impl From<Quaternion> for Matrix4x4 {
+    fn from(q: Quaternion) -> Matrix4x4 {
+        q.to_matrix()
+    }
+}
+
+impl_op_ex!(+= |a: &mut Quaternion, b: &Quaternion| {
+    a.v += b.v;
+    a.w += b.w;
+});
+
+impl_op_ex!(-|a: &Quaternion, b: &Quaternion| -> Quaternion {
+    Quaternion {
+        v: a.v - b.v,
+        w: a.w - b.w,
+    }
+});
+
+impl_op_ex!(-= |a: &mut Quaternion, b: &Quaternion| {
+    a.v -= b.v;
+    a.w -= b.w;
+});
+
+// Hamilton product, a * b applies the rotation b first
+impl_op_ex!(*|a: &Quaternion, b: &Quaternion| -> Quaternion {
+    Quaternion {
+        v: b.v * a.w + a.v * b.w + a.v.cross(&b.v),
+        w: a.w * b.w - a.v.dot(&b.v),
+    }
+});
+
+impl_op_ex!(*|a: &Quaternion, b: Float| -> Quaternion {
+    Quaternion {
+        v: a.v * b,
+        w: a.w * b,
+    }
+});
+
+impl_op_ex!(*|a: Float, b: &Quaternion| -> Quaternion {
+    Quaternion {
+        v: b.v * a,
+        w: a * b.w,
+    }
+});
+
+impl_op_ex!(*= |a: &mut Quaternion, b: Float| {
+    a.v *= b;
+    a.w *= b;
+});
+
+impl_op_ex!(/ |a: &Quaternion, b: Float| -> Quaternion {
+    Quaternion {
+        v: a.v / b,
+        w: a.w / b,
+    }
+});
+
+impl_op_ex!(/= |a: &mut Quaternion, b: Float| {
+    a.v /= b;
+    a.w /= b;
+});
+
+impl_op_ex!(-|a: &Quaternion| -> Quaternion { Quaternion { v: -a.v, w: -a.w } });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Vector3f, b: &Vector3f) {
+        assert!((*a - *b).length() < 1e-5, "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let axis = Vector3f::new(1.0, 2.0, -1.0);
+        for theta in [0.0, 30.0, 100.0, 179.0, 250.0] {
+            let t = Transform::rotate(theta, &axis);
+            let q = Quaternion::from(t);
+            let v = Vector3f::new(0.3, -1.0, 2.0);
+            assert_close(
+                &q.to_transform().transform_vector3f(&v),
+                &t.transform_vector3f(&v),
+            );
+            assert_close(&q.rotate_vector3f(&v), &t.transform_vector3f(&v));
+            let q_axis = Quaternion::from_axis_angle(theta, &axis);
+            assert_close(&q_axis.rotate_vector3f(&v), &t.transform_vector3f(&v));
+        }
+    }
+
+    #[test]
+    fn slerp_turns_at_constant_speed() {
+        let axis = Vector3f::new(0.0, 0.0, 1.0);
+        let q1 = Quaternion::from_axis_angle(10.0, &axis);
+        let q2 = Quaternion::from_axis_angle(130.0, &axis);
+        let v = Vector3f::new(1.0, 0.0, 0.0);
+        for (t, theta) in [(0.0, 10.0), (0.25, 40.0), (0.5, 70.0), (1.0, 130.0)] {
+            let q = Quaternion::slerp(t, &q1, &q2);
+            assert!((q.length() - 1.0).abs() < 1e-5);
+            assert_close(
+                &q.rotate_vector3f(&v),
+                &Quaternion::from_axis_angle(theta, &axis).rotate_vector3f(&v),
+            );
+        }
+        assert!((q1.angle_between(&q2) - radians(60.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn product_composes_rotations() {
+        let a = Quaternion::from_axis_angle(90.0, &Vector3f::new(1.0, 0.0, 0.0));
+        let b = Quaternion::from_axis_angle(90.0, &Vector3f::new(0.0, 1.0, 0.0));
+        let v = Vector3f::new(0.0, 0.0, 1.0);
+        assert_close(
+            &(a * b).rotate_vector3f(&v),
+            &a.rotate_vector3f(&b.rotate_vector3f(&v)),
+        );
+        assert_close(&(a * a.inverse()).v, &Vector3f::default());
+    }
+}
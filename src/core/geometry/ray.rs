@@ -1,16 +1,26 @@
 use std::{cell::Cell, sync::Arc};
 
-use crate::core::{medium::Medium, pbrt::Float};
+use crate::core::{
+    medium::Medium,
+    pbrt::{next_float_down, next_float_up, Float, SHADOW_EPSILON},
+};
 
-use super::{point::Point3f, vector::Vector3f};
+use super::{
+    normal::Normal3f,
+    point::{Point3f, Point3fi},
+    vector::Vector3f,
+};
 
 // Ray
 #[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ray {
     pub o: Point3f,
     pub d: Vector3f,
     pub t_max: Cell<Float>,
     pub time: Float,
+    // media are scene state rather than geometry, a deserialized ray has none
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub medium: Option<Arc<Medium>>,
     pub differential: Option<RayDifferential>,
 }
@@ -35,9 +45,134 @@ impl Ray {
 }
 
 #[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RayDifferential {
     pub rx_origin: Point3f,
     pub ry_origin: Point3f,
     pub rx_direction: Vector3f,
     pub ry_direction: Vector3f,
 }
+
+// moves p along n far enough to leave its error bounds, onto the side w
+// points to, so that a ray leaving in direction w cannot hit the surface p
+// lies on again
+pub fn offset_ray_origin(pi: &Point3fi, n: &Normal3f, w: &Vector3f) -> Point3f {
+    let d = Vector3f::from(n.abs()).dot(&pi.error());
+    let n = Vector3f::from(*n);
+    let mut offset = n * d;
+    if w.dot(&n) < 0.0 {
+        offset = -offset;
+    }
+    let mut po = Point3f::from(*pi) + offset;
+    // round offset point po away from p
+    for i in 0..3 {
+        if offset[i] > 0.0 {
+            po[i] = next_float_up(po[i]);
+        } else if offset[i] < 0.0 {
+            po[i] = next_float_down(po[i]);
+        }
+    }
+    po
+}
+
+pub fn spawn_ray(pi: &Point3fi, n: &Normal3f, time: Float, d: &Vector3f) -> Ray {
+    Ray {
+        o: offset_ray_origin(pi, n, d),
+        d: *d,
+        t_max: Cell::new(Float::INFINITY),
+        time,
+        medium: None,
+        differential: None,
+    }
+}
+
+// the ray reaches p_to at t = 1, t_max stops just short of it
+pub fn spawn_ray_to_point3f(p_from: &Point3fi, n: &Normal3f, time: Float, p_to: &Point3f) -> Ray {
+    let o = offset_ray_origin(p_from, n, &(*p_to - Point3f::from(*p_from)));
+    Ray {
+        o,
+        d: *p_to - o,
+        t_max: Cell::new(1.0 - SHADOW_EPSILON),
+        time,
+        medium: None,
+        differential: None,
+    }
+}
+
+// offsets both end points, for rays between two surfaces
+pub fn spawn_ray_to_point3fi(
+    p_from: &Point3fi,
+    n_from: &Normal3f,
+    time: Float,
+    p_to: &Point3fi,
+    n_to: &Normal3f,
+) -> Ray {
+    let pf = offset_ray_origin(
+        p_from,
+        n_from,
+        &(Point3f::from(*p_to) - Point3f::from(*p_from)),
+    );
+    let pt = offset_ray_origin(p_to, n_to, &(pf - Point3f::from(*p_to)));
+    Ray {
+        o: pf,
+        d: pt - pf,
+        t_max: Cell::new(1.0 - SHADOW_EPSILON),
+        time,
+        medium: None,
+        differential: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_leaves_error_bounds_on_the_side_of_w() {
+        let pi = Point3fi::new_with_error(
+            &Point3f::new(1.0, 2.0, 3.0),
+            &Vector3f::new(1e-3, 1e-3, 1e-3),
+        );
+        let n = Normal3f::new(0.0, 0.0, 1.0);
+        let up = offset_ray_origin(&pi, &n, &Vector3f::new(0.3, 0.0, 1.0));
+        assert!(up.z > 3.0 + 1e-3);
+        assert_eq!((up.x, up.y), (1.0, 2.0));
+        let down = offset_ray_origin(&pi, &n, &Vector3f::new(0.0, 0.3, -1.0));
+        assert!(down.z < 3.0 - 1e-3);
+    }
+
+    #[test]
+    fn spawned_rays_stop_short_of_the_target() {
+        let from = Point3fi::new_with_error(&Point3f::default(), &Vector3f::new(1e-4, 1e-4, 1e-4));
+        let n = Normal3f::new(0.0, 0.0, 1.0);
+        let to = Point3f::new(0.0, 0.0, 2.0);
+        let ray = spawn_ray_to_point3f(&from, &n, 0.5, &to);
+        assert_eq!(ray.position(1.0), to);
+        assert!(ray.t_max.get() < 1.0);
+        assert_eq!(ray.time, 0.5);
+        let ray = spawn_ray(&from, &n, 0.0, &Vector3f::new(0.0, 0.0, -1.0));
+        assert!(ray.o.z < 0.0);
+        assert_eq!(ray.t_max.get(), Float::INFINITY);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn ray_round_trips_without_medium() {
+        let ray = Ray {
+            o: Point3f::new(1.0, 2.0, 3.0),
+            d: Vector3f::new(0.0, 0.0, -1.0),
+            t_max: Cell::new(5.0),
+            time: 0.25,
+            medium: Some(Arc::new(Medium::Empty(crate::core::medium::NoMedium {}))),
+            differential: Some(RayDifferential::default()),
+        };
+        let json = serde_json::to_string(&ray).unwrap();
+        let back: Ray = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.o, ray.o);
+        assert_eq!(back.d, ray.d);
+        assert_eq!(back.t_max.get(), 5.0);
+        assert_eq!(back.time, 0.25);
+        assert!(back.medium.is_none());
+        assert!(back.differential.is_some());
+    }
+}
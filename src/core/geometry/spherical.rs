@@ -0,0 +1,289 @@
+use crate::core::pbrt::{clamp, degrees, safe_acos, safe_sqrt, Float, PI, PI_OVER_2, PI_OVER_4};
+
+use super::{
+    bounds::Bounds3f,
+    point::{Point2f, Point3f},
+    transform::Transform,
+    vector::Vector3f,
+};
+
+pub fn spherical_direction(sin_theta: Float, cos_theta: Float, phi: Float) -> Vector3f {
+    let sin_theta = clamp(sin_theta, -1.0, 1.0);
+    Vector3f {
+        x: sin_theta * phi.cos(),
+        y: sin_theta * phi.sin(),
+        z: clamp(cos_theta, -1.0, 1.0),
+    }
+}
+
+// spherical direction with respect to the coordinate system x, y, z
+pub fn spherical_direction_in_frame(
+    sin_theta: Float,
+    cos_theta: Float,
+    phi: Float,
+    x: &Vector3f,
+    y: &Vector3f,
+    z: &Vector3f,
+) -> Vector3f {
+    *x * (sin_theta * phi.cos()) + *y * (sin_theta * phi.sin()) + *z * cos_theta
+}
+
+pub fn spherical_theta(v: &Vector3f) -> Float {
+    safe_acos(v.z)
+}
+
+pub fn spherical_phi(v: &Vector3f) -> Float {
+    let p = v.y.atan2(v.x);
+    if p < 0.0 {
+        p + 2.0 * PI
+    } else {
+        p
+    }
+}
+
+// area of the spherical triangle given by three normalized directions
+pub fn spherical_triangle_area(a: &Vector3f, b: &Vector3f, c: &Vector3f) -> Float {
+    (2.0 * a
+        .dot(&b.cross(c))
+        .atan2(1.0 + a.dot(b) + a.dot(c) + b.dot(c)))
+    .abs()
+}
+
+// area of the spherical quadrilateral given by four normalized directions
+pub fn spherical_quad_area(a: &Vector3f, b: &Vector3f, c: &Vector3f, d: &Vector3f) -> Float {
+    let axb = a.cross(b);
+    let bxc = b.cross(c);
+    let cxd = c.cross(d);
+    let dxa = d.cross(a);
+    if axb.length_squared() == 0.0
+        || bxc.length_squared() == 0.0
+        || cxd.length_squared() == 0.0
+        || dxa.length_squared() == 0.0
+    {
+        return 0.0;
+    }
+    let axb = axb.normalize();
+    let bxc = bxc.normalize();
+    let cxd = cxd.normalize();
+    let dxa = dxa.normalize();
+
+    let alpha = dxa.angle_between(&-axb);
+    let beta = axb.angle_between(&-bxc);
+    let gamma = bxc.angle_between(&-cxd);
+    let delta = cxd.angle_between(&-dxa);
+    (alpha + beta + gamma + delta - 2.0 * PI).abs()
+}
+
+// Clarberg's equal-area mapping from [0,1]^2 to the unit sphere
+pub fn equal_area_square_to_sphere(p: &Point2f) -> Vector3f {
+    // transform p to [-1,1]^2 and compute absolute values
+    let u = 2.0 * p.x - 1.0;
+    let v = 2.0 * p.y - 1.0;
+    let up = u.abs();
+    let vp = v.abs();
+
+    // compute radius r as signed distance from diagonal
+    let signed_distance = 1.0 - (up + vp);
+    let d = signed_distance.abs();
+    let r = 1.0 - d;
+
+    // compute angle phi for square to sphere mapping
+    let phi = if r == 0.0 { 1.0 } else { (vp - up) / r + 1.0 } * PI_OVER_4;
+
+    // find z coordinate for spherical direction
+    let z = (1.0 - r * r).copysign(signed_distance);
+
+    // compute cos phi and sin phi for original quadrant and return vector
+    let cos_phi = phi.cos().copysign(u);
+    let sin_phi = phi.sin().copysign(v);
+    Vector3f {
+        x: cos_phi * r * safe_sqrt(2.0 - r * r),
+        y: sin_phi * r * safe_sqrt(2.0 - r * r),
+        z,
+    }
+}
+
+// inverse of equal_area_square_to_sphere, d must be normalized
+pub fn equal_area_sphere_to_square(d: &Vector3f) -> Point2f {
+    let x = d.x.abs();
+    let y = d.y.abs();
+    let z = d.z.abs();
+
+    // compute the radius r
+    let r = safe_sqrt(1.0 - z);
+
+    // compute the argument to atan (detect a = 0 to avoid div-by-zero)
+    let a = x.max(y);
+    let b = x.min(y);
+    let b = if a == 0.0 { 0.0 } else { b / a };
+
+    // polar angle scaled to [0,1]
+    let mut phi = b.atan() / PI_OVER_2;
+    if x < y {
+        phi = 1.0 - phi;
+    }
+
+    // find (u,v) based on (r,phi)
+    let mut v = phi * r;
+    let mut u = r - v;
+    if d.z < 0.0 {
+        // southern hemisphere, mirror u,v
+        std::mem::swap(&mut u, &mut v);
+        u = 1.0 - u;
+        v = 1.0 - v;
+    }
+
+    // move (u,v) to the correct quadrant based on the signs of (x,y)
+    let u = u.copysign(d.x);
+    let v = v.copysign(d.y);
+
+    // transform (u,v) from [-1,1] to [0,1]
+    Point2f {
+        x: 0.5 * (u + 1.0),
+        y: 0.5 * (v + 1.0),
+    }
+}
+
+// DirectionCone
+// a cone of directions around w, containing all directions within
+// acos(cos_theta) of it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DirectionCone {
+    pub w: Vector3f,
+    pub cos_theta: Float,
+}
+
+impl DirectionCone {
+    pub fn new(w: &Vector3f, cos_theta: Float) -> DirectionCone {
+        DirectionCone {
+            w: w.normalize(),
+            cos_theta,
+        }
+    }
+
+    pub fn from_direction(w: &Vector3f) -> DirectionCone {
+        DirectionCone::new(w, 1.0)
+    }
+
+    pub fn entire_sphere() -> DirectionCone {
+        DirectionCone::new(&Vector3f::new(0.0, 0.0, 1.0), -1.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cos_theta == Float::INFINITY
+    }
+
+    pub fn inside(&self, w: &Vector3f) -> bool {
+        !self.is_empty() && self.w.dot(&w.normalize()) >= self.cos_theta
+    }
+
+    // cone of directions from p that reach any point inside b
+    pub fn bound_subtended_directions(b: &Bounds3f, p: &Point3f) -> DirectionCone {
+        // compute bounding sphere for b and check if p is inside
+        let (p_center, radius) = b.bounding_sphere();
+        let distance_squared = p.distance_squared(&p_center);
+        if distance_squared < radius * radius {
+            return DirectionCone::entire_sphere();
+        }
+
+        // compute and return DirectionCone for bounding sphere
+        let w = (p_center - *p).normalize();
+        let sin2_theta_max = radius * radius / distance_squared;
+        let cos_theta_max = safe_sqrt(1.0 - sin2_theta_max);
+        DirectionCone::new(&w, cos_theta_max)
+    }
+
+    pub fn union(&self, b: &DirectionCone) -> DirectionCone {
+        // handle the cases where one or both cones are empty
+        if self.is_empty() {
+            return *b;
+        }
+        if b.is_empty() {
+            return *self;
+        }
+
+        // handle the cases where one cone is inside the other
+        let theta_a = safe_acos(self.cos_theta);
+        let theta_b = safe_acos(b.cos_theta);
+        let theta_d = self.w.angle_between(&b.w);
+        if (theta_d + theta_b).min(PI) <= theta_a {
+            return *self;
+        }
+        if (theta_d + theta_a).min(PI) <= theta_b {
+            return *b;
+        }
+
+        // compute the spread angle of the merged cone, theta_o
+        let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+        if theta_o >= PI {
+            return DirectionCone::entire_sphere();
+        }
+
+        // find the merged cone's axis and return cone union
+        let theta_r = theta_o - theta_a;
+        let wr = self.w.cross(&b.w);
+        if wr.length_squared() == 0.0 {
+            return DirectionCone::entire_sphere();
+        }
+        let w = Transform::rotate(degrees(theta_r), &wr).transform_vector3f(&self.w);
+        DirectionCone::new(&w, theta_o.cos())
+    }
+}
+
+impl Default for DirectionCone {
+    fn default() -> DirectionCone {
+        DirectionCone {
+            w: Vector3f::default(),
+            cos_theta: Float::INFINITY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_area_mapping_round_trips() {
+        for i in 0..=16 {
+            for j in 0..=16 {
+                let p = Point2f::new(i as Float / 16.0, j as Float / 16.0);
+                let d = equal_area_square_to_sphere(&p);
+                assert!((d.length() - 1.0).abs() < 1e-5);
+                let q = equal_area_sphere_to_square(&d);
+                let back = equal_area_square_to_sphere(&q);
+                assert!((back - d).length() < 1e-4, "{:?} -> {:?} -> {:?}", p, d, q);
+            }
+        }
+    }
+
+    #[test]
+    fn octant_areas() {
+        let (x, y, z) = (
+            Vector3f::new(1.0, 0.0, 0.0),
+            Vector3f::new(0.0, 1.0, 0.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+        );
+        assert!((spherical_triangle_area(&x, &y, &z) - PI_OVER_2).abs() < 1e-5);
+        let quad = spherical_quad_area(&x, &y, &-x, &-y);
+        assert!((quad - 2.0 * PI).abs() < 1e-4);
+    }
+
+    #[test]
+    fn cone_union_contains_both_cones() {
+        let a = DirectionCone::new(&Vector3f::new(1.0, 0.0, 0.0), (0.2 as Float).cos());
+        let b = DirectionCone::new(&Vector3f::new(0.0, 1.0, 0.0), (0.3 as Float).cos());
+        let u = a.union(&b);
+        for w in [a.w, b.w] {
+            assert!(u.inside(&w));
+        }
+        let edge = Transform::rotate_z(-degrees(0.2)).transform_vector3f(&a.w);
+        assert!(u.inside(&(edge * 0.999 + a.w * 0.001)));
+        assert_eq!(DirectionCone::default().union(&a), a);
+        let inside = DirectionCone::bound_subtended_directions(
+            &Bounds3f::new_with_points(Point3f::new(-1.0, -1.0, -1.0), Point3f::new(1.0, 1.0, 1.0)),
+            &Point3f::default(),
+        );
+        assert_eq!(inside.cos_theta, -1.0);
+    }
+}
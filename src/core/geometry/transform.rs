@@ -0,0 +1,940 @@
+use std::cell::Cell;
+use std::ops;
+
+use crate::core::interaction::SurfaceInteraction;
+use crate::core::pbrt::{clamp, degrees, gamma, lerp, radians, Float};
+
+use super::{
+    bounds::Bounds3f,
+    normal::Normal3f,
+    point::{Point3f, Point3fi},
+    ray::{Ray, RayDifferential},
+    vector::{Vector3f, Vector3fi},
+};
+
+// Matrix4x4
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Matrix4x4 {
+    pub m: [[Float; 4]; 4],
+}
+
+impl Matrix4x4 {
+    pub fn new(m: [[Float; 4]; 4]) -> Matrix4x4 {
+        Matrix4x4 { m }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_values(
+        t00: Float,
+        t01: Float,
+        t02: Float,
+        t03: Float,
+        t10: Float,
+        t11: Float,
+        t12: Float,
+        t13: Float,
+        t20: Float,
+        t21: Float,
+        t22: Float,
+        t23: Float,
+        t30: Float,
+        t31: Float,
+        t32: Float,
+        t33: Float,
+    ) -> Matrix4x4 {
+        Matrix4x4 {
+            m: [
+                [t00, t01, t02, t03],
+                [t10, t11, t12, t13],
+                [t20, t21, t22, t23],
+                [t30, t31, t32, t33],
+            ],
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Matrix4x4::default()
+    }
+
+    pub fn transpose(&self) -> Matrix4x4 {
+        let mut r = Matrix4x4::default();
+        for i in 0..4 {
+            for j in 0..4 {
+                r.m[i][j] = self.m[j][i];
+            }
+        }
+        r
+    }
+
+    pub fn mul(&self, m2: &Matrix4x4) -> Matrix4x4 {
+        let mut r = Matrix4x4::default();
+        for i in 0..4 {
+            for j in 0..4 {
+                r.m[i][j] = self.m[i][0] * m2.m[0][j]
+                    + self.m[i][1] * m2.m[1][j]
+                    + self.m[i][2] * m2.m[2][j]
+                    + self.m[i][3] * m2.m[3][j];
+            }
+        }
+        r
+    }
+
+    // Gauss-Jordan elimination with full pivoting, None if the matrix is
+    // singular
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let mut indxc = [0usize; 4];
+        let mut indxr = [0usize; 4];
+        let mut ipiv = [0usize; 4];
+        let mut minv = self.m;
+        for i in 0..4 {
+            let mut irow = 0;
+            let mut icol = 0;
+            let mut big: Float = 0.0;
+            // choose pivot
+            for j in 0..4 {
+                if ipiv[j] != 1 {
+                    for (k, ipiv_k) in ipiv.iter().enumerate() {
+                        if *ipiv_k == 0 {
+                            if minv[j][k].abs() >= big {
+                                big = minv[j][k].abs();
+                                irow = j;
+                                icol = k;
+                            }
+                        } else if *ipiv_k > 1 {
+                            return None;
+                        }
+                    }
+                }
+            }
+            ipiv[icol] += 1;
+            // swap rows irow and icol for pivot
+            if irow != icol {
+                minv.swap(irow, icol);
+            }
+            indxr[i] = irow;
+            indxc[i] = icol;
+            if minv[icol][icol] == 0.0 {
+                return None;
+            }
+            // set m[icol][icol] to one by scaling row icol appropriately
+            let pivinv = 1.0 / minv[icol][icol];
+            minv[icol][icol] = 1.0;
+            for v in minv[icol].iter_mut() {
+                *v *= pivinv;
+            }
+            // subtract this row from others to zero out their columns
+            for j in 0..4 {
+                if j != icol {
+                    let save = minv[j][icol];
+                    let pivot_row = minv[icol];
+                    minv[j][icol] = 0.0;
+                    for (v, p) in minv[j].iter_mut().zip(pivot_row.iter()) {
+                        *v -= p * save;
+                    }
+                }
+            }
+        }
+        // swap columns to reflect permutation
+        for j in (0..4).rev() {
+            if indxr[j] != indxc[j] {
+                for row in minv.iter_mut() {
+                    row.swap(indxr[j], indxc[j]);
+                }
+            }
+        }
+        Some(Matrix4x4 { m: minv })
+    }
+}
+
+impl Default for Matrix4x4 {
+    fn default() -> Matrix4x4 {
+        Matrix4x4 {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+}
+
+impl_op_ex!(*|a: &Matrix4x4, b: &Matrix4x4| -> Matrix4x4 { a.mul(b) });
+
+// Transform
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub m: Matrix4x4,
+    pub m_inv: Matrix4x4,
+}
+
+impl Transform {
+    pub fn new(m: Matrix4x4) -> Option<Transform> {
+        Some(Transform {
+            m,
+            m_inv: m.inverse()?,
+        })
+    }
+
+    pub fn new_with_inverse(m: Matrix4x4, m_inv: Matrix4x4) -> Transform {
+        Transform { m, m_inv }
+    }
+
+    pub fn translate(delta: &Vector3f) -> Transform {
+        let m = Matrix4x4::new_with_values(
+            1.0, 0.0, 0.0, delta.x, 0.0, 1.0, 0.0, delta.y, 0.0, 0.0, 1.0, delta.z, 0.0, 0.0, 0.0,
+            1.0,
+        );
+        let m_inv = Matrix4x4::new_with_values(
+            1.0, 0.0, 0.0, -delta.x, 0.0, 1.0, 0.0, -delta.y, 0.0, 0.0, 1.0, -delta.z, 0.0, 0.0,
+            0.0, 1.0,
+        );
+        Transform { m, m_inv }
+    }
+
+    pub fn scale(x: Float, y: Float, z: Float) -> Transform {
+        let m = Matrix4x4::new_with_values(
+            x, 0.0, 0.0, 0.0, 0.0, y, 0.0, 0.0, 0.0, 0.0, z, 0.0, 0.0, 0.0, 0.0, 1.0,
+        );
+        let m_inv = Matrix4x4::new_with_values(
+            1.0 / x,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0 / y,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0 / z,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        );
+        Transform { m, m_inv }
+    }
+
+    pub fn rotate_x(theta: Float) -> Transform {
+        let (sin_theta, cos_theta) = radians(theta).sin_cos();
+        let m = Matrix4x4::new_with_values(
+            1.0, 0.0, 0.0, 0.0, 0.0, cos_theta, -sin_theta, 0.0, 0.0, sin_theta, cos_theta, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        Transform {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+
+    pub fn rotate_y(theta: Float) -> Transform {
+        let (sin_theta, cos_theta) = radians(theta).sin_cos();
+        let m = Matrix4x4::new_with_values(
+            cos_theta, 0.0, sin_theta, 0.0, 0.0, 1.0, 0.0, 0.0, -sin_theta, 0.0, cos_theta, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        Transform {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+
+    pub fn rotate_z(theta: Float) -> Transform {
+        let (sin_theta, cos_theta) = radians(theta).sin_cos();
+        let m = Matrix4x4::new_with_values(
+            cos_theta, -sin_theta, 0.0, 0.0, sin_theta, cos_theta, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        Transform {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+
+    // rotation of theta degrees about an arbitrary axis
+    pub fn rotate(theta: Float, axis: &Vector3f) -> Transform {
+        let a = axis.normalize();
+        let (sin_theta, cos_theta) = radians(theta).sin_cos();
+        let mut m = Matrix4x4::default();
+        m.m[0][0] = a.x * a.x + (1.0 - a.x * a.x) * cos_theta;
+        m.m[0][1] = a.x * a.y * (1.0 - cos_theta) - a.z * sin_theta;
+        m.m[0][2] = a.x * a.z * (1.0 - cos_theta) + a.y * sin_theta;
+        m.m[0][3] = 0.0;
+
+        m.m[1][0] = a.x * a.y * (1.0 - cos_theta) + a.z * sin_theta;
+        m.m[1][1] = a.y * a.y + (1.0 - a.y * a.y) * cos_theta;
+        m.m[1][2] = a.y * a.z * (1.0 - cos_theta) - a.x * sin_theta;
+        m.m[1][3] = 0.0;
+
+        m.m[2][0] = a.x * a.z * (1.0 - cos_theta) - a.y * sin_theta;
+        m.m[2][1] = a.y * a.z * (1.0 - cos_theta) + a.x * sin_theta;
+        m.m[2][2] = a.z * a.z + (1.0 - a.z * a.z) * cos_theta;
+        m.m[2][3] = 0.0;
+        Transform {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+
+    // world-to-camera transform for a camera at pos looking at look, None
+    // if pos and look coincide or up is parallel to the viewing direction
+    pub fn look_at(pos: &Point3f, look: &Point3f, up: &Vector3f) -> Option<Transform> {
+        let mut camera_to_world = Matrix4x4::default();
+        camera_to_world.m[0][3] = pos.x;
+        camera_to_world.m[1][3] = pos.y;
+        camera_to_world.m[2][3] = pos.z;
+
+        let dir = (*look - *pos).normalize();
+        let right = up.normalize().cross(&dir).normalize();
+        if right.has_nans() {
+            return None;
+        }
+        let new_up = dir.cross(&right);
+        camera_to_world.m[0][0] = right.x;
+        camera_to_world.m[1][0] = right.y;
+        camera_to_world.m[2][0] = right.z;
+        camera_to_world.m[3][0] = 0.0;
+        camera_to_world.m[0][1] = new_up.x;
+        camera_to_world.m[1][1] = new_up.y;
+        camera_to_world.m[2][1] = new_up.z;
+        camera_to_world.m[3][1] = 0.0;
+        camera_to_world.m[0][2] = dir.x;
+        camera_to_world.m[1][2] = dir.y;
+        camera_to_world.m[2][2] = dir.z;
+        camera_to_world.m[3][2] = 0.0;
+        Some(Transform {
+            m: camera_to_world.inverse()?,
+            m_inv: camera_to_world,
+        })
+    }
+
+    pub fn orthographic(z_near: Float, z_far: Float) -> Transform {
+        Transform::scale(1.0, 1.0, 1.0 / (z_far - z_near))
+            * Transform::translate(&Vector3f::new(0.0, 0.0, -z_near))
+    }
+
+    // None if the near and far planes are degenerate
+    pub fn perspective(fov: Float, n: Float, f: Float) -> Option<Transform> {
+        // perform projective divide for perspective projection
+        let persp = Matrix4x4::new_with_values(
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            f / (f - n),
+            -f * n / (f - n),
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+        );
+        // scale canonical perspective view to specified field of view
+        let inv_tan_ang = 1.0 / (radians(fov) / 2.0).tan();
+        Some(Transform::scale(inv_tan_ang, inv_tan_ang, 1.0) * Transform::new(persp)?)
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    pub fn transpose(&self) -> Transform {
+        Transform {
+            m: self.m.transpose(),
+            m_inv: self.m_inv.transpose(),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.m.is_identity()
+    }
+
+    pub fn has_scale(&self) -> bool {
+        let la2 = self
+            .transform_vector3f(&Vector3f::new(1.0, 0.0, 0.0))
+            .length_squared();
+        let lb2 = self
+            .transform_vector3f(&Vector3f::new(0.0, 1.0, 0.0))
+            .length_squared();
+        let lc2 = self
+            .transform_vector3f(&Vector3f::new(0.0, 0.0, 1.0))
+            .length_squared();
+        let not_one = |x: Float| !(0.999..=1.001).contains(&x);
+        not_one(la2) || not_one(lb2) || not_one(lc2)
+    }
+
+    // determinant of the upper left 3x3 matrix, the factor by which the
+    // transform scales volumes
+    pub fn determinant(&self) -> Float {
+        let m = &self.m.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn swaps_handedness(&self) -> bool {
+        self.determinant() < 0.0
+    }
+
+    pub fn transform_point3f(&self, p: &Point3f) -> Point3f {
+        let m = &self.m.m;
+        let xp = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let yp = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let zp = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let wp = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if wp == 1.0 {
+            Point3f::new(xp, yp, zp)
+        } else {
+            Point3f::new(xp, yp, zp) / wp
+        }
+    }
+
+    pub fn transform_vector3f(&self, v: &Vector3f) -> Vector3f {
+        let m = &self.m.m;
+        Vector3f {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+
+    // transforms p and grows its error bounds by the rounding error of the
+    // transformation itself
+    pub fn transform_point3fi(&self, p: &Point3fi) -> Point3fi {
+        let m = &self.m.m;
+        let pm = p.midpoint();
+        let p_in_error = p.error();
+        let xp = m[0][0] * pm.x + m[0][1] * pm.y + m[0][2] * pm.z + m[0][3];
+        let yp = m[1][0] * pm.x + m[1][1] * pm.y + m[1][2] * pm.z + m[1][3];
+        let zp = m[2][0] * pm.x + m[2][1] * pm.y + m[2][2] * pm.z + m[2][3];
+        let wp = m[3][0] * pm.x + m[3][1] * pm.y + m[3][2] * pm.z + m[3][3];
+        let mut p_error = Vector3f::default();
+        for i in 0..3 {
+            p_error[i] = gamma(3)
+                * ((m[i][0] * pm.x).abs()
+                    + (m[i][1] * pm.y).abs()
+                    + (m[i][2] * pm.z).abs()
+                    + m[i][3].abs());
+            if !p.is_exact() {
+                p_error[i] += (gamma(3) + 1.0)
+                    * (m[i][0].abs() * p_in_error.x
+                        + m[i][1].abs() * p_in_error.y
+                        + m[i][2].abs() * p_in_error.z);
+            }
+        }
+        if wp == 1.0 {
+            Point3fi::new_with_error(&Point3f::new(xp, yp, zp), &p_error)
+        } else {
+            Point3fi::new_with_error(&(Point3f::new(xp, yp, zp) / wp), &(p_error / wp))
+        }
+    }
+
+    pub fn transform_vector3fi(&self, v: &Vector3fi) -> Vector3fi {
+        let m = &self.m.m;
+        let vm = v.midpoint();
+        let v_in_error = v.error();
+        let mut v_error = Vector3f::default();
+        for i in 0..3 {
+            v_error[i] = gamma(3)
+                * ((m[i][0] * vm.x).abs() + (m[i][1] * vm.y).abs() + (m[i][2] * vm.z).abs());
+            if !v.is_exact() {
+                v_error[i] += (gamma(3) + 1.0)
+                    * (m[i][0].abs() * v_in_error.x
+                        + m[i][1].abs() * v_in_error.y
+                        + m[i][2].abs() * v_in_error.z);
+            }
+        }
+        Vector3fi::new_with_error(&self.transform_vector3f(&vm), &v_error)
+    }
+
+    // normals are transformed by the inverse transpose
+    pub fn transform_normal3f(&self, n: &Normal3f) -> Normal3f {
+        let m_inv = &self.m_inv.m;
+        Normal3f {
+            x: m_inv[0][0] * n.x + m_inv[1][0] * n.y + m_inv[2][0] * n.z,
+            y: m_inv[0][1] * n.x + m_inv[1][1] * n.y + m_inv[2][1] * n.z,
+            z: m_inv[0][2] * n.x + m_inv[1][2] * n.y + m_inv[2][2] * n.z,
+        }
+    }
+
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        Ray {
+            o: self.transform_point3f(&r.o),
+            d: self.transform_vector3f(&r.d),
+            t_max: Cell::new(r.t_max.get()),
+            time: r.time,
+            medium: r.medium.clone(),
+            differential: r.differential.map(|diff| RayDifferential {
+                rx_origin: self.transform_point3f(&diff.rx_origin),
+                ry_origin: self.transform_point3f(&diff.ry_origin),
+                rx_direction: self.transform_vector3f(&diff.rx_direction),
+                ry_direction: self.transform_vector3f(&diff.ry_direction),
+            }),
+        }
+    }
+
+    pub fn transform_surface_interaction(&self, si: &SurfaceInteraction) -> SurfaceInteraction {
+        let mut ret = si.clone();
+        ret.interaction.pi = self.transform_point3fi(&si.interaction.pi);
+        ret.interaction.n = self.transform_normal3f(&si.interaction.n).normalize();
+        if si.interaction.wo != Vector3f::default() {
+            ret.interaction.wo = self.transform_vector3f(&si.interaction.wo).normalize();
+        }
+        ret.dpdu = self.transform_vector3f(&si.dpdu);
+        ret.dpdv = self.transform_vector3f(&si.dpdv);
+        ret.dndu = self.transform_normal3f(&si.dndu);
+        ret.dndv = self.transform_normal3f(&si.dndv);
+        ret.shading.n = self.transform_normal3f(&si.shading.n).normalize();
+        ret.shading.dpdu = self.transform_vector3f(&si.shading.dpdu);
+        ret.shading.dpdv = self.transform_vector3f(&si.shading.dpdv);
+        ret.shading.dndu = self.transform_normal3f(&si.shading.dndu);
+        ret.shading.dndv = self.transform_normal3f(&si.shading.dndv);
+        ret.dpdx = self.transform_vector3f(&si.dpdx);
+        ret.dpdy = self.transform_vector3f(&si.dpdy);
+        ret.shading.n = ret.shading.n.face_forward(&ret.interaction.n);
+        ret
+    }
+
+    pub fn transform_bounds3f(&self, b: &Bounds3f) -> Bounds3f {
+        (0..8).fold(Bounds3f::default(), |ret, corner| {
+            ret.union_point3f(&self.transform_point3f(&b.corner(corner)))
+        })
+    }
+}
+
+impl_op_ex!(*|a: &Transform, b: &Transform| -> Transform {
+    Transform {
+        m: a.m * b.m,
+        m_inv: b.m_inv * a.m_inv,
+    }
+});
+
+// AnimatedTransform
+#[derive(Debug, Clone, PartialEq)]
+struct Keyframe {
+    time: Float,
+    transform: Transform,
+    t: Vector3f,
+    r: Matrix4x4,
+    s: Matrix4x4,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    keyframes: Vec<Keyframe>,
+    actually_animated: bool,
+}
+
+impl AnimatedTransform {
+    pub fn new(
+        start_transform: &Transform,
+        start_time: Float,
+        end_transform: &Transform,
+        end_time: Float,
+    ) -> Result<AnimatedTransform, String> {
+        AnimatedTransform::new_with_keyframes(&[
+            (start_time, *start_transform),
+            (end_time, *end_transform),
+        ])
+    }
+
+    // keyframes are (time, transform) pairs sorted by increasing time
+    pub fn new_with_keyframes(
+        keyframes: &[(Float, Transform)],
+    ) -> Result<AnimatedTransform, String> {
+        if keyframes.is_empty() {
+            return Err(String::from(
+                "AnimatedTransform needs at least one keyframe",
+            ));
+        }
+        if let Some(i) = keyframes.iter().position(|k| !k.0.is_finite()) {
+            return Err(format!(
+                "AnimatedTransform keyframe {} has no finite time",
+                i
+            ));
+        }
+        if let Some(i) = keyframes.windows(2).position(|k| k[1].0 < k[0].0) {
+            return Err(format!(
+                "AnimatedTransform keyframe {} is not sorted by time",
+                i + 1
+            ));
+        }
+        // rotations can only be interpolated between frames of the same
+        // handedness
+        if let Some(i) = keyframes
+            .windows(2)
+            .position(|k| k[0].1.swaps_handedness() != k[1].1.swaps_handedness())
+        {
+            return Err(format!(
+                "AnimatedTransform keyframe {} swaps handedness",
+                i + 1
+            ));
+        }
+        let mut decomposed: Vec<Keyframe> = Vec::with_capacity(keyframes.len());
+        for (i, (time, transform)) in keyframes.iter().enumerate() {
+            let (t, r, s) = AnimatedTransform::decompose(&transform.m)
+                .ok_or_else(|| format!("AnimatedTransform keyframe {} is singular", i))?;
+            decomposed.push(Keyframe {
+                time: *time,
+                transform: *transform,
+                t,
+                r,
+                s,
+            });
+        }
+        let actually_animated = decomposed
+            .windows(2)
+            .any(|k| k[0].transform != k[1].transform);
+        Ok(AnimatedTransform {
+            keyframes: decomposed,
+            actually_animated,
+        })
+    }
+
+    // split m into translation, rotation and scale so that m = T * R * S,
+    // None if m is singular
+    pub fn decompose(m: &Matrix4x4) -> Option<(Vector3f, Matrix4x4, Matrix4x4)> {
+        // extract translation from transformation matrix
+        let t = Vector3f::new(m.m[0][3], m.m[1][3], m.m[2][3]);
+
+        // compute new transformation matrix without translation
+        let mut mt = *m;
+        for i in 0..3 {
+            mt.m[i][3] = 0.0;
+            mt.m[3][i] = 0.0;
+        }
+        mt.m[3][3] = 1.0;
+
+        // extract rotation from transformation matrix by polar decomposition
+        let mut r = mt;
+        for _ in 0..100 {
+            // compute next matrix in the series
+            let r_it = r.transpose().inverse()?;
+            let mut r_next = Matrix4x4::default();
+            for i in 0..4 {
+                for j in 0..4 {
+                    r_next.m[i][j] = 0.5 * (r.m[i][j] + r_it.m[i][j]);
+                }
+            }
+            // compute norm of difference between r and r_next
+            let mut norm: Float = 0.0;
+            for i in 0..3 {
+                let n = (0..3)
+                    .map(|j| (r.m[i][j] - r_next.m[i][j]).abs())
+                    .sum::<Float>();
+                norm = norm.max(n);
+            }
+            r = r_next;
+            if norm <= 0.0001 {
+                break;
+            }
+        }
+
+        // compute scale using rotation and original matrix
+        let s = r.inverse()? * mt;
+        Some((t, r, s))
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.actually_animated
+    }
+
+    // rotations by less than about 3.6 degrees between keyframes are
+    // ignored, they are left over from the polar decomposition
+    pub fn has_rotation(&self) -> bool {
+        self.keyframes
+            .windows(2)
+            .any(|k| relative_rotation(&k[0].r, &k[1].r).1 > 0.0632)
+    }
+
+    pub fn has_scale(&self) -> bool {
+        self.keyframes.iter().any(|k| k.transform.has_scale())
+    }
+
+    pub fn interpolate(&self, time: Float) -> Transform {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        // handle boundary conditions for matrix interpolation
+        if !self.actually_animated || time <= first.time {
+            return first.transform;
+        }
+        if time >= last.time {
+            return last.transform;
+        }
+        let i = self
+            .keyframes
+            .windows(2)
+            .position(|k| time < k[1].time)
+            .unwrap_or(self.keyframes.len() - 2);
+        let (k0, k1) = (&self.keyframes[i], &self.keyframes[i + 1]);
+        let dt = (time - k0.time) / (k1.time - k0.time);
+
+        // interpolate translation at dt
+        let trans = k0.t * (1.0 - dt) + k1.t * dt;
+
+        // interpolate rotation at dt by turning the first rotation around the
+        // axis to the second one at constant speed
+        let (axis, angle) = relative_rotation(&k0.r, &k1.r);
+        let rotate = Transform::new_with_inverse(k0.r, k0.r.transpose())
+            * Transform::rotate(degrees(dt * angle), &axis);
+
+        // interpolate scale at dt
+        let mut scale = Matrix4x4::default();
+        for i in 0..3 {
+            for j in 0..3 {
+                scale.m[i][j] = lerp(dt, k0.s.m[i][j], k1.s.m[i][j]);
+            }
+        }
+
+        // blends of the keyframes' positive definite scales stay invertible,
+        // only rounding could make this fail
+        match Transform::new(scale) {
+            Some(scale) => Transform::translate(&trans) * rotate * scale,
+            None => k0.transform,
+        }
+    }
+
+    pub fn transform_point3f(&self, time: Float, p: &Point3f) -> Point3f {
+        self.interpolate(time).transform_point3f(p)
+    }
+
+    pub fn transform_vector3f(&self, time: Float, v: &Vector3f) -> Vector3f {
+        self.interpolate(time).transform_vector3f(v)
+    }
+
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        self.interpolate(r.time).transform_ray(r)
+    }
+
+    pub fn motion_bounds(&self, b: &Bounds3f) -> Bounds3f {
+        if !self.actually_animated {
+            return self.keyframes[0].transform.transform_bounds3f(b);
+        }
+        if !self.has_rotation() {
+            // without rotation the corners move linearly between keyframes
+            return self
+                .keyframes
+                .iter()
+                .fold(Bounds3f::default(), |bounds, k| {
+                    bounds.union(&k.transform.transform_bounds3f(b))
+                });
+        }
+        // return motion bounds accounting for animated rotation
+        (0..8).fold(Bounds3f::default(), |bounds, corner| {
+            bounds.union(&self.bound_point_motion(&b.corner(corner)))
+        })
+    }
+
+    // samples the path of p between keyframes and grows the bounds of the
+    // samples by how far p can get from them at its maximum speed
+    pub fn bound_point_motion(&self, p: &Point3f) -> Bounds3f {
+        const N_STEPS: usize = 16;
+        let mut bounds = Bounds3f::from(self.keyframes[0].transform.transform_point3f(p));
+        if !self.actually_animated {
+            return bounds;
+        }
+        for k in self.keyframes.windows(2) {
+            let (k0, k1) = (&k[0], &k[1]);
+            let end = k1.transform.transform_point3f(p);
+            let (_, angle) = relative_rotation(&k0.r, &k1.r);
+            if angle == 0.0 || k0.time == k1.time {
+                // p moves along a line
+                bounds = bounds.union_point3f(&end);
+                continue;
+            }
+
+            // bound the speed of p over the normalized time between the
+            // keyframes by the translation, rotation and scale terms
+            let u = mul_3x3(&k0.s, &Vector3f::from(*p));
+            let v = mul_3x3(&k1.s, &Vector3f::from(*p)) - u;
+            let speed =
+                (k1.t - k0.t).length() + angle * u.length().max((u + v).length()) + v.length();
+
+            // every point of the path is within half a step of a sample
+            let mut samples = Bounds3f::from(end);
+            for i in 0..N_STEPS {
+                let time = lerp(i as Float / N_STEPS as Float, k0.time, k1.time);
+                samples = samples.union_point3f(&self.transform_point3f(time, p));
+            }
+            bounds = bounds.union(&samples.expand(speed / (2 * N_STEPS) as Float));
+        }
+        bounds
+    }
+}
+
+// axis and angle in [0, pi] of the rotation from r0 to r1, in the space of r0
+fn relative_rotation(r0: &Matrix4x4, r1: &Matrix4x4) -> (Vector3f, Float) {
+    let m = (r0.transpose() * *r1).m;
+    let cos_angle = clamp(0.5 * (m[0][0] + m[1][1] + m[2][2] - 1.0), -1.0, 1.0);
+    let angle = cos_angle.acos();
+    // the antisymmetric part is 2 sin(angle) times the axis
+    let w = Vector3f::new(m[2][1] - m[1][2], m[0][2] - m[2][0], m[1][0] - m[0][1]);
+    if cos_angle > 0.0 {
+        if w.length_squared() == 0.0 {
+            return (Vector3f::new(0.0, 0.0, 1.0), 0.0);
+        }
+        return (w.normalize(), angle);
+    }
+    // close to a half turn w vanishes, take the axis from the column of the
+    // symmetric part (1 - cos(angle)) axis axis^T with the largest diagonal
+    let i = if m[0][0] >= m[1][1] && m[0][0] >= m[2][2] {
+        0
+    } else if m[1][1] >= m[2][2] {
+        1
+    } else {
+        2
+    };
+    let mut axis = Vector3f::new(m[0][i] + m[i][0], m[1][i] + m[i][1], m[2][i] + m[i][2]);
+    match i {
+        0 => axis.x -= 2.0 * cos_angle,
+        1 => axis.y -= 2.0 * cos_angle,
+        _ => axis.z -= 2.0 * cos_angle,
+    }
+    if axis.dot(&w) < 0.0 {
+        axis = -axis;
+    }
+    (axis.normalize(), angle)
+}
+
+fn mul_3x3(m: &Matrix4x4, v: &Vector3f) -> Vector3f {
+    Vector3f {
+        x: m.m[0][0] * v.x + m.m[0][1] * v.y + m.m[0][2] * v.z,
+        y: m.m[1][0] * v.x + m.m[1][1] * v.y + m.m[1][2] * v.z,
+        z: m.m[2][0] * v.x + m.m[2][1] * v.y + m.m[2][2] * v.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_of_singular_matrix_is_none() {
+        let mut m = Matrix4x4::default();
+        m.m[1][1] = 0.0;
+        assert!(m.inverse().is_none());
+        assert!(Transform::new(m).is_none());
+    }
+
+    #[test]
+    fn determinant_scales_volumes() {
+        assert_eq!(Transform::scale(2.0, 3.0, 4.0).determinant(), 24.0);
+        assert_eq!(
+            Transform::translate(&Vector3f::new(1.0, 2.0, 3.0)).determinant(),
+            1.0
+        );
+        assert!(Transform::scale(-1.0, 1.0, 1.0).swaps_handedness());
+        assert!(!Transform::rotate_x(30.0).swaps_handedness());
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let t = Transform::translate(&Vector3f::new(1.0, -2.0, 3.0))
+            * Transform::rotate(30.0, &Vector3f::new(1.0, 1.0, 0.0))
+            * Transform::scale(2.0, 3.0, 0.5);
+        let m_inv = t.m.inverse().unwrap();
+        let identity = t.m * m_inv;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((identity.m[i][j] - expected).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn look_at_rejects_degenerate_frames() {
+        let pos = Point3f::new(0.0, 0.0, 0.0);
+        let up = Vector3f::new(0.0, 1.0, 0.0);
+        assert!(Transform::look_at(&pos, &Point3f::new(0.0, 5.0, 0.0), &up).is_none());
+        assert!(Transform::look_at(&pos, &pos, &up).is_none());
+        let t = Transform::look_at(&pos, &Point3f::new(0.0, 0.0, 5.0), &up).unwrap();
+        let p = t.transform_point3f(&Point3f::new(0.0, 0.0, 5.0));
+        assert!((p - Point3f::new(0.0, 0.0, 5.0)).length() < 1e-5);
+    }
+
+    fn assert_same_mapping(a: &Transform, b: &Transform) {
+        for p in [
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+            Point3f::new(0.0, 0.0, 1.0),
+        ] {
+            let d = a.transform_point3f(&p) - b.transform_point3f(&p);
+            assert!(d.length() < 1e-4, "{:?} vs {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn animated_transform_interpolates_keyframes() {
+        let start = Transform::default();
+        let end = Transform::translate(&Vector3f::new(2.0, 0.0, 0.0))
+            * Transform::rotate_z(90.0)
+            * Transform::scale(3.0, 3.0, 3.0);
+        let animated = AnimatedTransform::new(&start, 0.0, &end, 1.0).unwrap();
+        assert!(animated.is_animated());
+        assert!(animated.has_rotation());
+        assert_same_mapping(&animated.interpolate(-1.0), &start);
+        assert_same_mapping(&animated.interpolate(2.0), &end);
+        let halfway = Transform::translate(&Vector3f::new(1.0, 0.0, 0.0))
+            * Transform::rotate_z(45.0)
+            * Transform::scale(2.0, 2.0, 2.0);
+        assert_same_mapping(&animated.interpolate(0.5), &halfway);
+    }
+
+    #[test]
+    fn animated_transform_picks_surrounding_keyframes() {
+        let animated = AnimatedTransform::new_with_keyframes(&[
+            (0.0, Transform::rotate_x(0.0)),
+            (1.0, Transform::rotate_x(60.0)),
+            (3.0, Transform::rotate_x(120.0)),
+        ])
+        .unwrap();
+        assert_same_mapping(&animated.interpolate(0.5), &Transform::rotate_x(30.0));
+        assert_same_mapping(&animated.interpolate(2.0), &Transform::rotate_x(90.0));
+    }
+
+    #[test]
+    fn animated_transform_rejects_invalid_keyframes() {
+        let identity = Transform::default();
+        assert!(AnimatedTransform::new_with_keyframes(&[]).is_err());
+        assert!(AnimatedTransform::new(&identity, 1.0, &identity, 0.0).is_err());
+        assert!(AnimatedTransform::new(&identity, Float::NAN, &identity, 1.0).is_err());
+        let flat = Transform::scale(0.0, 1.0, 1.0);
+        assert!(AnimatedTransform::new(&identity, 0.0, &flat, 1.0).is_err());
+        let mirror = Transform::scale(-1.0, 1.0, 1.0);
+        assert!(AnimatedTransform::new(&identity, 0.0, &mirror, 1.0).is_err());
+    }
+
+    #[test]
+    fn motion_bounds_contain_swept_box() {
+        let start = Transform::translate(&Vector3f::new(1.0, 0.0, 0.0));
+        let end = Transform::rotate(170.0, &Vector3f::new(1.0, 1.0, 1.0))
+            * Transform::translate(&Vector3f::new(1.0, 0.5, 0.0))
+            * Transform::scale(2.0, 1.0, 1.0);
+        let animated = AnimatedTransform::new(&start, 0.0, &end, 1.0).unwrap();
+        let b =
+            Bounds3f::new_with_points(Point3f::new(-0.5, -0.5, -0.5), Point3f::new(0.5, 0.5, 0.5));
+        let bounds = animated.motion_bounds(&b);
+        for i in 0..=1000 {
+            let moved = animated
+                .interpolate(i as Float / 1000.0)
+                .transform_bounds3f(&b);
+            for p in [moved.p_min, moved.p_max] {
+                assert!(p.x >= bounds.p_min.x && p.x <= bounds.p_max.x);
+                assert!(p.y >= bounds.p_min.y && p.y <= bounds.p_max.y);
+                assert!(p.z >= bounds.p_min.z && p.z <= bounds.p_max.z);
+            }
+        }
+    }
+}
@@ -1,22 +1,52 @@
 use std::ops;
 use std::ops::{Index, IndexMut};
 
-use crate::core::pbrt::Float;
+use crate::core::interval::Interval;
+use crate::core::pbrt::{safe_asin, Float, PI};
 
 use super::point::Point3f;
 
-// Vector2f
-#[derive(Debug, Default, Copy, Clone)]
-pub struct Vector2f {
-    pub x: Float,
-    pub y: Float,
+// Vector2
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vector2<T> {
+    pub x: T,
+    pub y: T,
 }
 
-impl Vector2f {
-    pub fn new(x: Float, y: Float) -> Self {
-        Vector2f { x, y }
+pub type Vector2f = Vector2<Float>;
+pub type Vector2i = Vector2<i32>;
+
+impl<T> Vector2<T> {
+    pub fn new(x: T, y: T) -> Self {
+        Vector2 { x, y }
     }
+}
+
+impl<T> Index<usize> for Vector2<T> {
+    type Output = T;
 
+    fn index(&self, i: usize) -> &T {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Vector2 index out of bounds"),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Vector2<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        match i {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Vector2 index out of bounds"),
+        }
+    }
+}
+
+// Vector2f
+impl Vector2f {
     pub fn has_nans(&self) -> bool {
         self.x.is_nan() || self.y.is_nan()
     }
@@ -34,24 +64,11 @@ impl Vector2f {
     }
 }
 
-impl Index<usize> for Vector2f {
-    type Output = Float;
-
-    fn index(&self, i: usize) -> &Float {
-        match i {
-            0 => &self.x,
-            1 => &self.y,
-            _ => panic!("Vector2f index out of bounds"),
-        }
-    }
-}
-
-impl IndexMut<usize> for Vector2f {
-    fn index_mut(&mut self, i: usize) -> &mut Float {
-        match i {
-            0 => &mut self.x,
-            1 => &mut self.y,
-            _ => panic!("Vector2f index out of bounds"),
+impl From<Vector2i> for Vector2f {
+    fn from(v: Vector2i) -> Vector2f {
+        Vector2f {
+            x: v.x as Float,
+            y: v.y as Float,
         }
     }
 }
@@ -103,19 +120,89 @@ impl_op_ex!(/= |a: &mut Vector2f, b: Float| {
 
 impl_op_ex!(-|a: &Vector2f| -> Vector2f { Vector2f { x: -a.x, y: -a.y } });
 
-// Vector3f
+// Vector2i
+impl_op_ex!(+ |a: &Vector2i, b: &Vector2i| -> Vector2i {
+    Vector2i { x: a.x + b.x, y: a.y + b.y }
+});
+
+impl_op_ex!(+= |a: &mut Vector2i, b: &Vector2i| {
+    a.x += b.x;
+    a.y += b.y;
+});
+
+impl_op_ex!(-|a: &Vector2i, b: &Vector2i| -> Vector2i {
+    Vector2i {
+        x: a.x - b.x,
+        y: a.y - b.y,
+    }
+});
+
+impl_op_ex!(-= |a: &mut Vector2i, b: &Vector2i| {
+    a.x -= b.x;
+    a.y -= b.y;
+});
+
+impl_op_ex!(*|a: &Vector2i, b: i32| -> Vector2i {
+    Vector2i {
+        x: a.x * b,
+        y: a.y * b,
+    }
+});
+
+impl_op_ex!(/ |a: &Vector2i, b: i32| -> Vector2i {
+    Vector2i {
+        x: a.x / b,
+        y: a.y / b,
+    }
+});
+
+impl_op_ex!(-|a: &Vector2i| -> Vector2i { Vector2i { x: -a.x, y: -a.y } });
+
+// Vector3
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
-pub struct Vector3f {
-    pub x: Float,
-    pub y: Float,
-    pub z: Float,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vector3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
 }
 
-impl Vector3f {
-    pub fn new(x: Float, y: Float, z: Float) -> Self {
-        Vector3f { x, y, z }
+pub type Vector3f = Vector3<Float>;
+pub type Vector3i = Vector3<i32>;
+pub type Vector3fi = Vector3<Interval>;
+
+impl<T> Vector3<T> {
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Vector3 { x, y, z }
     }
+}
+
+impl<T> Index<usize> for Vector3<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector3 index out of bounds"),
+        }
+    }
+}
 
+impl<T> IndexMut<usize> for Vector3<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        match i {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vector3 index out of bounds"),
+        }
+    }
+}
+
+// Vector3f
+impl Vector3f {
     pub fn has_nans(&self) -> bool {
         self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
     }
@@ -139,6 +226,16 @@ impl Vector3f {
         self.dot(v).abs()
     }
 
+    // angle between two normalized vectors, accurate for nearly parallel
+    // and nearly opposite directions
+    pub fn angle_between(&self, v: &Vector3f) -> Float {
+        if self.dot(v) < 0.0 {
+            PI - 2.0 * safe_asin((*self + *v).length() / 2.0)
+        } else {
+            2.0 * safe_asin((*v - *self).length() / 2.0)
+        }
+    }
+
     pub fn cross(&self, v: &Vector3f) -> Vector3f {
         Vector3f {
             x: self.y * v.z - self.z * v.y,
@@ -147,6 +244,14 @@ impl Vector3f {
         }
     }
 
+    pub fn abs(&self) -> Vector3f {
+        Vector3f {
+            x: self.x.abs(),
+            y: self.y.abs(),
+            z: self.z.abs(),
+        }
+    }
+
     pub fn min_component(&self) -> Float {
         self.x.min(self.y.min(self.z))
     }
@@ -196,26 +301,12 @@ impl Vector3f {
     }
 }
 
-impl Index<usize> for Vector3f {
-    type Output = Float;
-
-    fn index(&self, i: usize) -> &Float {
-        match i {
-            0 => &self.x,
-            1 => &self.y,
-            2 => &self.z,
-            _ => panic!("Vector3f index out of bounds"),
-        }
-    }
-}
-
-impl IndexMut<usize> for Vector3f {
-    fn index_mut(&mut self, i: usize) -> &mut Float {
-        match i {
-            0 => &mut self.x,
-            1 => &mut self.y,
-            2 => &mut self.z,
-            _ => panic!("Vector3f index out of bounds"),
+impl From<Vector3i> for Vector3f {
+    fn from(v: Vector3i) -> Vector3f {
+        Vector3f {
+            x: v.x as Float,
+            y: v.y as Float,
+            z: v.z as Float,
         }
     }
 }
@@ -296,19 +387,212 @@ impl_op_ex!(-|a: &Vector3f| -> Vector3f {
     }
 });
 
-pub fn vec3_coordinate_system(v1: &Vector3f, v2: &mut Vector3f, v3: &mut Vector3f) {
-    if v1.x.abs() > v1.y.abs() {
-        *v2 = Vector3f {
-            x: -v1.z,
-            y: 0.0 as Float,
-            z: v1.x,
-        } / (v1.x * v1.x + v1.z * v1.z).sqrt();
-    } else {
-        *v2 = Vector3f {
-            x: 0.0 as Float,
-            y: v1.z,
-            z: -v1.y,
-        } / (v1.x * v1.x + v1.z * v1.z).sqrt();
+// Vector3i
+impl_op_ex!(+ |a: &Vector3i, b: &Vector3i| -> Vector3i {
+    Vector3i {
+        x: a.x + b.x,
+        y: a.y + b.y,
+        z: a.z + b.z,
+    }
+});
+
+impl_op_ex!(+= |a: &mut Vector3i, b: &Vector3i| {
+    a.x += b.x;
+    a.y += b.y;
+    a.z += b.z;
+});
+
+impl_op_ex!(-|a: &Vector3i, b: &Vector3i| -> Vector3i {
+    Vector3i {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+});
+
+impl_op_ex!(-= |a: &mut Vector3i, b: &Vector3i| {
+    a.x -= b.x;
+    a.y -= b.y;
+    a.z -= b.z;
+});
+
+impl_op_ex!(*|a: &Vector3i, b: i32| -> Vector3i {
+    Vector3i {
+        x: a.x * b,
+        y: a.y * b,
+        z: a.z * b,
+    }
+});
+
+impl_op_ex!(-|a: &Vector3i| -> Vector3i {
+    Vector3i {
+        x: -a.x,
+        y: -a.y,
+        z: -a.z,
+    }
+});
+
+// Vector3fi
+impl Vector3fi {
+    pub fn new_with_error(v: &Vector3f, e: &Vector3f) -> Vector3fi {
+        Vector3fi {
+            x: Interval::from_value_and_error(v.x, e.x),
+            y: Interval::from_value_and_error(v.y, e.y),
+            z: Interval::from_value_and_error(v.z, e.z),
+        }
+    }
+
+    // half the width of each component's interval
+    pub fn error(&self) -> Vector3f {
+        self.width() / 2.0
+    }
+
+    pub fn width(&self) -> Vector3f {
+        Vector3f {
+            x: self.x.width(),
+            y: self.y.width(),
+            z: self.z.width(),
+        }
+    }
+
+    pub fn midpoint(&self) -> Vector3f {
+        Vector3f {
+            x: self.x.midpoint(),
+            y: self.y.midpoint(),
+            z: self.z.midpoint(),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.x.width() == 0.0 && self.y.width() == 0.0 && self.z.width() == 0.0
+    }
+
+    pub fn dot(&self, v: &Vector3fi) -> Interval {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+
+    pub fn cross(&self, v: &Vector3fi) -> Vector3fi {
+        Vector3fi {
+            x: self.y * v.z - self.z * v.y,
+            y: self.z * v.x - self.x * v.z,
+            z: self.x * v.y - self.y * v.x,
+        }
+    }
+
+    pub fn length_squared(&self) -> Interval {
+        self.x.sqr() + self.y.sqr() + self.z.sqr()
+    }
+
+    pub fn length(&self) -> Interval {
+        self.length_squared().sqrt()
+    }
+}
+
+impl From<Vector3f> for Vector3fi {
+    fn from(v: Vector3f) -> Vector3fi {
+        Vector3fi {
+            x: Interval::from(v.x),
+            y: Interval::from(v.y),
+            z: Interval::from(v.z),
+        }
+    }
+}
+
+// collapses each component to its midpoint
+impl From<Vector3fi> for Vector3f {
+    fn from(v: Vector3fi) -> Vector3f {
+        v.midpoint()
+    }
+}
+
+impl_op_ex!(+ |a: &Vector3fi, b: &Vector3fi| -> Vector3fi {
+    Vector3fi {
+        x: a.x + b.x,
+        y: a.y + b.y,
+        z: a.z + b.z,
+    }
+});
+
+impl_op_ex!(+= |a: &mut Vector3fi, b: &Vector3fi| {
+    a.x += b.x;
+    a.y += b.y;
+    a.z += b.z;
+});
+
+impl_op_ex!(-|a: &Vector3fi, b: &Vector3fi| -> Vector3fi {
+    Vector3fi {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+});
+
+impl_op_ex!(-= |a: &mut Vector3fi, b: &Vector3fi| {
+    a.x -= b.x;
+    a.y -= b.y;
+    a.z -= b.z;
+});
+
+impl_op_ex!(*|a: &Vector3fi, b: &Interval| -> Vector3fi {
+    Vector3fi {
+        x: a.x * b,
+        y: a.y * b,
+        z: a.z * b,
     }
-    *v3 = v1.cross(&*v2);
+});
+
+impl_op_ex!(*|a: &Vector3fi, b: Float| -> Vector3fi {
+    Vector3fi {
+        x: a.x * b,
+        y: a.y * b,
+        z: a.z * b,
+    }
+});
+
+impl_op_ex!(/ |a: &Vector3fi, b: &Interval| -> Vector3fi {
+    Vector3fi {
+        x: a.x / b,
+        y: a.y / b,
+        z: a.z / b,
+    }
+});
+
+impl_op_ex!(/ |a: &Vector3fi, b: Float| -> Vector3fi {
+    Vector3fi {
+        x: a.x / b,
+        y: a.y / b,
+        z: a.z / b,
+    }
+});
+
+impl_op_ex!(-|a: &Vector3fi| -> Vector3fi {
+    Vector3fi {
+        x: -a.x,
+        y: -a.y,
+        z: -a.z,
+    }
+});
+
+// builds two vectors that together with the normalized v1 form an
+// orthonormal basis (Duff et al. 2017)
+pub fn coordinate_system(v1: &Vector3f) -> (Vector3f, Vector3f) {
+    let sign = (1.0 as Float).copysign(v1.z);
+    let a = -1.0 / (sign + v1.z);
+    let b = v1.x * v1.y * a;
+    let v2 = Vector3f {
+        x: 1.0 + sign * v1.x * v1.x * a,
+        y: sign * b,
+        z: -sign * v1.x,
+    };
+    let v3 = Vector3f {
+        x: b,
+        y: sign + v1.y * v1.y * a,
+        z: -v1.y,
+    };
+    (v2, v3)
+}
+
+// v with its component along the normalized w removed
+pub fn gram_schmidt(v: &Vector3f, w: &Vector3f) -> Vector3f {
+    *v - *w * v.dot(w)
 }
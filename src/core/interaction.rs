@@ -0,0 +1,335 @@
+use std::sync::Arc;
+
+use super::{
+    geometry::{
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::{offset_ray_origin, spawn_ray, spawn_ray_to_point3f, spawn_ray_to_point3fi, Ray},
+        vector::Vector3f,
+    },
+    medium::{Medium, MediumInterface},
+    pbrt::{solve_linear_system_2x2, Float},
+};
+
+// Interaction
+// a point where light interacts with the scene, n is zero for points in
+// participating media
+#[derive(Default, Clone)]
+pub struct Interaction {
+    pub pi: Point3fi,
+    pub time: Float,
+    pub wo: Vector3f,
+    pub n: Normal3f,
+    pub medium_interface: MediumInterface,
+}
+
+impl Interaction {
+    pub fn new(
+        pi: Point3fi,
+        n: Normal3f,
+        time: Float,
+        wo: Vector3f,
+        medium_interface: MediumInterface,
+    ) -> Interaction {
+        Interaction {
+            pi,
+            time,
+            wo,
+            n,
+            medium_interface,
+        }
+    }
+
+    pub fn p(&self) -> Point3f {
+        Point3f::from(self.pi)
+    }
+
+    pub fn is_surface_interaction(&self) -> bool {
+        self.n != Normal3f::default()
+    }
+
+    // the medium a ray leaving in direction w travels through
+    pub fn get_medium(&self, w: &Vector3f) -> Option<Arc<Medium>> {
+        if self.is_surface_interaction() && w.dot(&Vector3f::from(self.n)) > 0.0 {
+            self.medium_interface.outside.clone()
+        } else {
+            self.medium_interface.inside.clone()
+        }
+    }
+
+    pub fn offset_ray_origin(&self, w: &Vector3f) -> Point3f {
+        offset_ray_origin(&self.pi, &self.n, w)
+    }
+
+    pub fn spawn_ray(&self, d: &Vector3f) -> Ray {
+        let mut ray = spawn_ray(&self.pi, &self.n, self.time, d);
+        ray.medium = self.get_medium(d);
+        ray
+    }
+
+    pub fn spawn_ray_to_point3f(&self, p: &Point3f) -> Ray {
+        let mut ray = spawn_ray_to_point3f(&self.pi, &self.n, self.time, p);
+        ray.medium = self.get_medium(&ray.d);
+        ray
+    }
+
+    pub fn spawn_ray_to_interaction(&self, it: &Interaction) -> Ray {
+        let mut ray = spawn_ray_to_point3fi(&self.pi, &self.n, self.time, &it.pi, &it.n);
+        ray.medium = self.get_medium(&ray.d);
+        ray
+    }
+}
+
+// Shading
+// perturbed surface geometry, e.g. from interpolated vertex normals or bump
+// mapping, kept apart from the true geometry used for ray spawning
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Shading {
+    pub n: Normal3f,
+    pub dpdu: Vector3f,
+    pub dpdv: Vector3f,
+    pub dndu: Normal3f,
+    pub dndv: Normal3f,
+}
+
+// SurfaceInteraction
+// a ray hit on a shape, with the local differential geometry of the surface
+// around the hit point
+#[derive(Default, Clone)]
+pub struct SurfaceInteraction {
+    pub interaction: Interaction,
+    pub uv: Point2f,
+    pub dpdu: Vector3f,
+    pub dpdv: Vector3f,
+    pub dndu: Normal3f,
+    pub dndv: Normal3f,
+    pub shading: Shading,
+    // screen space derivatives, zero unless compute_differentials was called
+    // with a ray carrying differentials
+    pub dpdx: Vector3f,
+    pub dpdy: Vector3f,
+    pub dudx: Float,
+    pub dvdx: Float,
+    pub dudy: Float,
+    pub dvdy: Float,
+}
+
+impl SurfaceInteraction {
+    // the normal is dpdu x dpdv, flipped if the shape's orientation is
+    // reversed or its transform swaps handedness (but not both)
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pi: Point3fi,
+        uv: Point2f,
+        wo: Vector3f,
+        dpdu: Vector3f,
+        dpdv: Vector3f,
+        dndu: Normal3f,
+        dndv: Normal3f,
+        time: Float,
+        flip_normal: bool,
+    ) -> SurfaceInteraction {
+        let mut n = Normal3f::from(dpdu.cross(&dpdv).normalize());
+        if flip_normal {
+            n = -n;
+        }
+        SurfaceInteraction {
+            interaction: Interaction::new(pi, n, time, wo, MediumInterface::default()),
+            uv,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            // initialize shading geometry from true geometry
+            shading: Shading {
+                n,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn p(&self) -> Point3f {
+        self.interaction.p()
+    }
+
+    pub fn n(&self) -> Normal3f {
+        self.interaction.n
+    }
+
+    // if orientation_is_authoritative the geometric normal is flipped to the
+    // side of the shading normal, otherwise the other way around
+    pub fn set_shading_geometry(
+        &mut self,
+        dpdus: &Vector3f,
+        dpdvs: &Vector3f,
+        dndus: &Normal3f,
+        dndvs: &Normal3f,
+        orientation_is_authoritative: bool,
+    ) {
+        // compute shading normal for SurfaceInteraction
+        self.shading.n = Normal3f::from(dpdus.cross(dpdvs).normalize());
+        if orientation_is_authoritative {
+            self.interaction.n = self.interaction.n.face_forward(&self.shading.n);
+        } else {
+            self.shading.n = self.shading.n.face_forward(&self.interaction.n);
+        }
+
+        // initialize shading partial derivative values
+        self.shading.dpdu = *dpdus;
+        self.shading.dpdv = *dpdvs;
+        self.shading.dndu = *dndus;
+        self.shading.dndv = *dndvs;
+    }
+
+    // estimates how p and (u, v) change across the image from the offset
+    // rays of ray's differential, by intersecting them with the tangent plane
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        if let Some(diff) = ray.differential {
+            if let Some((dpdx, dpdy)) = self.tangent_plane_offsets(
+                &diff.rx_origin,
+                &diff.rx_direction,
+                &diff.ry_origin,
+                &diff.ry_direction,
+            ) {
+                self.dpdx = dpdx;
+                self.dpdy = dpdy;
+
+                // choose two dimensions to use for ray offset computation
+                let n = self.interaction.n;
+                let dim = if n.x.abs() > n.y.abs() && n.x.abs() > n.z.abs() {
+                    [1, 2]
+                } else if n.y.abs() > n.z.abs() {
+                    [0, 2]
+                } else {
+                    [0, 1]
+                };
+
+                // initialize matrices for chosen projection plane
+                let a = [
+                    [self.dpdu[dim[0]], self.dpdv[dim[0]]],
+                    [self.dpdu[dim[1]], self.dpdv[dim[1]]],
+                ];
+                let bx = [dpdx[dim[0]], dpdx[dim[1]]];
+                let by = [dpdy[dim[0]], dpdy[dim[1]]];
+                (self.dudx, self.dvdx) = solve_linear_system_2x2(&a, &bx).unwrap_or((0.0, 0.0));
+                (self.dudy, self.dvdy) = solve_linear_system_2x2(&a, &by).unwrap_or((0.0, 0.0));
+                return;
+            }
+        }
+        self.dpdx = Vector3f::default();
+        self.dpdy = Vector3f::default();
+        self.dudx = 0.0;
+        self.dvdx = 0.0;
+        self.dudy = 0.0;
+        self.dvdy = 0.0;
+    }
+
+    // p - the points where the offset rays hit the tangent plane at p
+    fn tangent_plane_offsets(
+        &self,
+        rx_origin: &Point3f,
+        rx_direction: &Vector3f,
+        ry_origin: &Point3f,
+        ry_direction: &Vector3f,
+    ) -> Option<(Vector3f, Vector3f)> {
+        let p = self.p();
+        let n = Vector3f::from(self.interaction.n);
+        let d = n.dot(&Vector3f::from(p));
+        let tx = -(n.dot(&Vector3f::from(*rx_origin)) - d) / n.dot(rx_direction);
+        let ty = -(n.dot(&Vector3f::from(*ry_origin)) - d) / n.dot(ry_direction);
+        if !tx.is_finite() || !ty.is_finite() {
+            return None;
+        }
+        let px = *rx_origin + *rx_direction * tx;
+        let py = *ry_origin + *ry_direction * ty;
+        Some((px - p, py - p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::core::{geometry::ray::RayDifferential, medium::NoMedium};
+
+    // the z = 0 plane with (u, v) = (x, y), hit at (1, 2, 0)
+    fn plane_hit() -> SurfaceInteraction {
+        SurfaceInteraction::new(
+            Point3fi::from(Point3f::new(1.0, 2.0, 0.0)),
+            Point2f::new(1.0, 2.0),
+            Vector3f::new(0.0, 0.0, 1.0),
+            Vector3f::new(1.0, 0.0, 0.0),
+            Vector3f::new(0.0, 1.0, 0.0),
+            Normal3f::default(),
+            Normal3f::default(),
+            0.0,
+            false,
+        )
+    }
+
+    #[test]
+    fn rays_pick_the_medium_on_their_side() {
+        let inside = Some(Arc::new(Medium::Empty(NoMedium {})));
+        let mut si = plane_hit();
+        si.interaction.medium_interface = MediumInterface::new(inside.clone(), None);
+        assert!(si.interaction.medium_interface.is_medium_transition());
+        let up = si.interaction.spawn_ray(&Vector3f::new(0.0, 0.0, 1.0));
+        assert!(up.medium.is_none());
+        let down = si.interaction.spawn_ray(&Vector3f::new(0.0, 0.0, -1.0));
+        assert!(Arc::ptr_eq(&down.medium.unwrap(), inside.as_ref().unwrap()));
+        assert!(!MediumInterface::from(inside).is_medium_transition());
+    }
+
+    #[test]
+    fn shading_normal_follows_the_geometric_normal() {
+        let mut si = plane_hit();
+        assert_eq!(si.shading.n, si.n());
+        let (dpdus, dpdvs) = (Vector3f::new(0.0, 1.0, 0.0), Vector3f::new(1.0, 0.0, 0.0));
+        si.set_shading_geometry(
+            &dpdus,
+            &dpdvs,
+            &Normal3f::default(),
+            &Normal3f::default(),
+            false,
+        );
+        assert_eq!(si.shading.n, Normal3f::new(0.0, 0.0, 1.0));
+        si.set_shading_geometry(
+            &dpdus,
+            &dpdvs,
+            &Normal3f::default(),
+            &Normal3f::default(),
+            true,
+        );
+        assert_eq!(si.n(), Normal3f::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn differentials_on_a_plane() {
+        let mut si = plane_hit();
+        let ray = Ray {
+            o: Point3f::new(1.0, 2.0, 1.0),
+            d: Vector3f::new(0.0, 0.0, -1.0),
+            t_max: Cell::new(Float::INFINITY),
+            differential: Some(RayDifferential {
+                rx_origin: Point3f::new(1.5, 2.0, 1.0),
+                ry_origin: Point3f::new(1.0, 2.0, 1.0),
+                rx_direction: Vector3f::new(0.0, 0.0, -1.0),
+                ry_direction: Vector3f::new(0.0, 0.25, -1.0),
+            }),
+            ..Default::default()
+        };
+        si.compute_differentials(&ray);
+        assert_eq!(si.dpdx, Vector3f::new(0.5, 0.0, 0.0));
+        assert_eq!(si.dpdy, Vector3f::new(0.0, 0.25, 0.0));
+        assert_eq!((si.dudx, si.dvdx, si.dudy, si.dvdy), (0.5, 0.0, 0.0, 0.25));
+
+        // a ray without differentials resets them
+        si.compute_differentials(&Ray::default());
+        assert_eq!((si.dpdx, si.dudx), (Vector3f::default(), 0.0));
+    }
+}
@@ -0,0 +1,285 @@
+use std::ops;
+
+use super::pbrt::{next_float_down, next_float_up, Float, PI, PI_OVER_2};
+
+// Interval
+// a closed range [low, high] of Float values, every operation rounds its
+// bounds outwards so that the exact result is always contained
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Interval {
+    pub low: Float,
+    pub high: Float,
+}
+
+impl Interval {
+    pub fn new(low: Float, high: Float) -> Interval {
+        Interval {
+            low: low.min(high),
+            high: low.max(high),
+        }
+    }
+
+    pub fn from_value_and_error(v: Float, err: Float) -> Interval {
+        if err == 0.0 {
+            Interval { low: v, high: v }
+        } else {
+            Interval {
+                low: next_float_down(v - err),
+                high: next_float_up(v + err),
+            }
+        }
+    }
+
+    pub fn upper_bound(&self) -> Float {
+        self.high
+    }
+
+    pub fn lower_bound(&self) -> Float {
+        self.low
+    }
+
+    pub fn midpoint(&self) -> Float {
+        (self.low + self.high) / 2.0
+    }
+
+    pub fn width(&self) -> Float {
+        self.high - self.low
+    }
+
+    pub fn exactly(&self, v: Float) -> bool {
+        self.low == v && self.high == v
+    }
+
+    pub fn in_range(&self, v: Float) -> bool {
+        v >= self.low && v <= self.high
+    }
+
+    pub fn overlaps(&self, i: &Interval) -> bool {
+        self.low <= i.high && self.high >= i.low
+    }
+
+    pub fn sqr(&self) -> Interval {
+        let alow = self.low.abs();
+        let ahigh = self.high.abs();
+        let (alow, ahigh) = if alow > ahigh {
+            (ahigh, alow)
+        } else {
+            (alow, ahigh)
+        };
+        if self.in_range(0.0) {
+            return Interval {
+                low: 0.0,
+                high: next_float_up(ahigh * ahigh),
+            };
+        }
+        Interval {
+            low: next_float_down(alow * alow),
+            high: next_float_up(ahigh * ahigh),
+        }
+    }
+
+    pub fn sqrt(&self) -> Interval {
+        Interval {
+            low: next_float_down(self.low.max(0.0).sqrt()),
+            high: next_float_up(self.high.sqrt()),
+        }
+    }
+
+    pub fn abs(&self) -> Interval {
+        if self.low >= 0.0 {
+            // the entire interval is greater than zero
+            *self
+        } else if self.high <= 0.0 {
+            // the entire interval is less than zero
+            -*self
+        } else {
+            // the interval straddles zero
+            Interval {
+                low: 0.0,
+                high: (-self.low).max(self.high),
+            }
+        }
+    }
+
+    // assumes the interval lies within [0, 2 pi]
+    pub fn sin(&self) -> Interval {
+        let mut low = self.low.max(0.0).sin();
+        let mut high = self.high.sin();
+        if low > high {
+            std::mem::swap(&mut low, &mut high);
+        }
+        low = next_float_down(low).max(-1.0);
+        high = next_float_up(high).min(1.0);
+        if self.in_range(PI_OVER_2) {
+            high = 1.0;
+        }
+        if self.in_range(3.0 * PI_OVER_2) {
+            low = -1.0;
+        }
+        Interval { low, high }
+    }
+
+    // assumes the interval lies within [0, 2 pi]
+    pub fn cos(&self) -> Interval {
+        let mut low = self.low.max(0.0).cos();
+        let mut high = self.high.cos();
+        if low > high {
+            std::mem::swap(&mut low, &mut high);
+        }
+        low = next_float_down(low).max(-1.0);
+        high = next_float_up(high).min(1.0);
+        if self.in_range(PI) {
+            low = -1.0;
+        }
+        Interval { low, high }
+    }
+}
+
+impl From<Float> for Interval {
+    fn from(v: Float) -> Interval {
+        Interval { low: v, high: v }
+    }
+}
+
+// collapses the interval to its midpoint
+impl From<Interval> for Float {
+    fn from(i: Interval) -> Float {
+        i.midpoint()
+    }
+}
+
+impl_op_ex!(+ |a: &Interval, b: &Interval| -> Interval {
+    Interval {
+        low: next_float_down(a.low + b.low),
+        high: next_float_up(a.high + b.high),
+    }
+});
+
+impl_op_ex!(+= |a: &mut Interval, b: &Interval| {
+    *a = *a + b;
+});
+
+impl_op_ex!(-|a: &Interval, b: &Interval| -> Interval {
+    Interval {
+        low: next_float_down(a.low - b.high),
+        high: next_float_up(a.high - b.low),
+    }
+});
+
+impl_op_ex!(-= |a: &mut Interval, b: &Interval| {
+    *a = *a - b;
+});
+
+impl_op_ex!(*|a: &Interval, b: &Interval| -> Interval {
+    let prod = [
+        a.low * b.low,
+        a.high * b.low,
+        a.low * b.high,
+        a.high * b.high,
+    ];
+    Interval {
+        low: next_float_down(prod[0].min(prod[1]).min(prod[2].min(prod[3]))),
+        high: next_float_up(prod[0].max(prod[1]).max(prod[2].max(prod[3]))),
+    }
+});
+
+impl_op_ex!(*= |a: &mut Interval, b: &Interval| {
+    *a = *a * b;
+});
+
+impl_op_ex!(/ |a: &Interval, b: &Interval| -> Interval {
+    if b.in_range(0.0) {
+        // the interval we're dividing by straddles zero so just return an
+        // interval of everything
+        return Interval {
+            low: -Float::INFINITY,
+            high: Float::INFINITY,
+        };
+    }
+    let div = [
+        a.low / b.low,
+        a.high / b.low,
+        a.low / b.high,
+        a.high / b.high,
+    ];
+    Interval {
+        low: next_float_down(div[0].min(div[1]).min(div[2].min(div[3]))),
+        high: next_float_up(div[0].max(div[1]).max(div[2].max(div[3]))),
+    }
+});
+
+impl_op_ex!(/= |a: &mut Interval, b: &Interval| {
+    *a = *a / b;
+});
+
+impl_op_ex!(-|a: &Interval| -> Interval {
+    Interval {
+        low: -a.high,
+        high: -a.low,
+    }
+});
+
+impl_op_ex!(+ |a: &Interval, b: Float| -> Interval { a + Interval::from(b) });
+
+impl_op_ex!(+ |a: Float, b: &Interval| -> Interval { Interval::from(a) + b });
+
+impl_op_ex!(-|a: &Interval, b: Float| -> Interval { a - Interval::from(b) });
+
+impl_op_ex!(-|a: Float, b: &Interval| -> Interval { Interval::from(a) - b });
+
+impl_op_ex!(*|a: &Interval, b: Float| -> Interval { a * Interval::from(b) });
+
+impl_op_ex!(*|a: Float, b: &Interval| -> Interval { Interval::from(a) * b });
+
+impl_op_ex!(/ |a: &Interval, b: Float| -> Interval { a / Interval::from(b) });
+
+impl_op_ex!(/ |a: Float, b: &Interval| -> Interval { Interval::from(a) / b });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::geometry::{
+        point::{Point3f, Point3fi},
+        vector::Vector3f,
+    };
+
+    #[test]
+    fn arithmetic_contains_exact_results() {
+        let a = Interval::from_value_and_error(1.5, 0.25);
+        let b = Interval::new(-2.0, 3.0);
+        assert!((a + b).in_range(1.25 + 3.0) && (a + b).in_range(1.75 - 2.0));
+        assert!((a - b).in_range(1.25 - 3.0) && (a - b).in_range(1.75 + 2.0));
+        assert!((a * b).in_range(1.75 * -2.0) && (a * b).in_range(1.75 * 3.0));
+        assert!((1.0 / a).in_range(1.0 / 1.25) && (1.0 / a).in_range(1.0 / 1.75));
+        // dividing by an interval that straddles zero gives everything
+        assert_eq!((a / b).upper_bound(), Float::INFINITY);
+        assert_eq!(b.sqr().lower_bound(), 0.0);
+        assert!(b.sqr().in_range(9.0));
+        assert!(Interval::new(4.0, 9.0).sqrt().in_range(2.0));
+        assert_eq!(Interval::new(-3.0, 1.0).abs(), Interval::new(0.0, 3.0));
+    }
+
+    #[test]
+    fn sin_and_cos_cover_extrema() {
+        let i = Interval::new(1.0, 5.0);
+        assert_eq!(i.sin().upper_bound(), 1.0);
+        assert_eq!(i.sin().lower_bound(), -1.0);
+        assert_eq!(i.cos().lower_bound(), -1.0);
+        assert!(i.cos().in_range((5.0 as Float).cos()));
+        let small = Interval::new(0.1, 0.2);
+        assert!(small.sin().in_range((0.15 as Float).sin()));
+        assert!(small.sin().upper_bound() < 0.2);
+    }
+
+    #[test]
+    fn interval_points_track_their_error() {
+        let p =
+            Point3fi::new_with_error(&Point3f::new(1.0, 2.0, 3.0), &Vector3f::new(0.1, 0.0, 0.2));
+        assert!(!p.is_exact());
+        let e = p.error();
+        assert!(e.x >= 0.1 && e.y == 0.0 && e.z >= 0.2);
+        let q = p * 2.0;
+        assert!(q.error().x >= 0.2);
+        assert!((q.midpoint() - Point3f::new(2.0, 4.0, 6.0)).length() < 1e-5);
+    }
+}
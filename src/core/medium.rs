@@ -1,5 +1,40 @@
+use std::sync::Arc;
+
 pub struct NoMedium {}
 
 pub enum Medium {
     Empty(NoMedium),
 }
+
+// MediumInterface
+// the media on either side of a surface, outside is the side the surface
+// normal points to. None is a vacuum
+#[derive(Default, Clone)]
+pub struct MediumInterface {
+    pub inside: Option<Arc<Medium>>,
+    pub outside: Option<Arc<Medium>>,
+}
+
+impl MediumInterface {
+    pub fn new(inside: Option<Arc<Medium>>, outside: Option<Arc<Medium>>) -> MediumInterface {
+        MediumInterface { inside, outside }
+    }
+
+    pub fn is_medium_transition(&self) -> bool {
+        match (&self.inside, &self.outside) {
+            (Some(inside), Some(outside)) => !Arc::ptr_eq(inside, outside),
+            (None, None) => false,
+            _ => true,
+        }
+    }
+}
+
+// the same medium on both sides
+impl From<Option<Arc<Medium>>> for MediumInterface {
+    fn from(medium: Option<Arc<Medium>>) -> MediumInterface {
+        MediumInterface {
+            inside: medium.clone(),
+            outside: medium,
+        }
+    }
+}
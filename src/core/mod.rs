@@ -1,3 +1,9 @@
+pub mod efloat;
 pub mod geometry;
+pub mod interaction;
+pub mod interval;
 pub mod medium;
 pub mod pbrt;
+pub mod sampling;
+pub mod shape;
+pub mod texture;
@@ -1,5 +1,233 @@
+use super::{efloat::EFloat, interval::Interval};
+
+#[cfg(not(feature = "float-f32"))]
 pub type Float = f64;
+#[cfg(feature = "float-f32")]
+pub type Float = f32;
+
+#[cfg(feature = "float-f32")]
+use std::f32::consts;
+#[cfg(not(feature = "float-f32"))]
+use std::f64::consts;
+
+pub const PI: Float = consts::PI;
+pub const INV_PI: Float = consts::FRAC_1_PI;
+pub const INV_2_PI: Float = consts::FRAC_1_PI * 0.5;
+pub const INV_4_PI: Float = consts::FRAC_1_PI * 0.25;
+pub const PI_OVER_2: Float = consts::FRAC_PI_2;
+pub const PI_OVER_4: Float = consts::FRAC_PI_4;
+
+pub const MACHINE_EPSILON: Float = Float::EPSILON * 0.5;
+pub const SHADOW_EPSILON: Float = 0.0001;
+// largest float strictly less than one
+pub const ONE_MINUS_EPSILON: Float = 1.0 - MACHINE_EPSILON;
 
 pub fn lerp(t: Float, v1: Float, v2: Float) -> Float {
     (1.0 - t) * v1 + t * v2
 }
+
+// conservative bound on the relative error of n floating-point operations
+pub fn gamma(n: i32) -> Float {
+    (n as Float * MACHINE_EPSILON) / (1.0 - n as Float * MACHINE_EPSILON)
+}
+
+// smallest representable float strictly greater than v
+pub fn next_float_up(v: Float) -> Float {
+    if v.is_infinite() && v > 0.0 {
+        return v;
+    }
+    // treat -0.0 as 0.0 so that the next float up is the smallest positive value
+    let v = if v == -0.0 { 0.0 } else { v };
+    let bits = v.to_bits();
+    let bits = if v >= 0.0 { bits + 1 } else { bits - 1 };
+    Float::from_bits(bits)
+}
+
+// largest representable float strictly less than v
+pub fn next_float_down(v: Float) -> Float {
+    if v.is_infinite() && v < 0.0 {
+        return v;
+    }
+    // treat 0.0 as -0.0 so that the next float down is the smallest negative value
+    let v = if v == 0.0 { -0.0 } else { v };
+    let bits = v.to_bits();
+    let bits = if v > 0.0 { bits - 1 } else { bits + 1 };
+    Float::from_bits(bits)
+}
+
+pub fn clamp(val: Float, low: Float, high: Float) -> Float {
+    if val < low {
+        low
+    } else if val > high {
+        high
+    } else {
+        val
+    }
+}
+
+pub fn safe_asin(x: Float) -> Float {
+    clamp(x, -1.0, 1.0).asin()
+}
+
+pub fn safe_acos(x: Float) -> Float {
+    clamp(x, -1.0, 1.0).acos()
+}
+
+pub fn safe_sqrt(x: Float) -> Float {
+    x.max(0.0).sqrt()
+}
+
+// a * b - c * d without the catastrophic cancellation of the direct form
+pub fn difference_of_products(a: Float, b: Float, c: Float, d: Float) -> Float {
+    let cd = c * d;
+    let difference = a.mul_add(b, -cd);
+    let error = (-c).mul_add(d, cd);
+    difference + error
+}
+
+// real roots of a t^2 + b t + c in ascending order, a linear equation if a
+// is zero
+pub fn quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+    let discrim = difference_of_products(b, b, 4.0 * a, c);
+    if discrim < 0.0 {
+        return None;
+    }
+    let root_discrim = discrim.sqrt();
+    let q = -0.5 * (b + root_discrim.copysign(b));
+    let t0 = q / a;
+    let t1 = c / q;
+    if t0 > t1 {
+        Some((t1, t0))
+    } else {
+        Some((t0, t1))
+    }
+}
+
+// solves a t^2 + b t + c = 0, returning the roots in increasing order
+// together with conservative error bounds
+#[allow(clippy::unnecessary_cast)]
+pub fn quadratic_efloat(a: EFloat, b: EFloat, c: EFloat) -> Option<(EFloat, EFloat)> {
+    // find quadratic discriminant in double precision
+    let discrim = b.value() as f64 * b.value() as f64 - 4.0 * a.value() as f64 * c.value() as f64;
+    if discrim < 0.0 {
+        return None;
+    }
+    let root_discrim = discrim.sqrt();
+    let float_root_discrim = EFloat::new(
+        root_discrim as Float,
+        MACHINE_EPSILON * root_discrim as Float,
+    );
+
+    // compute quadratic t values
+    let q = if b.value() < 0.0 {
+        (b - float_root_discrim) * -0.5
+    } else {
+        (b + float_root_discrim) * -0.5
+    };
+    let t0 = q / a;
+    let t1 = c / q;
+    if t0.value() > t1.value() {
+        Some((t1, t0))
+    } else {
+        Some((t0, t1))
+    }
+}
+
+// quadratic with interval coefficients, returning the roots ordered by their
+// lower bounds, or None if the roots may not be real
+pub fn quadratic_interval(a: Interval, b: Interval, c: Interval) -> Option<(Interval, Interval)> {
+    let discrim = b.sqr() - 4.0 * a * c;
+    if discrim.lower_bound() < 0.0 {
+        return None;
+    }
+    let root_discrim = discrim.sqrt();
+
+    // compute quadratic t values
+    let q = if Float::from(b) < 0.0 {
+        -0.5 * (b - root_discrim)
+    } else {
+        -0.5 * (b + root_discrim)
+    };
+    let t0 = q / a;
+    let t1 = c / q;
+    if t0.lower_bound() > t1.lower_bound() {
+        Some((t1, t0))
+    } else {
+        Some((t0, t1))
+    }
+}
+
+pub fn radians(deg: Float) -> Float {
+    (PI / 180.0) * deg
+}
+
+pub fn degrees(rad: Float) -> Float {
+    (180.0 / PI) * rad
+}
+
+// solves a x = b, returns None if a is (nearly) singular
+pub fn solve_linear_system_2x2(a: &[[Float; 2]; 2], b: &[Float; 2]) -> Option<(Float, Float)> {
+    let det = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+    if det.abs() < 1e-10 {
+        return None;
+    }
+    let x0 = (a[1][1] * b[0] - a[0][1] * b[1]) / det;
+    let x1 = (a[0][0] * b[1] - a[1][0] * b[0]) / det;
+    if x0.is_nan() || x1.is_nan() {
+        return None;
+    }
+    Some((x0, x1))
+}
+
+// scrambles the bits of v so that nearby inputs give unrelated outputs
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+// a deterministic pseudo-random value in [0, 1) derived from values
+#[allow(clippy::unnecessary_cast)]
+pub fn hash_float(values: &[Float]) -> Float {
+    let h = values
+        .iter()
+        .fold(0, |h, v| mix_bits(h ^ v.to_bits() as u64));
+    ((h >> 11) as Float / (1u64 << 53) as Float).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_quadratic_brackets_the_roots() {
+        // (t - 1) (t - 3) with slightly uncertain coefficients
+        let a = Interval::from_value_and_error(1.0, 1e-6);
+        let b = Interval::from_value_and_error(-4.0, 1e-6);
+        let c = Interval::from(3.0);
+        let (t0, t1) = quadratic_interval(a, b, c).unwrap();
+        assert!(t0.in_range(1.0) && t1.in_range(3.0));
+        assert!(quadratic_interval(Interval::from(1.0), Interval::from(0.0), c).is_none());
+    }
+
+    #[test]
+    fn quadratic_roots_are_accurate() {
+        assert_eq!(quadratic(1.0, -4.0, 3.0), Some((1.0, 3.0)));
+        assert_eq!(quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert_eq!(quadratic(1.0, 0.0, 1.0), None);
+        // b^2 dominates 4ac, the small root must not cancel away
+        let (t0, t1) = quadratic(1.0, 1e8, 1.0).unwrap();
+        assert!((t0 + 1e8).abs() < 1e-2);
+        assert!((t1 + 1e-8).abs() < 1e-14);
+        assert_eq!(difference_of_products(3.0, 4.0, 2.0, 5.0), 2.0);
+    }
+}
@@ -0,0 +1,451 @@
+use super::{
+    geometry::{
+        point::{Point2f, Point3f},
+        spherical::spherical_quad_area,
+        vector::{gram_schmidt, Vector3f},
+    },
+    pbrt::{clamp, difference_of_products, lerp, safe_sqrt, Float, ONE_MINUS_EPSILON, PI},
+};
+
+// barycentric coordinates uniformly distributed over a triangle, without
+// the square root of the classic warp so that nearby samples stay close
+pub fn sample_uniform_triangle(u: &Point2f) -> [Float; 3] {
+    let (b0, b1) = if u.x < u.y {
+        let b0 = u.x / 2.0;
+        (b0, u.y - b0)
+    } else {
+        let b1 = u.y / 2.0;
+        (u.x - b1, b1)
+    };
+    [b0, b1, 1.0 - b0 - b1]
+}
+
+// index of the entry picked with probability proportional to its weight,
+// given the running sums of the weights in cdf, and u remapped to [0, 1)
+// within that entry so it can be reused
+pub fn sample_cdf(cdf: &[Float], u: Float) -> (usize, Float) {
+    let total = cdf[cdf.len() - 1];
+    let target = u * total;
+    let i = cdf.partition_point(|&c| c <= target).min(cdf.len() - 1);
+    let low = if i == 0 { 0.0 } else { cdf[i - 1] };
+    let width = cdf[i] - low;
+    let u_remapped = if width > 0.0 {
+        ((target - low) / width).min(ONE_MINUS_EPSILON)
+    } else {
+        0.0
+    };
+    (i, u_remapped)
+}
+
+// x in [0, 1) distributed proportionally to the line from a at 0 to b at 1
+pub fn sample_linear(u: Float, a: Float, b: Float) -> Float {
+    if u == 0.0 && a == 0.0 {
+        return 0.0;
+    }
+    let x = u * (a + b) / (a + lerp(u, a * a, b * b).sqrt());
+    x.min(ONE_MINUS_EPSILON)
+}
+
+// a point of the unit square distributed proportionally to the bilinear
+// function with corner values w = [w00, w10, w01, w11]
+pub fn sample_bilinear(u: &Point2f, w: &[Float; 4]) -> Point2f {
+    // sample y for bilinear marginal distribution
+    let y = sample_linear(u.y, w[0] + w[1], w[2] + w[3]);
+    // sample x for bilinear conditional distribution
+    let x = sample_linear(u.x, lerp(y, w[0], w[2]), lerp(y, w[1], w[3]));
+    Point2f::new(x, y)
+}
+
+pub fn bilinear_pdf(p: &Point2f, w: &[Float; 4]) -> Float {
+    if p.x < 0.0 || p.x > 1.0 || p.y < 0.0 || p.y > 1.0 {
+        return 0.0;
+    }
+    let sum = w[0] + w[1] + w[2] + w[3];
+    if sum == 0.0 {
+        return 1.0;
+    }
+    4.0 * ((1.0 - p.x) * (1.0 - p.y) * w[0]
+        + p.x * (1.0 - p.y) * w[1]
+        + (1.0 - p.x) * p.y * w[2]
+        + p.x * p.y * w[3])
+        / sum
+}
+
+// a point on the rectangle s + [0, 1] ex + [0, 1] ey distributed uniformly
+// over the solid angle it subtends from p_ref (Urena et al. 2013), returned
+// with the pdf with respect to solid angle
+pub fn sample_spherical_rectangle(
+    p_ref: &Point3f,
+    s: &Point3f,
+    ex: &Vector3f,
+    ey: &Vector3f,
+    u: &Point2f,
+) -> (Point3f, Float) {
+    // compute local reference frame and transform rectangle coordinates
+    let exl = ex.length();
+    let eyl = ey.length();
+    let x_axis = *ex / exl;
+    let y_axis = *ey / eyl;
+    let mut z_axis = x_axis.cross(&y_axis);
+    let d_local = *s - *p_ref;
+    let mut z0 = d_local.dot(&z_axis);
+    // flip z to make it point against the rectangle
+    if z0 > 0.0 {
+        z_axis = -z_axis;
+        z0 = -z0;
+    }
+    let x0 = d_local.dot(&x_axis);
+    let y0 = d_local.dot(&y_axis);
+    let x1 = x0 + exl;
+    let y1 = y0 + eyl;
+
+    // find plane normals to rectangle edges and compute internal angles
+    let v00 = Vector3f::new(x0, y0, z0);
+    let v01 = Vector3f::new(x0, y1, z0);
+    let v10 = Vector3f::new(x1, y0, z0);
+    let v11 = Vector3f::new(x1, y1, z0);
+    let n0 = v00.cross(&v10).normalize();
+    let n1 = v10.cross(&v11).normalize();
+    let n2 = v11.cross(&v01).normalize();
+    let n3 = v01.cross(&v00).normalize();
+    let g0 = (-n0).angle_between(&n1);
+    let g1 = (-n1).angle_between(&n2);
+    let g2 = (-n2).angle_between(&n3);
+    let g3 = (-n3).angle_between(&n0);
+
+    // compute spherical rectangle solid angle and pdf
+    let solid_angle = g0 + g1 + g2 + g3 - 2.0 * PI;
+    let on_rectangle = *s + *ex * u.x + *ey * u.y;
+    if solid_angle <= 0.0 {
+        return (on_rectangle, 0.0);
+    }
+    let pdf = 1.0 / solid_angle;
+    if solid_angle < 1e-3 {
+        return (on_rectangle, pdf);
+    }
+
+    // sample cu for spherical rectangle sample
+    let b0 = n0.z;
+    let b1 = n2.z;
+    let au = u.x * (g0 + g1 - 2.0 * PI) + (u.x - 1.0) * (g2 + g3);
+    let fu = (au.cos() * b0 - b1) / au.sin();
+    let cu = (1.0 / (fu * fu + b0 * b0).sqrt()).copysign(fu);
+    // avoid NaNs
+    let cu = clamp(cu, -ONE_MINUS_EPSILON, ONE_MINUS_EPSILON);
+
+    // find xu along x edge for spherical rectangle sample
+    let xu = clamp(-(cu * z0) / safe_sqrt(1.0 - cu * cu), x0, x1);
+
+    // find yv along y edge for spherical rectangle sample
+    let dd = (xu * xu + z0 * z0).sqrt();
+    let h0 = y0 / (dd * dd + y0 * y0).sqrt();
+    let h1 = y1 / (dd * dd + y1 * y1).sqrt();
+    let hv = h0 + u.y * (h1 - h0);
+    let hvsq = hv * hv;
+    let yv = if hvsq < 1.0 - 1e-6 {
+        (hv * dd) / (1.0 - hvsq).sqrt()
+    } else {
+        y1
+    };
+
+    // return spherical rectangle sample in original coordinate system
+    (*p_ref + x_axis * xu + y_axis * yv + z_axis * z0, pdf)
+}
+
+// the sample u for which sample_spherical_rectangle returns p_rect
+pub fn invert_spherical_rectangle_sample(
+    p_ref: &Point3f,
+    s: &Point3f,
+    ex: &Vector3f,
+    ey: &Vector3f,
+    p_rect: &Point3f,
+) -> Point2f {
+    // compute local reference frame and transform rectangle coordinates
+    let exl = ex.length();
+    let eyl = ey.length();
+    let x_axis = *ex / exl;
+    let y_axis = *ey / eyl;
+    let z_axis = x_axis.cross(&y_axis);
+    let d_local = *s - *p_ref;
+    let z0 = -d_local.dot(&z_axis).abs();
+    let x0 = d_local.dot(&x_axis);
+    let y0 = d_local.dot(&y_axis);
+    let x1 = x0 + exl;
+    let y1 = y0 + eyl;
+    let p_local = *p_rect - *p_ref;
+    let xu = clamp(p_local.dot(&x_axis), x0, x1);
+    let yv = clamp(p_local.dot(&y_axis), y0, y1);
+
+    let corner = |x: Float, y: Float| Vector3f::new(x, y, z0).normalize();
+    let solid_angle = spherical_quad_area(
+        &corner(x0, y0),
+        &corner(x1, y0),
+        &corner(x1, y1),
+        &corner(x0, y1),
+    );
+    if solid_angle < 1e-3 {
+        // sample_spherical_rectangle samples by area for small solid angles
+        return Point2f::new((xu - x0) / exl, (yv - y0) / eyl);
+    }
+
+    // invert the area of the sub-rectangle left of xu to find u.x
+    let sub_area = spherical_quad_area(
+        &corner(x0, y0),
+        &corner(xu, y0),
+        &corner(xu, y1),
+        &corner(x0, y1),
+    );
+    let ux = sub_area / solid_angle;
+
+    // invert the interpolation of h along the y edge to find u.y
+    let dd2 = xu * xu + z0 * z0;
+    let h0 = y0 / (dd2 + y0 * y0).sqrt();
+    let h1 = y1 / (dd2 + y1 * y1).sqrt();
+    let hv = yv / (dd2 + yv * yv).sqrt();
+    let uy = if h1 == h0 { 0.5 } else { (hv - h0) / (h1 - h0) };
+    Point2f::new(clamp(ux, 0.0, 1.0), clamp(uy, 0.0, 1.0))
+}
+
+// angles at the vertices a, b and c of the spherical triangle given by
+// three normalized directions, with the normalized edge plane normals
+// n_ab, n_bc and n_ca, or None if it is degenerate
+fn spherical_triangle_angles(
+    a: &Vector3f,
+    b: &Vector3f,
+    c: &Vector3f,
+) -> Option<([Float; 3], [Vector3f; 3])> {
+    // compute normalized cross products of all direction pairs
+    let n_ab = a.cross(b);
+    let n_bc = b.cross(c);
+    let n_ca = c.cross(a);
+    if n_ab.length_squared() == 0.0 || n_bc.length_squared() == 0.0 || n_ca.length_squared() == 0.0
+    {
+        return None;
+    }
+    let n_ab = n_ab.normalize();
+    let n_bc = n_bc.normalize();
+    let n_ca = n_ca.normalize();
+
+    // find angles alpha, beta and gamma at spherical triangle vertices
+    let alpha = n_ab.angle_between(&-n_ca);
+    let beta = n_bc.angle_between(&-n_ab);
+    let gamma = n_ca.angle_between(&-n_bc);
+    Some(([alpha, beta, gamma], [n_ab, n_bc, n_ca]))
+}
+
+// barycentric coordinates of a point on the triangle v distributed uniformly
+// over the solid angle it subtends from p (Arvo 1995), returned with the pdf
+// with respect to solid angle, or None if the triangle is degenerate as seen
+// from p
+pub fn sample_spherical_triangle(
+    v: &[Point3f; 3],
+    p: &Point3f,
+    u: &Point2f,
+) -> Option<([Float; 3], Float)> {
+    // compute vectors a, b and c to spherical triangle vertices
+    let a = (v[0] - *p).normalize();
+    let b = (v[1] - *p).normalize();
+    let c = (v[2] - *p).normalize();
+    let ([alpha, beta, gamma], _) = spherical_triangle_angles(&a, &b, &c)?;
+
+    // uniformly sample triangle area A to compute A'
+    let a_pi = alpha + beta + gamma;
+    let ap_pi = lerp(u.x, PI, a_pi);
+    let area = a_pi - PI;
+    if area <= 0.0 {
+        return None;
+    }
+    let pdf = 1.0 / area;
+
+    // find cos beta' for point along b for sampled area
+    let (sin_alpha, cos_alpha) = alpha.sin_cos();
+    let sin_phi = ap_pi.sin() * cos_alpha - ap_pi.cos() * sin_alpha;
+    let cos_phi = ap_pi.cos() * cos_alpha + ap_pi.sin() * sin_alpha;
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(&b);
+    let cos_bp = (k2 + difference_of_products(k2, cos_phi, k1, sin_phi) * cos_alpha)
+        / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha);
+    // happens if the triangle basically covers the entire hemisphere
+    let cos_bp = clamp(cos_bp, -1.0, 1.0);
+
+    // sample c' along the arc between b' and a
+    let sin_bp = safe_sqrt(1.0 - cos_bp * cos_bp);
+    let cp = a * cos_bp + gram_schmidt(&c, &a).normalize() * sin_bp;
+
+    // compute sampled spherical triangle direction
+    let cos_theta = 1.0 - u.y * (1.0 - cp.dot(&b));
+    let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+    let w = b * cos_theta + gram_schmidt(&cp, &b).normalize() * sin_theta;
+
+    // find barycentric coordinates for sampled direction w
+    let e1 = v[1] - v[0];
+    let e2 = v[2] - v[0];
+    let s1 = w.cross(&e2);
+    let divisor = s1.dot(&e1);
+    if divisor == 0.0 {
+        // the triangle is seen edge-on, so any point of it will do
+        return Some(([1.0, 0.0, 0.0], pdf));
+    }
+    let inv_divisor = 1.0 / divisor;
+    let s = *p - v[0];
+    let mut b1 = clamp(s.dot(&s1) * inv_divisor, 0.0, 1.0);
+    let mut b2 = clamp(w.dot(&s.cross(&e1)) * inv_divisor, 0.0, 1.0);
+    if b1 + b2 > 1.0 {
+        let sum = b1 + b2;
+        b1 /= sum;
+        b2 /= sum;
+    }
+    Some(([1.0 - b1 - b2, b1, b2], pdf))
+}
+
+// the sample u for which sample_spherical_triangle returns the point in
+// direction w from p
+pub fn invert_spherical_triangle_sample(v: &[Point3f; 3], p: &Point3f, w: &Vector3f) -> Point2f {
+    // compute vectors a, b and c to spherical triangle vertices
+    let a = (v[0] - *p).normalize();
+    let b = (v[1] - *p).normalize();
+    let c = (v[2] - *p).normalize();
+    let Some(([alpha, beta, gamma], [n_ab, _, _])) = spherical_triangle_angles(&a, &b, &c) else {
+        return Point2f::default();
+    };
+
+    // find vertex c' along the a-c arc for w
+    let mut cp = b.cross(w).cross(&c.cross(&a)).normalize();
+    if cp.dot(&(a + c)) < 0.0 {
+        cp = -cp;
+    }
+
+    // invert uniform area sampling to find u.x
+    let ux = if a.dot(&cp) > 0.999_998_5 {
+        // c' is within 0.1 degrees of a
+        0.0
+    } else {
+        // compute area A' of subtriangle
+        let n_cpb = cp.cross(&b);
+        let n_acp = a.cross(&cp);
+        if n_cpb.length_squared() == 0.0 || n_acp.length_squared() == 0.0 {
+            return Point2f::new(0.5, 0.5);
+        }
+        let n_cpb = n_cpb.normalize();
+        let n_acp = n_acp.normalize();
+        let ap = alpha + n_ab.angle_between(&n_cpb) + n_acp.angle_between(&-n_cpb) - PI;
+
+        // compute sample u.x that gives the area A'
+        ap / (alpha + beta + gamma - PI)
+    };
+
+    // invert arc sampling to find u.y
+    let uy = (1.0 - w.dot(&b)) / (1.0 - cp.dot(&b));
+    Point2f::new(clamp(ux, 0.0, 1.0), clamp(uy, 0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_triangle_samples_are_barycentric() {
+        let mut counts = [0; 2];
+        for i in 0..32 {
+            for j in 0..32 {
+                let u = Point2f::new((i as Float + 0.5) / 32.0, (j as Float + 0.5) / 32.0);
+                let b = sample_uniform_triangle(&u);
+                assert!(b.iter().all(|&b| (0.0..=1.0).contains(&b)));
+                assert!((b[0] + b[1] + b[2] - 1.0).abs() < 1e-6);
+                // the triangle's two halves on either side of b0 = b1 have
+                // equal area and get equally many samples
+                if b[0] != b[1] {
+                    counts[(b[0] > b[1]) as usize] += 1;
+                }
+            }
+        }
+        assert_eq!(counts[0], counts[1]);
+    }
+
+    #[test]
+    fn bilinear_samples_follow_their_pdf() {
+        let w = [1.0, 3.0, 0.5, 2.0];
+        // the pdf integrates to one over the unit square
+        let n = 64;
+        let mut integral = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let p = Point2f::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                integral += bilinear_pdf(&p, &w) / (n * n) as Float;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-4);
+        // samples are monotonic in u and cover the square
+        let a = sample_bilinear(&Point2f::new(0.2, 0.3), &w);
+        let b = sample_bilinear(&Point2f::new(0.6, 0.3), &w);
+        assert!(a.x < b.x && a.y == b.y);
+        assert_eq!(
+            sample_bilinear(&Point2f::new(0.0, 0.0), &w),
+            Point2f::new(0.0, 0.0)
+        );
+        assert!(sample_linear(0.999_999, 1.0, 2.0) < 1.0);
+    }
+
+    #[test]
+    fn spherical_rectangle_samples_invert() {
+        let p_ref = Point3f::new(0.3, -0.2, 1.0);
+        let s = Point3f::new(-1.0, -1.0, 0.0);
+        let (ex, ey) = (Vector3f::new(2.0, 0.0, 0.0), Vector3f::new(0.0, 1.5, 0.0));
+        for u in [
+            Point2f::new(0.1, 0.2),
+            Point2f::new(0.5, 0.5),
+            Point2f::new(0.9, 0.7),
+        ] {
+            let (p, pdf) = sample_spherical_rectangle(&p_ref, &s, &ex, &ey, &u);
+            assert!(pdf > 0.0);
+            assert!(p.z.abs() < 1e-6);
+            assert!((-1.0..=1.0).contains(&p.x) && (-1.0..=0.5).contains(&p.y));
+            let v = invert_spherical_rectangle_sample(&p_ref, &s, &ex, &ey, &p);
+            assert!((v - u).length() < 1e-4, "{:?} -> {:?}", u, v);
+        }
+    }
+
+    #[test]
+    fn spherical_triangle_samples_invert() {
+        let v = [
+            Point3f::new(-1.0, -1.0, 0.0),
+            Point3f::new(1.0, -1.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+        ];
+        let p = Point3f::new(0.2, 0.1, 1.0);
+        for u in [
+            Point2f::new(0.1, 0.2),
+            Point2f::new(0.5, 0.5),
+            Point2f::new(0.8, 0.9),
+        ] {
+            let (b, pdf) = sample_spherical_triangle(&v, &p, &u).unwrap();
+            assert!(pdf > 0.0);
+            assert!(b.iter().all(|&b| (0.0..=1.0).contains(&b)));
+            let q = v[0] * b[0] + v[1] * b[1] + v[2] * b[2];
+            let w = (q - p).normalize();
+            let back = invert_spherical_triangle_sample(&v, &p, &w);
+            assert!((back - u).length() < 1e-3, "{:?} -> {:?}", u, back);
+        }
+        // seen edge-on the triangle has no solid angle to sample
+        assert!(sample_spherical_triangle(
+            &v,
+            &Point3f::new(5.0, 0.0, 0.0),
+            &Point2f::new(0.5, 0.5)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn cdf_samples_are_proportional_to_weights() {
+        let cdf = [1.0, 1.0, 4.0];
+        assert_eq!(sample_cdf(&cdf, 0.0), (0, 0.0));
+        // the zero weight entry is never picked
+        let (i, u) = sample_cdf(&cdf, 0.5);
+        assert_eq!(i, 2);
+        assert!((u - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(sample_cdf(&cdf, ONE_MINUS_EPSILON).0, 2);
+    }
+}
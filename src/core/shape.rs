@@ -0,0 +1,169 @@
+use super::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::Vector3f,
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    medium::MediumInterface,
+    pbrt::Float,
+};
+
+// solid angles outside this range are sampled by area instead, the spherical
+// sampling warps are numerically unreliable for them
+pub const MIN_SPHERICAL_SAMPLE_AREA: Float = 3e-4;
+pub const MAX_SPHERICAL_SAMPLE_AREA: Float = 6.22;
+
+// Shape
+// geometry in its own object space, placed in the world by a transform.
+// intersect returns the parametric distance along the ray to the closest hit
+// in (0, ray.t_max) together with the surface geometry at that point. Shapes
+// are shared between threads, so they must be Send and Sync
+pub trait Shape: Send + Sync {
+    fn object_bound(&self) -> Bounds3f;
+
+    fn world_bound(&self) -> Bounds3f;
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)>;
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float;
+
+    // picks a point on the surface, returns it with the pdf with respect to
+    // area
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float);
+
+    // picks a point on the surface as seen from ref_it, returns it with the
+    // pdf with respect to solid angle at ref_it
+    fn sample_ref(&self, ref_it: &Interaction, u: &Point2f) -> Option<(Interaction, Float)> {
+        sample_ref_by_area(self, ref_it, u)
+    }
+
+    // pdf with respect to solid angle of sample_ref picking direction wi at
+    // ref_it, zero if wi misses the shape
+    fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        pdf_ref_by_area(self, ref_it, wi)
+    }
+}
+
+// sample_ref for shapes that sample uniformly by area, converting the area
+// pdf to solid angle at ref_it
+pub fn sample_ref_by_area<S: Shape + ?Sized>(
+    shape: &S,
+    ref_it: &Interaction,
+    u: &Point2f,
+) -> Option<(Interaction, Float)> {
+    // sample shape by area and compute incident direction wi
+    let (mut it, pdf) = shape.sample_area(u);
+    it.time = ref_it.time;
+    let wi = it.p() - ref_it.p();
+    if wi.length_squared() == 0.0 {
+        return None;
+    }
+    // convert area sampling pdf to solid angle measure
+    let pdf =
+        pdf * ref_it.p().distance_squared(&it.p()) / Vector3f::from(it.n).abs_dot(&wi.normalize());
+    if pdf.is_infinite() || pdf == 0.0 {
+        return None;
+    }
+    Some((it, pdf))
+}
+
+// pdf_ref matching sample_ref_by_area for shapes whose area pdf is 1 / area
+pub fn pdf_ref_by_area<S: Shape + ?Sized>(shape: &S, ref_it: &Interaction, wi: &Vector3f) -> Float {
+    // intersect sample ray with shape geometry
+    let ray = ref_it.spawn_ray(wi);
+    let Some((_, isect)) = shape.intersect(&ray) else {
+        return 0.0;
+    };
+    // convert light sample weight to solid angle measure
+    let pdf = ref_it.p().distance_squared(&isect.p())
+        / (Vector3f::from(isect.n()).abs_dot(&wi.normalize()) * shape.area());
+    if pdf.is_infinite() {
+        0.0
+    } else {
+        pdf
+    }
+}
+
+// closest hit on a quadric in object space, before the surface geometry is
+// computed
+pub struct QuadricIntersection {
+    pub t_hit: Float,
+    pub p_obj: Point3f,
+    pub p_error: Vector3f,
+    pub phi: Float,
+}
+
+// Interaction for a point sampled on a quadric in object space, with the
+// normal oriented like the quadric's intersections
+pub fn quadric_sample(
+    object_to_world: &Transform,
+    p: &Point3f,
+    p_error: &Vector3f,
+    n: &Normal3f,
+    flip_normal: bool,
+) -> Interaction {
+    let n = if flip_normal { -*n } else { *n };
+    Interaction::new(
+        object_to_world.transform_point3fi(&Point3fi::new_with_error(p, p_error)),
+        object_to_world.transform_normal3f(&n).normalize(),
+        0.0,
+        Vector3f::default(),
+        MediumInterface::default(),
+    )
+}
+
+// normal derivatives from the first and second derivatives of the surface
+// using the Weingarten equations
+pub fn weingarten(
+    dpdu: &Vector3f,
+    dpdv: &Vector3f,
+    d2pduu: &Vector3f,
+    d2pduv: &Vector3f,
+    d2pdvv: &Vector3f,
+) -> (Normal3f, Normal3f) {
+    // compute coefficients for fundamental forms
+    let e1 = dpdu.dot(dpdu);
+    let f1 = dpdu.dot(dpdv);
+    let g1 = dpdv.dot(dpdv);
+    let n = dpdu.cross(dpdv).normalize();
+    let e2 = n.dot(d2pduu);
+    let f2 = n.dot(d2pduv);
+    let g2 = n.dot(d2pdvv);
+
+    let egf2 = e1 * g1 - f1 * f1;
+    let inv_egf2 = if egf2 == 0.0 { 0.0 } else { 1.0 / egf2 };
+    let dndu = Normal3f::from(
+        *dpdu * ((f2 * f1 - e2 * g1) * inv_egf2) + *dpdv * ((e2 * f1 - f2 * e1) * inv_egf2),
+    );
+    let dndv = Normal3f::from(
+        *dpdu * ((g2 * f1 - f2 * g1) * inv_egf2) + *dpdv * ((f2 * f1 - g2 * e1) * inv_egf2),
+    );
+    (dndu, dndv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weingarten_of_unit_sphere() {
+        // unit sphere at the point (1, 0, 0), parameterized so that dpdu x dpdv
+        // points outward
+        let dpdu = Vector3f::new(0.0, 1.0, 0.0);
+        let dpdv = Vector3f::new(0.0, 0.0, 1.0);
+        let d2pduu = Vector3f::new(-1.0, 0.0, 0.0);
+        let d2pdvv = Vector3f::new(-1.0, 0.0, 0.0);
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &Vector3f::default(), &d2pdvv);
+        // the normal of a unit sphere moves exactly like the point
+        assert!((Vector3f::from(dndu) - dpdu).length() < 1e-6);
+        assert!((Vector3f::from(dndv) - dpdv).length() < 1e-6);
+    }
+}
@@ -0,0 +1,16 @@
+use super::{interaction::SurfaceInteraction, pbrt::Float};
+
+// FloatTexture
+// a scalar that varies over a surface, evaluated at a ray hit
+pub trait FloatTexture: Send + Sync {
+    fn evaluate(&self, si: &SurfaceInteraction) -> Float;
+}
+
+impl<F> FloatTexture for F
+where
+    F: Fn(&SurfaceInteraction) -> Float + Send + Sync,
+{
+    fn evaluate(&self, si: &SurfaceInteraction) -> Float {
+        self(si)
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{bounds::Bounds3f, point::Point2f, ray::Ray, vector::Vector3f},
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{hash_float, Float},
+    shape::Shape,
+    texture::FloatTexture,
+};
+
+// AlphaMaskedShape
+// a shape with cutouts, hits where the alpha texture is below alpha_threshold
+// are ignored. Hits with a fractional alpha at or above the threshold are kept
+// with probability alpha, decided by hashing the ray so that closest hit and
+// shadow queries along the same ray agree
+pub struct AlphaMaskedShape {
+    pub shape: Arc<dyn Shape>,
+    pub alpha: Arc<dyn FloatTexture>,
+    pub alpha_threshold: Float,
+}
+
+impl AlphaMaskedShape {
+    pub fn new(
+        shape: Arc<dyn Shape>,
+        alpha: Arc<dyn FloatTexture>,
+        alpha_threshold: Float,
+    ) -> AlphaMaskedShape {
+        AlphaMaskedShape {
+            shape,
+            alpha,
+            alpha_threshold,
+        }
+    }
+
+    // whether the hit isect of ray r survives the alpha test
+    fn is_opaque(&self, r: &Ray, isect: &SurfaceInteraction) -> bool {
+        let a = self.alpha.evaluate(isect);
+        if a >= 1.0 {
+            return true;
+        }
+        if a < self.alpha_threshold || a <= 0.0 {
+            return false;
+        }
+        // resolve fractional alpha stochastically
+        let u = hash_float(&[r.o.x, r.o.y, r.o.z, r.d.x, r.d.y, r.d.z]);
+        u < a
+    }
+}
+
+impl Shape for AlphaMaskedShape {
+    fn object_bound(&self) -> Bounds3f {
+        self.shape.object_bound()
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.shape.world_bound()
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let mut r = ray.clone();
+        let mut t_offset = 0.0;
+        loop {
+            let (t_hit, isect) = self.shape.intersect(&r)?;
+            if self.is_opaque(&r, &isect) {
+                return Some((t_offset + t_hit, isect));
+            }
+            // ignore the cut away hit and continue the ray past it
+            let t_max = r.t_max.get() - t_hit;
+            t_offset += t_hit;
+            r = isect.interaction.spawn_ray(&r.d);
+            r.t_max.set(t_max);
+        }
+    }
+
+    // shadow rays need the alpha test too, so they cannot use the shape's
+    // cheaper intersect_p
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.shape.area()
+    }
+
+    // sampling ignores the alpha texture
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        self.shape.sample_area(u)
+    }
+
+    fn sample_ref(&self, ref_it: &Interaction, u: &Point2f) -> Option<(Interaction, Float)> {
+        self.shape.sample_ref(ref_it, u)
+    }
+
+    fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        self.shape.pdf_ref(ref_it, wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        core::geometry::{point::Point3f, transform::Transform},
+        shapes::sphere::Sphere,
+    };
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    // the unit sphere with alpha given by the height of the hit
+    fn masked(alpha: fn(Float) -> Float, alpha_threshold: Float) -> AlphaMaskedShape {
+        let sphere = Sphere::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            1.0,
+            -1.0,
+            1.0,
+            360.0,
+        );
+        AlphaMaskedShape::new(
+            Arc::new(sphere),
+            Arc::new(move |si: &SurfaceInteraction| alpha(si.p().z)),
+            alpha_threshold,
+        )
+    }
+
+    #[test]
+    fn cut_away_hits_are_skipped() {
+        // the lower half of the sphere is cut away
+        let shape = masked(|z| if z < 0.0 { 0.0 } else { 1.0 }, 0.5);
+        let up = ray(Point3f::new(0.0, 0.0, -5.0), Vector3f::new(0.0, 0.0, 1.0));
+        let (t_hit, si) = shape.intersect(&up).unwrap();
+        assert!((t_hit - 6.0).abs() < 1e-4);
+        assert!((si.p().z - 1.0).abs() < 1e-4);
+        let below = ray(Point3f::new(0.0, 0.0, -5.0), Vector3f::new(0.0, 0.0, 1.0));
+        below.t_max.set(5.5);
+        assert!(!shape.intersect_p(&below));
+        let side = ray(Point3f::new(-5.0, 0.0, -0.5), Vector3f::new(1.0, 0.0, 0.0));
+        assert!(!shape.intersect_p(&side));
+    }
+
+    #[test]
+    fn fractional_alpha_is_resolved_consistently() {
+        let below_threshold = masked(|_| 0.3, 0.5);
+        let r = ray(Point3f::new(0.1, 0.2, -5.0), Vector3f::new(0.0, 0.0, 1.0));
+        assert!(!below_threshold.intersect_p(&r));
+        let half = masked(|_| 0.5, 0.0);
+        let mut n_hits = 0;
+        for i in 0..64 {
+            let o = Point3f::new(0.01 * i as Float - 0.3, 0.1, -5.0);
+            let r = ray(o, Vector3f::new(0.0, 0.0, 1.0));
+            // closest hit and shadow queries along the same ray agree
+            assert_eq!(half.intersect(&r).is_some(), half.intersect_p(&r));
+            n_hits += half.intersect_p(&r) as usize;
+        }
+        assert!(n_hits > 0 && n_hits < 64);
+    }
+}
@@ -0,0 +1,574 @@
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{lerp, Point2f, Point3f, Point3fi},
+        ray::Ray,
+        spherical::spherical_quad_area,
+        transform::Transform,
+        vector::{coordinate_system, Vector3f},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    medium::MediumInterface,
+    pbrt::{gamma, quadratic, Float},
+    sampling::{
+        bilinear_pdf, invert_spherical_rectangle_sample, sample_bilinear,
+        sample_spherical_rectangle,
+    },
+    shape::{weingarten, Shape, MAX_SPHERICAL_SAMPLE_AREA, MIN_SPHERICAL_SAMPLE_AREA},
+};
+
+// BilinearPatch
+// the surface p(u, v) interpolating the corners p = [p00, p10, p01, p11]
+// bilinearly. Corners and normals are stored in world space, normals and uv
+// are optional and given in the same corner order
+pub struct BilinearPatch {
+    pub world_to_object: Transform,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub p: [Point3f; 4],
+    pub n: Option<[Normal3f; 4]>,
+    pub uv: Option<[Point2f; 4]>,
+    pub is_rectangle: bool,
+    pub area: Float,
+}
+
+impl BilinearPatch {
+    pub fn new(
+        object_to_world: &Transform,
+        reverse_orientation: bool,
+        p: [Point3f; 4],
+        n: Option<[Normal3f; 4]>,
+        uv: Option<[Point2f; 4]>,
+    ) -> BilinearPatch {
+        // transform patch corners to world space
+        let p = p.map(|p| object_to_world.transform_point3f(&p));
+        let n = n.map(|n| n.map(|n| object_to_world.transform_normal3f(&n)));
+        let [p00, p10, p01, p11] = p;
+        let is_rectangle = is_rectangle(&p);
+        let area = if is_rectangle {
+            p00.distance(&p01) * p00.distance(&p10)
+        } else {
+            // compute approximate area of bilinear patch
+            const NA: usize = 3;
+            let mut grid = [[Point3f::default(); NA + 1]; NA + 1];
+            for (i, row) in grid.iter_mut().enumerate() {
+                let u = i as Float / NA as Float;
+                for (j, q) in row.iter_mut().enumerate() {
+                    let v = j as Float / NA as Float;
+                    *q = lerp(u, &lerp(v, &p00, &p01), &lerp(v, &p10, &p11));
+                }
+            }
+            let mut area = 0.0;
+            for i in 0..NA {
+                for j in 0..NA {
+                    area += 0.5
+                        * (grid[i + 1][j + 1] - grid[i][j])
+                            .cross(&(grid[i + 1][j] - grid[i][j + 1]))
+                            .length();
+                }
+            }
+            area
+        };
+        BilinearPatch {
+            world_to_object: object_to_world.inverse(),
+            reverse_orientation,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+            p,
+            n,
+            uv,
+            is_rectangle,
+            area,
+        }
+    }
+
+    // the geometric normal at a sampled point, flipped to the side of the
+    // interpolated normals if there are any
+    fn oriented_normal(&self, n: Normal3f, uv: &Point2f) -> Normal3f {
+        match self.n {
+            Some([n00, n10, n01, n11]) => {
+                let ns = lerp_normal(
+                    uv.x,
+                    &lerp_normal(uv.y, &n00, &n01),
+                    &lerp_normal(uv.y, &n10, &n11),
+                );
+                n.face_forward(&ns)
+            }
+            None if self.reverse_orientation ^ self.transform_swaps_handedness => -n,
+            None => n,
+        }
+    }
+
+    // corner weights for approximately uniform area sampling of patches that
+    // are not rectangles, the differential area at each corner
+    fn area_weights(&self) -> Option<[Float; 4]> {
+        if self.is_rectangle {
+            return None;
+        }
+        let [p00, p10, p01, p11] = self.p;
+        Some([
+            (p10 - p00).cross(&(p01 - p00)).length(),
+            (p10 - p00).cross(&(p11 - p10)).length(),
+            (p01 - p00).cross(&(p11 - p01)).length(),
+            (p11 - p10).cross(&(p11 - p01)).length(),
+        ])
+    }
+
+    // position, dpdu and dpdv at patch coordinates uv
+    fn evaluate(&self, uv: &Point2f) -> (Point3f, Vector3f, Vector3f) {
+        let [p00, p10, p01, p11] = self.p;
+        let pu0 = lerp(uv.y, &p00, &p01);
+        let pu1 = lerp(uv.y, &p10, &p11);
+        let p = lerp(uv.x, &pu0, &pu1);
+        let dpdu = pu1 - pu0;
+        let dpdv = lerp(uv.x, &p01, &p11) - lerp(uv.x, &p00, &p10);
+        (p, dpdu, dpdv)
+    }
+
+    // pdf with respect to area of sample_area picking patch coordinates uv
+    fn area_pdf(&self, uv: &Point2f) -> Float {
+        let (_, dpdu, dpdv) = self.evaluate(uv);
+        let dpdu_x_dpdv = dpdu.cross(&dpdv).length();
+        if dpdu_x_dpdv == 0.0 {
+            return 0.0;
+        }
+        match self.area_weights() {
+            Some(w) => bilinear_pdf(uv, &w) / dpdu_x_dpdv,
+            None => 1.0 / dpdu_x_dpdv,
+        }
+    }
+
+    // the solid angle the patch subtends from p if it is sampled by solid
+    // angle from there, which needs a rectangle of moderate solid angle
+    fn sampling_solid_angle(&self, p: &Point3f) -> Option<Float> {
+        if !self.is_rectangle {
+            return None;
+        }
+        let [v00, v10, v01, v11] = self.p.map(|q| (q - *p).normalize());
+        let solid_angle = spherical_quad_area(&v00, &v10, &v11, &v01);
+        if (MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA).contains(&solid_angle) {
+            Some(solid_angle)
+        } else {
+            None
+        }
+    }
+
+    // corner weights that warp spherical rectangle samples towards the
+    // incident cos theta factor at p with normal n
+    fn cos_weights(&self, p: &Point3f, n: &Normal3f) -> [Float; 4] {
+        let n = Vector3f::from(*n);
+        self.p.map(|q| (q - *p).normalize().abs_dot(&n).max(0.01))
+    }
+
+    // picks a point on the patch, uniformly over its area for rectangles and
+    // approximately so otherwise, returns it with the pdf with respect to area
+    pub fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        // sample bilinear patch parametric (u,v) coordinates
+        let uv = match self.area_weights() {
+            Some(w) => sample_bilinear(u, &w),
+            None => *u,
+        };
+
+        // compute bilinear patch geometric quantities at sampled (u,v)
+        let pdf = self.area_pdf(&uv);
+        if pdf == 0.0 {
+            return (Interaction::default(), 0.0);
+        }
+        let (p, dpdu, dpdv) = self.evaluate(&uv);
+        let n = self.oriented_normal(Normal3f::from(dpdu.cross(&dpdv).normalize()), &uv);
+        let it = Interaction::new(
+            Point3fi::new_with_error(&p, &self.p_error()),
+            n,
+            0.0,
+            Vector3f::default(),
+            MediumInterface::default(),
+        );
+        (it, pdf)
+    }
+
+    // picks a point on the patch as seen from ref_it, uniformly over the
+    // solid angle of rectangles (warped by the cos theta factor at ref_it if
+    // it has a normal) and by area otherwise, returns it with the pdf with
+    // respect to solid angle at ref_it
+    pub fn sample_ref(&self, ref_it: &Interaction, u: &Point2f) -> Option<(Interaction, Float)> {
+        let ref_p = ref_it.p();
+        if self.sampling_solid_angle(&ref_p).is_none() {
+            // sample shape by area and compute incident direction wi
+            let (mut it, pdf) = self.sample_area(u);
+            it.time = ref_it.time;
+            let wi = it.p() - ref_p;
+            if wi.length_squared() == 0.0 {
+                return None;
+            }
+            // convert area sampling pdf to solid angle measure
+            let pdf = pdf * ref_p.distance_squared(&it.p())
+                / Vector3f::from(it.n).abs_dot(&wi.normalize());
+            if pdf.is_infinite() || pdf == 0.0 {
+                return None;
+            }
+            return Some((it, pdf));
+        }
+
+        // sample direction to rectangular bilinear patch
+        let mut u = *u;
+        let mut pdf = 1.0;
+        // warp uniform sample u to account for incident cos theta factor
+        if ref_it.is_surface_interaction() {
+            let w = self.cos_weights(&ref_p, &ref_it.n);
+            u = sample_bilinear(&u, &w);
+            pdf = bilinear_pdf(&u, &w);
+        }
+
+        // sample spherical rectangle at reference point
+        let [p00, p10, p01, _] = self.p;
+        let eu = p10 - p00;
+        let ev = p01 - p00;
+        let (p, quad_pdf) = sample_spherical_rectangle(&ref_p, &p00, &eu, &ev, &u);
+        pdf *= quad_pdf;
+        if pdf == 0.0 {
+            return None;
+        }
+
+        // compute (u,v) and surface normal for sampled point on rectangle
+        let uv = Point2f::new(
+            (p - p00).dot(&eu) / p10.distance_squared(&p00),
+            (p - p00).dot(&ev) / p01.distance_squared(&p00),
+        );
+        let n = self.oriented_normal(Normal3f::from(eu.cross(&ev).normalize()), &uv);
+        let it = Interaction::new(
+            Point3fi::from(p),
+            n,
+            ref_it.time,
+            Vector3f::default(),
+            MediumInterface::default(),
+        );
+        Some((it, pdf))
+    }
+
+    // pdf with respect to solid angle at ref_it of sample_ref picking the
+    // point of the patch in direction wi, zero if wi misses the patch
+    pub fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        // intersect sample ray with the patch
+        let ray = ref_it.spawn_ray(wi);
+        let Some((uv, _)) = intersect_bilinear_patch(&ray, ray.t_max.get(), &self.p) else {
+            return 0.0;
+        };
+        let ref_p = ref_it.p();
+        let (p, dpdu, dpdv) = self.evaluate(&uv);
+        let Some(solid_angle) = self.sampling_solid_angle(&ref_p) else {
+            // convert area sampling pdf to solid angle measure
+            let n = dpdu.cross(&dpdv);
+            if n.length_squared() == 0.0 {
+                return 0.0;
+            }
+            let pdf = self.area_pdf(&uv) * ref_p.distance_squared(&p)
+                / n.normalize().abs_dot(&wi.normalize());
+            return if pdf.is_infinite() { 0.0 } else { pdf };
+        };
+
+        // account for the cos theta warp of sample_ref
+        let mut pdf = 1.0 / solid_angle;
+        if ref_it.is_surface_interaction() {
+            let [p00, p10, p01, _] = self.p;
+            let u = invert_spherical_rectangle_sample(&ref_p, &p00, &(p10 - p00), &(p01 - p00), &p);
+            pdf *= bilinear_pdf(&u, &self.cos_weights(&ref_p, &ref_it.n));
+        }
+        pdf
+    }
+
+    fn p_error(&self) -> Vector3f {
+        let [p00, p10, p01, p11] = self.p;
+        Vector3f::from(p00.abs() + p01.abs() + p10.abs() + p11.abs()) * gamma(6)
+    }
+}
+
+impl Shape for BilinearPatch {
+    fn object_bound(&self) -> Bounds3f {
+        let [p00, p10, p01, p11] = self.p.map(|p| self.world_to_object.transform_point3f(&p));
+        Bounds3f::new_with_points(p00, p01)
+            .union_point3f(&p10)
+            .union_point3f(&p11)
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        let [p00, p10, p01, p11] = self.p;
+        Bounds3f::new_with_points(p00, p01)
+            .union_point3f(&p10)
+            .union_point3f(&p11)
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let [p00, p10, p01, p11] = self.p;
+        let (uv, t) = intersect_bilinear_patch(ray, ray.t_max.get(), &self.p)?;
+
+        // compute bilinear patch point p, dpdu and dpdv for (u,v)
+        let p = lerp(uv.x, &lerp(uv.y, &p00, &p01), &lerp(uv.y, &p10, &p11));
+        let mut dpdu = lerp(uv.y, &p10, &p11) - lerp(uv.y, &p00, &p01);
+        let mut dpdv = lerp(uv.x, &p01, &p11) - lerp(uv.x, &p00, &p10);
+
+        // compute (s,t) texture coordinates at bilinear patch (u,v)
+        let mut st = uv;
+        let (mut duds, mut dudt, mut dvds, mut dvdt) = (1.0, 0.0, 0.0, 1.0);
+        if let Some([uv00, uv10, uv01, uv11]) = self.uv {
+            st = lerp_point2f(
+                uv.x,
+                &lerp_point2f(uv.y, &uv00, &uv01),
+                &lerp_point2f(uv.y, &uv10, &uv11),
+            );
+            // compute partial derivatives of (u,v) with respect to (s,t)
+            let dstdu = lerp_point2f(uv.y, &uv10, &uv11) - lerp_point2f(uv.y, &uv00, &uv01);
+            let dstdv = lerp_point2f(uv.x, &uv01, &uv11) - lerp_point2f(uv.x, &uv00, &uv10);
+            let inverse = |x: Float| if x.abs() < 1e-8 { 0.0 } else { 1.0 / x };
+            duds = inverse(dstdu.x);
+            dvds = inverse(dstdv.x);
+            dudt = inverse(dstdu.y);
+            dvdt = inverse(dstdv.y);
+
+            // compute partial derivatives of p with respect to (s,t)
+            let dpds = dpdu * duds + dpdv * dvds;
+            let mut dpdt = dpdu * dudt + dpdv * dvdt;
+            if dpds.cross(&dpdt) != Vector3f::default() {
+                if dpdu.cross(&dpdv).dot(&dpds.cross(&dpdt)) < 0.0 {
+                    dpdt = -dpdt;
+                }
+                dpdu = dpds;
+                dpdv = dpdt;
+            }
+        }
+
+        // find partial derivatives dndu and dndv for bilinear patch
+        let d2pduu = Vector3f::default();
+        let d2pdvv = Vector3f::default();
+        let d2pduv = (p00 - p01) + (p11 - p10);
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &d2pduv, &d2pdvv);
+        // update dndu and dndv to account for (s,t) parameterization
+        let dnds = dndu * duds + dndv * dvds;
+        let dndt = dndu * dudt + dndv * dvdt;
+
+        // initialize SurfaceInteraction for bilinear patch intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let mut isect = SurfaceInteraction::new(
+            Point3fi::new_with_error(&p, &self.p_error()),
+            st,
+            -ray.d,
+            dpdu,
+            dpdv,
+            dnds,
+            dndt,
+            ray.time,
+            flip_normal,
+        );
+
+        // compute bilinear patch shading normal if necessary
+        if let Some([n00, n10, n01, n11]) = self.n {
+            let ns = lerp_normal(
+                uv.x,
+                &lerp_normal(uv.y, &n00, &n01),
+                &lerp_normal(uv.y, &n10, &n11),
+            );
+            if ns.length_squared() > 0.0 {
+                let ns = Vector3f::from(ns.normalize());
+                // shading normal derivatives with respect to (s,t)
+                let dndu = lerp_normal(uv.y, &n10, &n11) - lerp_normal(uv.y, &n00, &n01);
+                let dndv = lerp_normal(uv.x, &n01, &n11) - lerp_normal(uv.x, &n00, &n10);
+                let dnds = dndu * duds + dndv * dvds;
+                let dndt = dndu * dudt + dndv * dvdt;
+
+                // shading tangents perpendicular to ns, ss along dpdu
+                let mut ts = ns.cross(&dpdu);
+                let ss;
+                if ts.length_squared() > 0.0 {
+                    ts = ts.normalize();
+                    ss = ts.cross(&ns);
+                } else {
+                    (ss, ts) = coordinate_system(&ns);
+                }
+                isect.set_shading_geometry(&ss, &ts, &dnds, &dndt, true);
+            }
+        }
+        Some((t, isect))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        intersect_bilinear_patch(ray, ray.t_max.get(), &self.p).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.area
+    }
+
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        BilinearPatch::sample_area(self, u)
+    }
+
+    fn sample_ref(&self, ref_it: &Interaction, u: &Point2f) -> Option<(Interaction, Float)> {
+        BilinearPatch::sample_ref(self, ref_it, u)
+    }
+
+    fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        BilinearPatch::pdf_ref(self, ref_it, wi)
+    }
+}
+
+// a planar patch whose corners all lie at the same distance from its center
+fn is_rectangle(p: &[Point3f; 4]) -> bool {
+    let [p00, p10, p01, p11] = *p;
+    if p00 == p01 || p01 == p11 || p11 == p10 || p10 == p00 {
+        return false;
+    }
+    // check if bilinear patch vertices are coplanar
+    let n = (p10 - p00).cross(&(p01 - p00)).normalize();
+    if (p11 - p00).normalize().abs_dot(&n) > 1e-5 {
+        return false;
+    }
+    // check if planar vertices form a rectangle
+    let p_center = (p00 + p01 + p10 + p11) * 0.25;
+    let d2 = [
+        p00.distance_squared(&p_center),
+        p01.distance_squared(&p_center),
+        p10.distance_squared(&p_center),
+        p11.distance_squared(&p_center),
+    ];
+    d2[1..].iter().all(|d| (d - d2[0]).abs() / d2[0] <= 1e-4)
+}
+
+// the closest hit of ray with the patch before t_max as its (u,v) and t,
+// solving for u exactly from the distance of the ray to the u iso-lines
+// (Reshetov 2019)
+fn intersect_bilinear_patch(ray: &Ray, t_max: Float, p: &[Point3f; 4]) -> Option<(Point2f, Float)> {
+    let [p00, p10, p01, p11] = *p;
+    // find quadratic coefficients for distance from ray to u iso-lines
+    let a = (p10 - p00).cross(&(p01 - p11)).dot(&ray.d);
+    let c = (p00 - ray.o).cross(&ray.d).dot(&(p01 - p00));
+    let b = (p10 - ray.o).cross(&ray.d).dot(&(p11 - p10)) - (a + c);
+
+    // solve quadratic for bilinear patch u intersection
+    let (u1, u2) = quadratic(a, b, c)?;
+
+    // find epsilon eps to ensure that candidate t is greater than zero
+    let eps = gamma(10)
+        * (Vector3f::from(ray.o).abs().max_component()
+            + ray.d.abs().max_component()
+            + Vector3f::from(p00).abs().max_component()
+            + Vector3f::from(p10).abs().max_component()
+            + Vector3f::from(p01).abs().max_component()
+            + Vector3f::from(p11).abs().max_component());
+
+    // v and t of the hit on the u iso-line, scaled by the returned divisor
+    let candidate = |u: Float| {
+        let uo = lerp(u, &p00, &p10);
+        let ud = lerp(u, &p01, &p11) - uo;
+        let deltao = uo - ray.o;
+        let perp = ray.d.cross(&ud);
+        let p2 = perp.length_squared();
+        // compute matrix determinants for v and t numerators
+        let v = deltao.dot(&ray.d.cross(&perp));
+        let t = deltao.dot(&ud.cross(&perp));
+        (v, t, p2)
+    };
+
+    // compute v and t for the first u intersection
+    let mut hit = None;
+    let mut t = t_max;
+    if (0.0..=1.0).contains(&u1) {
+        let (v1, t1, p2) = candidate(u1);
+        if t1 > p2 * eps && 0.0 <= v1 && v1 <= p2 {
+            hit = Some(Point2f::new(u1, v1 / p2));
+            t = t1 / p2;
+        }
+    }
+
+    // compute v and t for the second u intersection
+    if (0.0..=1.0).contains(&u2) && u2 != u1 {
+        let (v2, t2, p2) = candidate(u2);
+        if 0.0 <= v2 && v2 <= p2 && t > t2 / p2 && t2 > p2 * eps {
+            hit = Some(Point2f::new(u2, v2 / p2));
+            t = t2 / p2;
+        }
+    }
+
+    // check intersection t against t_max and possibly return intersection
+    if t >= t_max {
+        return None;
+    }
+    hit.map(|uv| (uv, t))
+}
+
+fn lerp_normal(t: Float, n0: &Normal3f, n1: &Normal3f) -> Normal3f {
+    *n0 * (1.0 - t) + *n1 * t
+}
+
+fn lerp_point2f(t: Float, p0: &Point2f, p1: &Point2f) -> Point2f {
+    *p0 * (1.0 - t) + *p1 * t
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    fn patch(p11: Point3f) -> BilinearPatch {
+        let p = [
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+            p11,
+        ];
+        BilinearPatch::new(&Transform::default(), false, p, None, None)
+    }
+
+    #[test]
+    fn intersects_rectangles_and_saddles() {
+        let rect = patch(Point3f::new(2.0, 1.0, 0.0));
+        assert!(rect.is_rectangle);
+        assert!((rect.area() - 2.0).abs() < 1e-6);
+        let r = ray(Point3f::new(0.5, 0.25, 3.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t, si) = rect.intersect(&r).unwrap();
+        assert!((t - 3.0).abs() < 1e-6);
+        assert!((si.uv - Point2f::new(0.25, 0.25)).length() < 1e-6);
+
+        // raising p11 gives a saddle with z = u v at (u, v)
+        let saddle = patch(Point3f::new(2.0, 1.0, 1.0));
+        assert!(!saddle.is_rectangle);
+        let r = ray(Point3f::new(1.0, 0.5, 3.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t, si) = saddle.intersect(&r).unwrap();
+        assert!((t - 2.75).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(1.0, 0.5, 0.25)).length() < 1e-6);
+        let r = ray(Point3f::new(2.5, 0.5, 3.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!(!saddle.intersect_p(&r));
+    }
+
+    #[test]
+    fn solid_angle_samples_match_pdf_ref() {
+        let rect = patch(Point3f::new(2.0, 1.0, 0.0));
+        let ref_it = Interaction::new(
+            Point3fi::from(Point3f::new(0.5, 0.3, 1.0)),
+            Normal3f::new(0.3, 0.0, -1.0).normalize(),
+            0.0,
+            Vector3f::default(),
+            MediumInterface::default(),
+        );
+        for u in [Point2f::new(0.2, 0.7), Point2f::new(0.8, 0.1)] {
+            let (it, pdf) = rect.sample_ref(&ref_it, &u).unwrap();
+            assert!(it.p().z.abs() < 1e-6);
+            let wi = (it.p() - ref_it.p()).normalize();
+            let pdf_ref = rect.pdf_ref(&ref_it, &wi);
+            assert!((pdf - pdf_ref).abs() < 1e-3 * pdf, "{} vs {}", pdf, pdf_ref);
+        }
+        // area sampled saddle, converted to solid angle
+        let saddle = patch(Point3f::new(2.0, 1.0, 1.0));
+        let (it, pdf) = saddle.sample_ref(&ref_it, &Point2f::new(0.4, 0.6)).unwrap();
+        let wi = (it.p() - ref_it.p()).normalize();
+        assert!((pdf - saddle.pdf_ref(&ref_it, &wi)).abs() < 1e-3 * pdf);
+    }
+}
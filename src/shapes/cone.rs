@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{Vector3f, Vector3fi},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{clamp, gamma, quadratic_interval, radians, Float, PI},
+    sampling::sample_linear,
+    shape::{quadric_sample, weingarten, QuadricIntersection, Shape},
+};
+
+// Cone
+// with its base of radius in the z = 0 plane of object space and its apex
+// at z = height, optionally cut off beyond phi_max
+pub struct Cone {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub radius: Float,
+    pub height: Float,
+    pub phi_max: Float,
+}
+
+impl Cone {
+    // phi_max is in degrees
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        height: Float,
+        radius: Float,
+        phi_max: Float,
+    ) -> Cone {
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Cone {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            radius,
+            height,
+            phi_max: radians(clamp(phi_max, 0.0, 360.0)),
+        }
+    }
+
+    // phi of the hit point, or None if it lies in a part of the cone that
+    // was cut away
+    fn unclipped_phi(&self, p_hit: &Point3f) -> Option<Float> {
+        let mut phi = p_hit.y.atan2(p_hit.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        if p_hit.z < 0.0 || p_hit.z > self.height || phi > self.phi_max {
+            None
+        } else {
+            Some(phi)
+        }
+    }
+
+    fn basic_intersect(&self, r: &Ray) -> Option<QuadricIntersection> {
+        let t_max = r.t_max.get();
+        // transform ray origin and direction to object space
+        let oi = self
+            .world_to_object
+            .transform_point3fi(&Point3fi::from(r.o));
+        let di = self
+            .world_to_object
+            .transform_vector3fi(&Vector3fi::from(r.d));
+
+        // compute quadratic cone coefficients
+        let k = (self.radius / self.height) * (self.radius / self.height);
+        let oz = oi.z - self.height;
+        let a = di.x.sqr() + di.y.sqr() - k * di.z.sqr();
+        let b = 2.0 * (di.x * oi.x + di.y * oi.y - k * di.z * oz);
+        let c = oi.x.sqr() + oi.y.sqr() - k * oz.sqr();
+
+        // solve quadratic equation for t values
+        let (t0, t1) = quadratic_interval(a, b, c)?;
+
+        // check quadric shape t0 and t1 for nearest intersection
+        if t0.upper_bound() > t_max || t1.lower_bound() <= 0.0 {
+            return None;
+        }
+        let mut t_shape_hit = t0;
+        if t_shape_hit.lower_bound() <= 0.0 {
+            t_shape_hit = t1;
+            if t_shape_hit.upper_bound() > t_max {
+                return None;
+            }
+        }
+        let mut pi = oi + di * t_shape_hit;
+        let mut phi = self.unclipped_phi(&pi.midpoint());
+
+        // test cone intersection against clipping parameters
+        if phi.is_none() {
+            if t_shape_hit == t1 || t1.upper_bound() > t_max {
+                return None;
+            }
+            t_shape_hit = t1;
+            pi = oi + di * t_shape_hit;
+            phi = self.unclipped_phi(&pi.midpoint());
+        }
+        Some(QuadricIntersection {
+            t_hit: Float::from(t_shape_hit),
+            p_obj: pi.midpoint(),
+            p_error: pi.error(),
+            phi: phi?,
+        })
+    }
+
+    fn interaction_from_intersection(
+        &self,
+        isect: &QuadricIntersection,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction {
+        let p_hit = isect.p_obj;
+        // find parametric representation of cone hit
+        let u = isect.phi / self.phi_max;
+        let v = p_hit.z / self.height;
+
+        // compute cone dpdu and dpdv
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let dpdv = Vector3f::new(-p_hit.x / (1.0 - v), -p_hit.y / (1.0 - v), self.height);
+
+        // compute cone dndu and dndv
+        let d2pduu = Vector3f::new(p_hit.x, p_hit.y, 0.0) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::new(p_hit.y, -p_hit.x, 0.0) * (self.phi_max / (1.0 - v));
+        let d2pdvv = Vector3f::default();
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &d2pduv, &d2pdvv);
+
+        // return SurfaceInteraction for quadric intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let wo_object = self.world_to_object.transform_vector3f(wo);
+        self.object_to_world
+            .transform_surface_interaction(&SurfaceInteraction::new(
+                Point3fi::new_with_error(&p_hit, &isect.p_error),
+                Point2f::new(u, v),
+                wo_object,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+                time,
+                flip_normal,
+            ))
+    }
+}
+
+impl Shape for Cone {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(-self.radius, -self.radius, 0.0),
+            Point3f::new(self.radius, self.radius, self.height),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let isect = self.basic_intersect(ray)?;
+        let si = self.interaction_from_intersection(&isect, &-ray.d, ray.time);
+        Some((isect.t_hit, si))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.basic_intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.radius * (self.height * self.height + self.radius * self.radius).sqrt() * self.phi_max
+            / 2.0
+    }
+
+    // z is distributed like the radius at z, which is uniform over the area
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let z = self.height * sample_linear(u.x, 1.0, 0.0);
+        let r = self.radius * (1.0 - z / self.height);
+        let phi = u.y * self.phi_max;
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let p_obj = Point3f::new(r * cos_phi, r * sin_phi, z);
+        let p_error = Vector3f::from(p_obj.abs()) * gamma(5);
+        let n =
+            Normal3f::new(self.height * cos_phi, self.height * sin_phi, self.radius).normalize();
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+        (it, 1.0 / self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hits_the_side_at_the_right_radius() {
+        let cone = Cone::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            2.0,
+            1.0,
+            360.0,
+        );
+        // the cone has radius 0.5 at half its height
+        let r = ray(Point3f::new(-5.0, 0.0, 1.0), Vector3f::new(1.0, 0.0, 0.0));
+        let (t_hit, si) = cone.intersect(&r).unwrap();
+        assert!((t_hit - 4.5).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(-0.5, 0.0, 1.0)).length() < 1e-6);
+        // above the apex
+        let r = ray(Point3f::new(-5.0, 0.0, 2.5), Vector3f::new(1.0, 0.0, 0.0));
+        assert!(!cone.intersect_p(&r));
+        assert!((cone.area() - PI * (5.0 as Float).sqrt()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,559 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{lerp, Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{coordinate_system, Vector3f},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{clamp, lerp as lerp_float, Float},
+    shape::{quadric_sample, Shape},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CurveType {
+    // a ribbon that always faces the ray
+    Flat,
+    // a flat ribbon shaded as if it were a tube
+    Cylinder,
+    // a ribbon oriented by the curve normals
+    Ribbon,
+}
+
+// CurveCommon
+// the cubic Bezier control points, widths and (for ribbons) end normals in
+// object space, shared by all segments of a curve
+pub struct CurveCommon {
+    pub curve_type: CurveType,
+    pub cp_obj: [Point3f; 4],
+    pub width: [Float; 2],
+    pub n: [Normal3f; 2],
+    pub normal_angle: Float,
+    pub inv_sin_normal_angle: Float,
+}
+
+impl CurveCommon {
+    pub fn new(
+        c: &[Point3f; 4],
+        width0: Float,
+        width1: Float,
+        curve_type: CurveType,
+        norm: Option<&[Normal3f; 2]>,
+    ) -> Result<CurveCommon, String> {
+        if curve_type == CurveType::Ribbon && norm.is_none() {
+            return Err(String::from(
+                "CurveCommon ribbon curves need normals at both ends",
+            ));
+        }
+        let mut common = CurveCommon {
+            curve_type,
+            cp_obj: *c,
+            width: [width0, width1],
+            n: [Normal3f::default(); 2],
+            normal_angle: 0.0,
+            inv_sin_normal_angle: 0.0,
+        };
+        if let Some(norm) = norm {
+            common.n = [norm[0].normalize(), norm[1].normalize()];
+            common.normal_angle = clamp(common.n[0].dot(&common.n[1]), 0.0, 1.0).acos();
+            common.inv_sin_normal_angle = 1.0 / common.normal_angle.sin();
+        }
+        Ok(common)
+    }
+}
+
+// Curve
+// the part of a curve between u_min and u_max
+pub struct Curve {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub common: Arc<CurveCommon>,
+    pub u_min: Float,
+    pub u_max: Float,
+}
+
+// parameters of a ray curve hit found by the subdivision
+struct CurveHit {
+    t: Float,
+    u: Float,
+    v: Float,
+    hit_width: Float,
+    n_hit: Normal3f,
+}
+
+impl Curve {
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        common: Arc<CurveCommon>,
+        u_min: Float,
+        u_max: Float,
+    ) -> Curve {
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Curve {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            common,
+            u_min,
+            u_max,
+        }
+    }
+
+    // object space control points of this segment
+    fn segment_control_points(&self) -> [Point3f; 4] {
+        let cp = &self.common.cp_obj;
+        [
+            blossom_bezier(cp, self.u_min, self.u_min, self.u_min),
+            blossom_bezier(cp, self.u_min, self.u_min, self.u_max),
+            blossom_bezier(cp, self.u_min, self.u_max, self.u_max),
+            blossom_bezier(cp, self.u_max, self.u_max, self.u_max),
+        ]
+    }
+
+    fn width_at(&self, u: Float) -> Float {
+        lerp_float(u, self.common.width[0], self.common.width[1])
+    }
+
+    // the ribbon normal at u, spherically interpolated between the end normals
+    fn ribbon_normal(&self, u: Float) -> Normal3f {
+        let common = &self.common;
+        if common.normal_angle == 0.0 {
+            common.n[0]
+        } else {
+            let sin0 = ((1.0 - u) * common.normal_angle).sin() * common.inv_sin_normal_angle;
+            let sin1 = (u * common.normal_angle).sin() * common.inv_sin_normal_angle;
+            common.n[0] * sin0 + common.n[1] * sin1
+        }
+    }
+
+    // projects the segment into a coordinate system with the ray along +z
+    // and returns the closest hit before t_max, or any hit if any_hit
+    fn find_hit(&self, ray: &Ray, any_hit: bool) -> Option<(CurveHit, Transform)> {
+        let cp_obj = self.segment_control_points();
+
+        // project curve control points to plane perpendicular to ray, with
+        // the up direction along the curve so that it lies roughly along x
+        let mut dx = ray.d.cross(&(cp_obj[3] - cp_obj[0]));
+        if dx.length_squared() == 0.0 {
+            // the ray is parallel to the line between the end points, any
+            // orientation of the ray coordinate system will do
+            (dx, _) = coordinate_system(&ray.d.normalize());
+        }
+        // a degenerate ray direction has no ray coordinate system and hits
+        // nothing
+        let object_to_ray = Transform::look_at(&ray.o, &(ray.o + ray.d), &dx)?;
+        let cp = cp_obj.map(|p| object_to_ray.transform_point3f(&p));
+
+        // see if the ray's bounding box overlaps the curve's before going
+        // any further
+        let max_width = self.width_at(self.u_min).max(self.width_at(self.u_max));
+        let ray_length = ray.d.length();
+        let z_max = ray_length * ray.t_max.get();
+        if !overlaps_ray(&cp, max_width, z_max) {
+            return None;
+        }
+
+        // compute refinement depth for curve, max_depth
+        let mut l0: Float = 0.0;
+        for i in 0..2 {
+            l0 = l0.max(
+                (cp[i].x - 2.0 * cp[i + 1].x + cp[i + 2].x)
+                    .abs()
+                    .max((cp[i].y - 2.0 * cp[i + 1].y + cp[i + 2].y).abs())
+                    .max((cp[i].z - 2.0 * cp[i + 1].z + cp[i + 2].z).abs()),
+            );
+        }
+        // width / 20
+        let eps = self.common.width[0].max(self.common.width[1]) * 0.05;
+        // compute log base 4 by dividing log2 in half
+        let r0 = log2_int(std::f64::consts::SQRT_2 as Float * 6.0 * l0 / (8.0 * eps)) / 2;
+        let max_depth = r0.clamp(0, 10);
+
+        let hit =
+            self.recursive_intersect(ray, z_max, any_hit, &cp, self.u_min, self.u_max, max_depth)?;
+        Some((hit, object_to_ray))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn recursive_intersect(
+        &self,
+        ray: &Ray,
+        mut z_max: Float,
+        any_hit: bool,
+        cp: &[Point3f; 4],
+        u0: Float,
+        u1: Float,
+        depth: i32,
+    ) -> Option<CurveHit> {
+        let ray_length = ray.d.length();
+        if depth > 0 {
+            // split curve segment into sub-segments and test for intersection
+            let cp_split = subdivide_bezier(cp);
+            let u = [u0, (u0 + u1) / 2.0, u1];
+            let mut closest = None;
+            for seg in 0..2 {
+                let cps = [
+                    cp_split[3 * seg],
+                    cp_split[3 * seg + 1],
+                    cp_split[3 * seg + 2],
+                    cp_split[3 * seg + 3],
+                ];
+                let max_width = self.width_at(u[seg]).max(self.width_at(u[seg + 1]));
+                if !overlaps_ray(&cps, max_width, z_max) {
+                    continue;
+                }
+                if let Some(hit) = self.recursive_intersect(
+                    ray,
+                    z_max,
+                    any_hit,
+                    &cps,
+                    u[seg],
+                    u[seg + 1],
+                    depth - 1,
+                ) {
+                    // a shadow ray can stop at the first hit, otherwise only
+                    // closer hits are of interest in the second half
+                    if any_hit {
+                        return Some(hit);
+                    }
+                    z_max = hit.t * ray_length;
+                    closest = Some(hit);
+                }
+            }
+            return closest;
+        }
+
+        // intersect ray with curve segment
+        // test sample point against tangent perpendicular at curve start
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return None;
+        }
+        // test sample point against tangent perpendicular at curve end
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return None;
+        }
+
+        // compute line w that gives minimum distance to sample point
+        let segment_x = cp[3].x - cp[0].x;
+        let segment_y = cp[3].y - cp[0].y;
+        let denom = segment_x * segment_x + segment_y * segment_y;
+        if denom == 0.0 {
+            return None;
+        }
+        let w = -(cp[0].x * segment_x + cp[0].y * segment_y) / denom;
+
+        // compute u coordinate of curve intersection point and hit_width
+        let u = clamp(lerp_float(w, u0, u1), u0, u1);
+        let mut hit_width = self.width_at(u);
+        let mut n_hit = Normal3f::default();
+        if self.common.curve_type == CurveType::Ribbon {
+            // scale hit_width based on ribbon orientation
+            n_hit = self.ribbon_normal(u);
+            hit_width *= Vector3f::from(n_hit).abs_dot(&ray.d) / ray_length;
+        }
+
+        // test intersection point against curve width
+        let (pc, dpcdw) = eval_bezier(cp, clamp(w, 0.0, 1.0));
+        let pt_curve_dist2 = pc.x * pc.x + pc.y * pc.y;
+        if pt_curve_dist2 > hit_width * hit_width * 0.25 {
+            return None;
+        }
+        if pc.z < 0.0 || pc.z > z_max {
+            return None;
+        }
+
+        // compute v coordinate of curve intersection point
+        let pt_curve_dist = pt_curve_dist2.sqrt();
+        let edge_func = dpcdw.x * -pc.y + pc.x * dpcdw.y;
+        let v = if edge_func > 0.0 {
+            0.5 + pt_curve_dist / hit_width
+        } else {
+            0.5 - pt_curve_dist / hit_width
+        };
+        Some(CurveHit {
+            t: pc.z / ray_length,
+            u,
+            v,
+            hit_width,
+            n_hit,
+        })
+    }
+}
+
+impl Shape for Curve {
+    fn object_bound(&self) -> Bounds3f {
+        let cp_obj = self.segment_control_points();
+        let b = Bounds3f::new_with_points(cp_obj[0], cp_obj[1])
+            .union(&Bounds3f::new_with_points(cp_obj[2], cp_obj[3]));
+        b.expand(self.width_at(self.u_min).max(self.width_at(self.u_max)) * 0.5)
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, r: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        // transform ray to object space
+        let ray = self.world_to_object.transform_ray(r);
+        let (hit, object_to_ray) = self.find_hit(&ray, false)?;
+
+        // compute dpdu and dpdv for curve intersection
+        let (_, dpdu) = eval_bezier(&self.common.cp_obj, hit.u);
+        let dpdv = if self.common.curve_type == CurveType::Ribbon {
+            Vector3f::from(hit.n_hit).cross(&dpdu).normalize() * hit.hit_width
+        } else {
+            // compute curve dpdv for flat and cylinder curves
+            let dpdu_plane = object_to_ray.transform_vector3f(&dpdu);
+            let mut dpdv_plane =
+                Vector3f::new(-dpdu_plane.y, dpdu_plane.x, 0.0).normalize() * hit.hit_width;
+            if self.common.curve_type == CurveType::Cylinder {
+                // rotate dpdv_plane to give cylindrical appearance
+                let theta = lerp_float(hit.v, -90.0, 90.0);
+                dpdv_plane = Transform::rotate(-theta, &dpdu_plane).transform_vector3f(&dpdv_plane);
+            }
+            object_to_ray.inverse().transform_vector3f(&dpdv_plane)
+        };
+
+        // compute error bounds for curve intersection
+        let p_error = Vector3f::new(
+            2.0 * hit.hit_width,
+            2.0 * hit.hit_width,
+            2.0 * hit.hit_width,
+        );
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let si = SurfaceInteraction::new(
+            Point3fi::new_with_error(&ray.position(hit.t), &p_error),
+            Point2f::new(hit.u, hit.v),
+            -ray.d,
+            dpdu,
+            dpdv,
+            Normal3f::default(),
+            Normal3f::default(),
+            ray.time,
+            flip_normal,
+        );
+        Some((
+            hit.t,
+            self.object_to_world.transform_surface_interaction(&si),
+        ))
+    }
+
+    fn intersect_p(&self, r: &Ray) -> bool {
+        let ray = self.world_to_object.transform_ray(r);
+        self.find_hit(&ray, true).is_some()
+    }
+
+    // approximates the length by the control polygon
+    fn area(&self) -> Float {
+        let cp_obj = self.segment_control_points();
+        let avg_width = (self.width_at(self.u_min) + self.width_at(self.u_max)) * 0.5;
+        let approx_length: Float = (0..3).map(|i| cp_obj[i].distance(&cp_obj[i + 1])).sum();
+        approx_length * avg_width
+    }
+
+    // samples (u, v) uniformly over the curve and its width, flat and
+    // cylinder curves face the ray so any orientation around the curve is
+    // used for them. The pdf is as approximate as the area
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let u_curve = lerp_float(u.x, self.u_min, self.u_max);
+        let (p_curve, dpdu) = eval_bezier(&self.common.cp_obj, u_curve);
+        let n_width = if self.common.curve_type == CurveType::Ribbon {
+            Vector3f::from(self.ribbon_normal(u_curve))
+        } else {
+            coordinate_system(&dpdu.normalize()).0
+        };
+
+        // offset the point across the width like dpdv of intersections
+        let width = self.width_at(u_curve);
+        let dpdv = n_width.cross(&dpdu).normalize() * width;
+        let p_obj = p_curve + dpdv * (u.y - 0.5);
+        let p_error = Vector3f::new(2.0 * width, 2.0 * width, 2.0 * width);
+        let n = Normal3f::from(dpdu.cross(&dpdv).normalize());
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+        (it, 1.0 / self.area())
+    }
+}
+
+// splits the curve into 2^split_depth segments that are intersected
+// independently
+#[allow(clippy::too_many_arguments)]
+pub fn create_curves(
+    object_to_world: Arc<Transform>,
+    world_to_object: Arc<Transform>,
+    reverse_orientation: bool,
+    c: &[Point3f; 4],
+    width0: Float,
+    width1: Float,
+    curve_type: CurveType,
+    norm: Option<&[Normal3f; 2]>,
+    split_depth: u32,
+) -> Result<Vec<Curve>, String> {
+    let common = Arc::new(CurveCommon::new(c, width0, width1, curve_type, norm)?);
+    let n_segments = 1_usize
+        .checked_shl(split_depth)
+        .filter(|&n| n <= u32::MAX as usize)
+        .ok_or_else(|| format!("create_curves split depth {} is too large", split_depth))?;
+    Ok((0..n_segments)
+        .map(|i| {
+            Curve::new(
+                object_to_world.clone(),
+                world_to_object.clone(),
+                reverse_orientation,
+                common.clone(),
+                i as Float / n_segments as Float,
+                (i + 1) as Float / n_segments as Float,
+            )
+        })
+        .collect())
+}
+
+// false if the ray space control points, widened by half of max_width, lie
+// entirely to one side of the ray or outside of [0, z_max] along it. y is
+// tested first since the curve's extent is usually smallest there
+fn overlaps_ray(cp: &[Point3f; 4], max_width: Float, z_max: Float) -> bool {
+    let half_width = 0.5 * max_width;
+    let max = |f: fn(&Point3f) -> Float| cp.iter().map(f).fold(Float::NEG_INFINITY, Float::max);
+    let min = |f: fn(&Point3f) -> Float| cp.iter().map(f).fold(Float::INFINITY, Float::min);
+    !(max(|p| p.y) + half_width < 0.0
+        || min(|p| p.y) - half_width > 0.0
+        || max(|p| p.x) + half_width < 0.0
+        || min(|p| p.x) - half_width > 0.0
+        || max(|p| p.z) + half_width < 0.0
+        || min(|p| p.z) - half_width > z_max)
+}
+
+// the point of the cubic Bezier curve at u and its derivative there
+fn eval_bezier(cp: &[Point3f; 4], u: Float) -> (Point3f, Vector3f) {
+    let cp1 = [
+        lerp(u, &cp[0], &cp[1]),
+        lerp(u, &cp[1], &cp[2]),
+        lerp(u, &cp[2], &cp[3]),
+    ];
+    let cp2 = [lerp(u, &cp1[0], &cp1[1]), lerp(u, &cp1[1], &cp1[2])];
+    let deriv = if (cp2[1] - cp2[0]).length_squared() > 0.0 {
+        (cp2[1] - cp2[0]) * 3.0
+    } else {
+        // the derivative is legitimately zero if the first or last three
+        // control points coincide, fall back to the end point difference so
+        // that there still is a surface normal
+        cp[3] - cp[0]
+    };
+    (lerp(u, &cp2[0], &cp2[1]), deriv)
+}
+
+// control points of the two halves of the curve, sharing the middle one
+fn subdivide_bezier(cp: &[Point3f; 4]) -> [Point3f; 7] {
+    [
+        cp[0],
+        (cp[0] + cp[1]) / 2.0,
+        (cp[0] + cp[1] * 2.0 + cp[2]) / 4.0,
+        (cp[0] + cp[1] * 3.0 + cp[2] * 3.0 + cp[3]) / 8.0,
+        (cp[1] + cp[2] * 2.0 + cp[3]) / 4.0,
+        (cp[2] + cp[3]) / 2.0,
+        cp[3],
+    ]
+}
+
+fn blossom_bezier(p: &[Point3f; 4], u0: Float, u1: Float, u2: Float) -> Point3f {
+    let a = [
+        lerp(u0, &p[0], &p[1]),
+        lerp(u0, &p[1], &p[2]),
+        lerp(u0, &p[2], &p[3]),
+    ];
+    let b = [lerp(u1, &a[0], &a[1]), lerp(u1, &a[1], &a[2])];
+    lerp(u2, &b[0], &b[1])
+}
+
+// log2 of v rounded to the nearest integer, 0 for v < 1
+fn log2_int(v: Float) -> i32 {
+    if v < 1.0 {
+        return 0;
+    }
+    let e = v.log2().floor();
+    e as i32 + i32::from(v >= 1.5 * e.exp2())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    // a straight curve along x from -1 to 1
+    fn line(curve_type: CurveType, norm: Option<&[Normal3f; 2]>) -> Result<Vec<Curve>, String> {
+        create_curves(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            &[
+                Point3f::new(-1.0, 0.0, 0.0),
+                Point3f::new(-1.0 / 3.0, 0.0, 0.0),
+                Point3f::new(1.0 / 3.0, 0.0, 0.0),
+                Point3f::new(1.0, 0.0, 0.0),
+            ],
+            0.2,
+            0.2,
+            curve_type,
+            norm,
+            2,
+        )
+    }
+
+    #[test]
+    fn flat_curve_is_hit_by_its_segment() {
+        let curves = line(CurveType::Flat, None).unwrap();
+        assert_eq!(curves.len(), 4);
+        let r = ray(Point3f::new(0.3, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let hits: Vec<_> = curves.iter().filter_map(|c| c.intersect(&r)).collect();
+        assert_eq!(hits.len(), 1);
+        let (t_hit, si) = &hits[0];
+        assert!((t_hit - 5.0).abs() < 1e-3);
+        assert!((si.uv.x - 0.65).abs() < 1e-2);
+        assert!(curves[2].intersect_p(&r));
+        let miss = ray(Point3f::new(0.3, 0.5, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!(curves.iter().all(|c| !c.intersect_p(&miss)));
+    }
+
+    #[test]
+    fn ribbon_curves_need_normals() {
+        assert!(line(CurveType::Ribbon, None).is_err());
+        let n = [Normal3f::new(0.0, 0.0, 1.0), Normal3f::new(0.0, 1.0, 1.0)];
+        assert!(line(CurveType::Ribbon, Some(&n)).is_ok());
+    }
+
+    #[test]
+    fn samples_lie_within_the_curve_width() {
+        let curves = line(CurveType::Flat, None).unwrap();
+        for (i, curve) in curves.iter().enumerate() {
+            let (it, pdf) = curve.sample_area(&Point2f::new(0.5, 0.9));
+            let p = it.p();
+            // the segments split [-1, 1] into quarters
+            let x_mid = -0.75 + 0.5 * i as Float;
+            assert!((p.x - x_mid).abs() < 1e-5);
+            assert!((p.y * p.y + p.z * p.z).sqrt() <= 0.1 + 1e-5);
+            assert!((pdf * curve.area() - 1.0).abs() < 1e-6);
+        }
+    }
+}
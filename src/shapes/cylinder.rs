@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{Vector3f, Vector3fi},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    interval::Interval,
+    pbrt::{clamp, gamma, lerp, radians, Float, PI},
+    shape::{quadric_sample, weingarten, QuadricIntersection, Shape},
+};
+
+// Cylinder
+// around the object space z axis between z_min and z_max, optionally cut off
+// beyond phi_max
+pub struct Cylinder {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub radius: Float,
+    pub z_min: Float,
+    pub z_max: Float,
+    pub phi_max: Float,
+}
+
+impl Cylinder {
+    // phi_max is in degrees
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+        phi_max: Float,
+    ) -> Cylinder {
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Cylinder {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            radius,
+            z_min: z_min.min(z_max),
+            z_max: z_min.max(z_max),
+            phi_max: radians(clamp(phi_max, 0.0, 360.0)),
+        }
+    }
+
+    fn is_clipped(&self, p_hit: &Point3f, phi: Float) -> bool {
+        p_hit.z < self.z_min || p_hit.z > self.z_max || phi > self.phi_max
+    }
+
+    // refine the cylinder intersection point and compute its phi
+    fn hit_point(&self, oi: &Point3fi, di: &Vector3fi, t: Float) -> (Point3f, Float) {
+        let mut p_hit = oi.midpoint() + di.midpoint() * t;
+        let hit_rad = (p_hit.x * p_hit.x + p_hit.y * p_hit.y).sqrt();
+        p_hit.x *= self.radius / hit_rad;
+        p_hit.y *= self.radius / hit_rad;
+        let mut phi = p_hit.y.atan2(p_hit.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        (p_hit, phi)
+    }
+
+    fn basic_intersect(&self, r: &Ray) -> Option<QuadricIntersection> {
+        let t_max = r.t_max.get();
+        // transform ray origin and direction to object space
+        let oi = self
+            .world_to_object
+            .transform_point3fi(&Point3fi::from(r.o));
+        let di = self
+            .world_to_object
+            .transform_vector3fi(&Vector3fi::from(r.d));
+
+        // solve quadratic equation to find cylinder t0 and t1 values
+        let a = di.x.sqr() + di.y.sqr();
+        if a.upper_bound() == 0.0 {
+            // the ray is parallel to the cylinder axis
+            return None;
+        }
+        let b = 2.0 * (di.x * oi.x + di.y * oi.y);
+        let c = oi.x.sqr() + oi.y.sqr() - Interval::from(self.radius).sqr();
+        // compute the discriminant from the distance between the axis and the
+        // closest point on the ray, which is more accurate than b^2 - 4ac
+        let f = b / (2.0 * a);
+        let vx = oi.x - f * di.x;
+        let vy = oi.y - f * di.y;
+        let length = (vx.sqr() + vy.sqr()).sqrt();
+        let discrim = 4.0
+            * a
+            * (Interval::from(self.radius) + length)
+            * (Interval::from(self.radius) - length);
+        if discrim.lower_bound() < 0.0 {
+            return None;
+        }
+        let root_discrim = discrim.sqrt();
+        let q = if Float::from(b) < 0.0 {
+            -0.5 * (b - root_discrim)
+        } else {
+            -0.5 * (b + root_discrim)
+        };
+        let mut t0 = q / a;
+        let mut t1 = c / q;
+        if t0.lower_bound() > t1.lower_bound() {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+
+        // check quadric shape t0 and t1 for nearest intersection
+        if t0.upper_bound() > t_max || t1.lower_bound() <= 0.0 {
+            return None;
+        }
+        let mut t_shape_hit = t0;
+        if t_shape_hit.lower_bound() <= 0.0 {
+            t_shape_hit = t1;
+            if t_shape_hit.upper_bound() > t_max {
+                return None;
+            }
+        }
+        let (mut p_hit, mut phi) = self.hit_point(&oi, &di, Float::from(t_shape_hit));
+
+        // test cylinder intersection against clipping parameters
+        if self.is_clipped(&p_hit, phi) {
+            if t_shape_hit == t1 || t1.upper_bound() > t_max {
+                return None;
+            }
+            t_shape_hit = t1;
+            (p_hit, phi) = self.hit_point(&oi, &di, Float::from(t_shape_hit));
+            if self.is_clipped(&p_hit, phi) {
+                return None;
+            }
+        }
+        Some(QuadricIntersection {
+            t_hit: Float::from(t_shape_hit),
+            p_obj: p_hit,
+            p_error: Vector3f::new(p_hit.x, p_hit.y, 0.0).abs() * gamma(3),
+            phi,
+        })
+    }
+
+    fn interaction_from_intersection(
+        &self,
+        isect: &QuadricIntersection,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction {
+        let p_hit = isect.p_obj;
+        // find parametric representation of cylinder hit
+        let u = isect.phi / self.phi_max;
+        let v = (p_hit.z - self.z_min) / (self.z_max - self.z_min);
+
+        // compute cylinder dpdu and dpdv
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let dpdv = Vector3f::new(0.0, 0.0, self.z_max - self.z_min);
+
+        // compute cylinder dndu and dndv
+        let d2pduu = Vector3f::new(p_hit.x, p_hit.y, 0.0) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::default();
+        let d2pdvv = Vector3f::default();
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &d2pduv, &d2pdvv);
+
+        // return SurfaceInteraction for quadric intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let wo_object = self.world_to_object.transform_vector3f(wo);
+        self.object_to_world
+            .transform_surface_interaction(&SurfaceInteraction::new(
+                Point3fi::new_with_error(&p_hit, &isect.p_error),
+                Point2f::new(u, v),
+                wo_object,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+                time,
+                flip_normal,
+            ))
+    }
+}
+
+impl Shape for Cylinder {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(-self.radius, -self.radius, self.z_min),
+            Point3f::new(self.radius, self.radius, self.z_max),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let isect = self.basic_intersect(ray)?;
+        let si = self.interaction_from_intersection(&isect, &-ray.d, ray.time);
+        Some((isect.t_hit, si))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.basic_intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        (self.z_max - self.z_min) * self.radius * self.phi_max
+    }
+
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let z = lerp(u.x, self.z_min, self.z_max);
+        let phi = u.y * self.phi_max;
+        // compute cylinder sample position and its error
+        let p_obj = Point3f::new(self.radius * phi.cos(), self.radius * phi.sin(), z);
+        let p_error = Vector3f::new(p_obj.x, p_obj.y, 0.0).abs() * gamma(3);
+        let n = Normal3f::new(p_obj.x, p_obj.y, 0.0) / self.radius;
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+        (it, 1.0 / self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hits_the_wall_from_outside_and_inside() {
+        let cylinder = Cylinder::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            1.0,
+            -1.0,
+            1.0,
+            360.0,
+        );
+        let r = ray(Point3f::new(-5.0, 0.0, 0.5), Vector3f::new(1.0, 0.0, 0.0));
+        let (t_hit, si) = cylinder.intersect(&r).unwrap();
+        assert!((t_hit - 4.0).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(-1.0, 0.0, 0.5)).length() < 1e-6);
+        assert!(Vector3f::from(si.n()).dot(&Vector3f::new(-1.0, 0.0, 0.0)) > 0.99);
+        let r = ray(Point3f::new(0.0, 0.0, 0.0), Vector3f::new(0.0, 1.0, 0.0));
+        assert!((cylinder.intersect(&r).unwrap().0 - 1.0).abs() < 1e-6);
+        // above z_max
+        let r = ray(Point3f::new(-5.0, 0.0, 1.5), Vector3f::new(1.0, 0.0, 0.0));
+        assert!(!cylinder.intersect_p(&r));
+        assert!((cylinder.area() - 4.0 * PI).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{Vector3f, Vector3fi},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{clamp, lerp, radians, Float, PI},
+    shape::{quadric_sample, QuadricIntersection, Shape},
+};
+
+// Disk
+// in the plane z = height of object space, facing +z, with an optional hole
+// of inner_radius and optionally cut off beyond phi_max
+pub struct Disk {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub height: Float,
+    pub radius: Float,
+    pub inner_radius: Float,
+    pub phi_max: Float,
+}
+
+impl Disk {
+    // phi_max is in degrees
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        height: Float,
+        radius: Float,
+        inner_radius: Float,
+        phi_max: Float,
+    ) -> Disk {
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Disk {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            height,
+            radius,
+            inner_radius,
+            phi_max: radians(clamp(phi_max, 0.0, 360.0)),
+        }
+    }
+
+    fn basic_intersect(&self, r: &Ray) -> Option<QuadricIntersection> {
+        // transform ray origin and direction to object space
+        let oi = self
+            .world_to_object
+            .transform_point3fi(&Point3fi::from(r.o));
+        let di = self
+            .world_to_object
+            .transform_vector3fi(&Vector3fi::from(r.d));
+
+        // compute plane intersection for disk
+        // reject disk intersections for rays parallel to the disk's plane
+        let (o, d) = (oi.midpoint(), di.midpoint());
+        if d.z == 0.0 {
+            return None;
+        }
+        let t_shape_hit = (self.height - o.z) / d.z;
+        if t_shape_hit <= 0.0 || t_shape_hit >= r.t_max.get() {
+            return None;
+        }
+
+        // see if hit point is inside disk radii and phi_max
+        let mut p_hit = o + d * t_shape_hit;
+        let dist2 = p_hit.x * p_hit.x + p_hit.y * p_hit.y;
+        if dist2 > self.radius * self.radius || dist2 < self.inner_radius * self.inner_radius {
+            return None;
+        }
+        let mut phi = p_hit.y.atan2(p_hit.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        if phi > self.phi_max {
+            return None;
+        }
+
+        // refine disk intersection point, which is exact in z
+        p_hit.z = self.height;
+        Some(QuadricIntersection {
+            t_hit: t_shape_hit,
+            p_obj: p_hit,
+            p_error: Vector3f::default(),
+            phi,
+        })
+    }
+
+    fn interaction_from_intersection(
+        &self,
+        isect: &QuadricIntersection,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction {
+        let p_hit = isect.p_obj;
+        // find parametric representation of disk hit
+        let u = isect.phi / self.phi_max;
+        let r_hit = (p_hit.x * p_hit.x + p_hit.y * p_hit.y).sqrt();
+        let v = (self.radius - r_hit) / (self.radius - self.inner_radius);
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let dpdv =
+            Vector3f::new(p_hit.x, p_hit.y, 0.0) * ((self.inner_radius - self.radius) / r_hit);
+
+        // return SurfaceInteraction for quadric intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let wo_object = self.world_to_object.transform_vector3f(wo);
+        self.object_to_world
+            .transform_surface_interaction(&SurfaceInteraction::new(
+                Point3fi::new_with_error(&p_hit, &isect.p_error),
+                Point2f::new(u, v),
+                wo_object,
+                dpdu,
+                dpdv,
+                Normal3f::default(),
+                Normal3f::default(),
+                time,
+                flip_normal,
+            ))
+    }
+}
+
+impl Shape for Disk {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(-self.radius, -self.radius, self.height),
+            Point3f::new(self.radius, self.radius, self.height),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let isect = self.basic_intersect(ray)?;
+        let si = self.interaction_from_intersection(&isect, &-ray.d, ray.time);
+        Some((isect.t_hit, si))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.basic_intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.phi_max * 0.5 * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+
+    // uniform in r^2 and phi, which is uniform over the area of the ring
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let r = lerp(
+            u.x,
+            self.inner_radius * self.inner_radius,
+            self.radius * self.radius,
+        )
+        .sqrt();
+        let phi = u.y * self.phi_max;
+        let p_obj = Point3f::new(r * phi.cos(), r * phi.sin(), self.height);
+        let n = Normal3f::new(0.0, 0.0, 1.0);
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(
+            &self.object_to_world,
+            &p_obj,
+            &Vector3f::default(),
+            &n,
+            flip_normal,
+        );
+        (it, 1.0 / self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hits_between_inner_and_outer_radius() {
+        let disk = Disk::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            1.0,
+            2.0,
+            0.5,
+            360.0,
+        );
+        let down = Vector3f::new(0.0, 0.0, -1.0);
+        let (t_hit, si) = disk
+            .intersect(&ray(Point3f::new(1.0, 0.0, 5.0), down))
+            .unwrap();
+        assert!((t_hit - 4.0).abs() < 1e-6);
+        assert_eq!(si.p(), Point3f::new(1.0, 0.0, 1.0));
+        assert!(!disk.intersect_p(&ray(Point3f::new(0.2, 0.0, 5.0), down)));
+        assert!(!disk.intersect_p(&ray(Point3f::new(2.5, 0.0, 5.0), down)));
+        // parallel to the disk
+        let r = ray(Point3f::new(-5.0, 0.0, 1.0), Vector3f::new(1.0, 0.0, 0.0));
+        assert!(!disk.intersect_p(&r));
+        assert!((disk.area() - PI * 3.75).abs() < 1e-5);
+    }
+}
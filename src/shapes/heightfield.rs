@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::{Bounds2f, Bounds3f},
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{coordinate_system, Vector3f},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{gamma, lerp, Float},
+    sampling::{sample_cdf, sample_uniform_triangle},
+    shape::{quadric_sample, Shape},
+};
+
+use super::triangle::{intersect_triangle, TriangleIntersection};
+
+// Heightfield
+// z = z[y * nx + x] sampled on an nx by ny grid spanning bounds in the xy
+// plane of object space, facing +z. Every grid cell is split into two
+// triangles which are intersected on the fly while stepping through the cells
+// the ray passes over, so no triangle data is stored
+pub struct Heightfield {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub bounds: Bounds2f,
+    pub nx: usize,
+    pub ny: usize,
+    pub z: Vec<Float>,
+    pub z_min: Float,
+    pub z_max: Float,
+    // running sums of the triangle areas in cell order, two per cell
+    pub triangle_cdf: Vec<Float>,
+}
+
+impl Heightfield {
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        bounds: Bounds2f,
+        nx: usize,
+        ny: usize,
+        z: Vec<Float>,
+    ) -> Result<Heightfield, String> {
+        if nx < 2 || ny < 2 {
+            return Err(format!(
+                "Heightfield needs at least 2x2 samples, got {}x{}",
+                nx, ny
+            ));
+        }
+        if nx.checked_mul(ny) != Some(z.len()) {
+            return Err(format!(
+                "Heightfield has {} heights for {}x{} samples",
+                z.len(),
+                nx,
+                ny
+            ));
+        }
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        let z_min = z.iter().copied().fold(Float::INFINITY, Float::min);
+        let z_max = z.iter().copied().fold(Float::NEG_INFINITY, Float::max);
+        let mut heightfield = Heightfield {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            bounds,
+            nx,
+            ny,
+            z,
+            z_min,
+            z_max,
+            triangle_cdf: Vec::with_capacity(2 * (nx - 1) * (ny - 1)),
+        };
+        let mut area = 0.0;
+        for y in 0..ny - 1 {
+            for x in 0..nx - 1 {
+                for triangle in 0..2 {
+                    let [p0, p1, p2] = heightfield.triangle_positions(x, y, triangle);
+                    area += 0.5 * (p1 - p0).cross(&(p2 - p0)).length();
+                    heightfield.triangle_cdf.push(area);
+                }
+            }
+        }
+        Ok(heightfield)
+    }
+
+    fn height(&self, x: usize, y: usize) -> Float {
+        self.z[y * self.nx + x]
+    }
+
+    fn uv(&self, x: usize, y: usize) -> Point2f {
+        Point2f::new(
+            x as Float / (self.nx - 1) as Float,
+            y as Float / (self.ny - 1) as Float,
+        )
+    }
+
+    fn position(&self, x: usize, y: usize) -> Point3f {
+        let uv = self.uv(x, y);
+        Point3f::new(
+            lerp(uv.x, self.bounds.p_min.x, self.bounds.p_max.x),
+            lerp(uv.y, self.bounds.p_min.y, self.bounds.p_max.y),
+            self.height(x, y),
+        )
+    }
+
+    // smooth normal at a grid sample from central differences, one-sided at
+    // the border
+    fn vertex_normal(&self, x: usize, y: usize) -> Normal3f {
+        let cell_size = self.cell_size();
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.nx - 1));
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(self.ny - 1));
+        let dzdx = (self.height(x1, y) - self.height(x0, y)) / ((x1 - x0) as Float * cell_size.x);
+        let dzdy = (self.height(x, y1) - self.height(x, y0)) / ((y1 - y0) as Float * cell_size.y);
+        Normal3f::new(-dzdx, -dzdy, 1.0).normalize()
+    }
+
+    fn cell_size(&self) -> Point2f {
+        let diagonal = self.bounds.diagonal();
+        Point2f::new(
+            diagonal.x / (self.nx - 1) as Float,
+            diagonal.y / (self.ny - 1) as Float,
+        )
+    }
+
+    // grid samples of the two counter-clockwise triangles of a cell
+    fn cell_triangle(x: usize, y: usize, triangle: usize) -> [(usize, usize); 3] {
+        if triangle == 0 {
+            [(x, y), (x + 1, y), (x + 1, y + 1)]
+        } else {
+            [(x, y), (x + 1, y + 1), (x, y + 1)]
+        }
+    }
+
+    fn triangle_positions(&self, x: usize, y: usize, triangle: usize) -> [Point3f; 3] {
+        Heightfield::cell_triangle(x, y, triangle).map(|(x, y)| self.position(x, y))
+    }
+
+    // conservatively checks whether the ray's z range over [t0, t1] overlaps
+    // the heights of the cell
+    fn overlaps_cell(&self, r: &Ray, t0: Float, t1: Float, x: usize, y: usize) -> bool {
+        let heights = [
+            self.height(x, y),
+            self.height(x + 1, y),
+            self.height(x, y + 1),
+            self.height(x + 1, y + 1),
+        ];
+        let cell_min = heights.iter().copied().fold(Float::INFINITY, Float::min);
+        let cell_max = heights
+            .iter()
+            .copied()
+            .fold(Float::NEG_INFINITY, Float::max);
+        let (z0, z1) = (r.o.z + r.d.z * t0, r.o.z + r.d.z * t1);
+        let (ray_min, ray_max) = (z0.min(z1), z0.max(z1));
+        let slack = gamma(8)
+            * (ray_min.abs().max(ray_max.abs())
+                + cell_min.abs().max(cell_max.abs())
+                + (r.d.z * t1).abs());
+        ray_max + slack >= cell_min && ray_min - slack <= cell_max
+    }
+
+    // walks the cells under the object space ray with a 2D DDA and returns
+    // the closest triangle hit, or the first one found if any_hit is set
+    fn find_hit(
+        &self,
+        r: &Ray,
+        any_hit: bool,
+    ) -> Option<(TriangleIntersection, [(usize, usize); 3])> {
+        // clip ray against the heightfield bounds
+        let mut t0 = 0.0;
+        let mut t1 = 0.0;
+        if !self.object_bound().intersect_b(r, &mut t0, &mut t1) {
+            return None;
+        }
+
+        // set up 2D DDA for ray
+        let cell_size = self.cell_size();
+        let n_cells = [self.nx as isize - 1, self.ny as isize - 1];
+        let p_grid = r.position(t0);
+        let mut cell = [0isize; 2];
+        let mut next_crossing_t = [Float::INFINITY; 2];
+        let mut delta_t = [Float::INFINITY; 2];
+        let mut step = [0isize; 2];
+        let mut out = [-1isize; 2];
+        for axis in 0..2 {
+            // compute current cell for axis
+            let o = p_grid[axis] - self.bounds.p_min[axis];
+            cell[axis] = ((o / cell_size[axis]) as isize).clamp(0, n_cells[axis] - 1);
+
+            if r.d[axis] > 0.0 {
+                // handle ray with positive direction for DDA
+                next_crossing_t[axis] =
+                    t0 + ((cell[axis] + 1) as Float * cell_size[axis] - o) / r.d[axis];
+                delta_t[axis] = cell_size[axis] / r.d[axis];
+                step[axis] = 1;
+                out[axis] = n_cells[axis];
+            } else if r.d[axis] < 0.0 {
+                // handle ray with negative direction for DDA
+                next_crossing_t[axis] =
+                    t0 + (cell[axis] as Float * cell_size[axis] - o) / r.d[axis];
+                delta_t[axis] = -cell_size[axis] / r.d[axis];
+                step[axis] = -1;
+                out[axis] = -1;
+            }
+        }
+
+        // walk ray through cell grid
+        let mut t_max = r.t_max.get();
+        let mut t_enter = t0;
+        let mut closest = None;
+        loop {
+            let (x, y) = (cell[0] as usize, cell[1] as usize);
+            let t_exit = next_crossing_t[0].min(next_crossing_t[1]).min(t1);
+            if self.overlaps_cell(r, t_enter, t_exit, x, y) {
+                // check for intersection in current cell
+                for triangle in 0..2 {
+                    let v = Heightfield::cell_triangle(x, y, triangle);
+                    let [p0, p1, p2] = v.map(|(x, y)| self.position(x, y));
+                    if let Some(hit) = intersect_triangle(r, t_max, &p0, &p1, &p2) {
+                        t_max = hit.t;
+                        closest = Some((hit, v));
+                        if any_hit {
+                            return closest;
+                        }
+                    }
+                }
+                // hits in the cells after this one are further along the ray
+                if closest.is_some() && t_max <= t_exit {
+                    return closest;
+                }
+            }
+
+            // advance to next cell
+            let axis = if next_crossing_t[0] < next_crossing_t[1] {
+                0
+            } else {
+                1
+            };
+            if next_crossing_t[axis] > t1.min(t_max) {
+                return closest;
+            }
+            cell[axis] += step[axis];
+            if cell[axis] == out[axis] {
+                return closest;
+            }
+            t_enter = next_crossing_t[axis];
+            next_crossing_t[axis] += delta_t[axis];
+        }
+    }
+}
+
+impl Shape for Heightfield {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(self.bounds.p_min.x, self.bounds.p_min.y, self.z_min),
+            Point3f::new(self.bounds.p_max.x, self.bounds.p_max.y, self.z_max),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        // transform ray to object space
+        let r = self.world_to_object.transform_ray(ray);
+        let (TriangleIntersection { b0, b1, b2, t }, v) = self.find_hit(&r, false)?;
+        let [p0, p1, p2] = v.map(|(x, y)| self.position(x, y));
+        let uv = v.map(|(x, y)| self.uv(x, y));
+
+        // compute heightfield partial derivatives from the hit triangle,
+        // which never has degenerate (u,v)
+        let duv02 = uv[0] - uv[2];
+        let duv12 = uv[1] - uv[2];
+        let dp02 = p0 - p2;
+        let dp12 = p1 - p2;
+        let invdet = 1.0 / (duv02.x * duv12.y - duv02.y * duv12.x);
+        let dpdu = (dp02 * duv12.y - dp12 * duv02.y) * invdet;
+        let dpdv = (dp12 * duv02.x - dp02 * duv12.x) * invdet;
+
+        // interpolate (u,v) parametric coordinates and hit point
+        let p_error =
+            Vector3f::from((p0 * b0).abs() + (p1 * b1).abs() + (p2 * b2).abs()) * gamma(7);
+        let p_hit = p0 * b0 + p1 * b1 + p2 * b2;
+        let uv_hit = Point2f::new(
+            b0 * uv[0].x + b1 * uv[1].x + b2 * uv[2].x,
+            b0 * uv[0].y + b1 * uv[1].y + b2 * uv[2].y,
+        );
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let mut isect = SurfaceInteraction::new(
+            Point3fi::new_with_error(&p_hit, &p_error),
+            uv_hit,
+            -r.d,
+            dpdu,
+            dpdv,
+            Normal3f::default(),
+            Normal3f::default(),
+            ray.time,
+            flip_normal,
+        );
+
+        // shade with the normals interpolated from the grid samples
+        let [n0, n1, n2] = v.map(|(x, y)| self.vertex_normal(x, y));
+        let ns = Vector3f::from((n0 * b0 + n1 * b1 + n2 * b2).normalize());
+        let mut ss = dpdu;
+        let mut ts = ss.cross(&ns);
+        if ts.length_squared() > 0.0 {
+            ts = ts.normalize();
+            ss = ts.cross(&ns);
+        } else {
+            (ss, ts) = coordinate_system(&ns);
+        }
+        let dn1 = n0 - n2;
+        let dn2 = n1 - n2;
+        let dndu = (dn1 * duv12.y - dn2 * duv02.y) * invdet;
+        let dndv = (dn2 * duv02.x - dn1 * duv12.x) * invdet;
+        isect.set_shading_geometry(&ss, &ts, &dndu, &dndv, false);
+        Some((
+            t,
+            self.object_to_world.transform_surface_interaction(&isect),
+        ))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        let r = self.world_to_object.transform_ray(ray);
+        self.find_hit(&r, true).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.triangle_cdf[self.triangle_cdf.len() - 1]
+    }
+
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        // pick a triangle proportionally to its area and reuse u.x in it
+        let (i, ux) = sample_cdf(&self.triangle_cdf, u.x);
+        let cell = i / 2;
+        let (x, y) = (cell % (self.nx - 1), cell / (self.nx - 1));
+        let [p0, p1, p2] = self.triangle_positions(x, y, i % 2);
+
+        // sample the triangle, its counter-clockwise winding gives the
+        // normal of intersections
+        let [b0, b1, b2] = sample_uniform_triangle(&Point2f::new(ux, u.y));
+        let p_obj = p0 * b0 + p1 * b1 + p2 * b2;
+        let p_error =
+            Vector3f::from((p0 * b0).abs() + (p1 * b1).abs() + (p2 * b2).abs()) * gamma(6);
+        let n = Normal3f::from((p1 - p0).cross(&(p2 - p0)).normalize());
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+        (it, 1.0 / self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    fn heightfield(nx: usize, ny: usize, z: Vec<Float>) -> Result<Heightfield, String> {
+        Heightfield::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            Bounds2f::new_with_points(Point2f::new(0.0, 0.0), Point2f::new(2.0, 2.0)),
+            nx,
+            ny,
+            z,
+        )
+    }
+
+    #[test]
+    fn tilted_plane_is_hit_at_its_height() {
+        // z = x sampled on a 3x3 grid
+        let h = heightfield(3, 3, vec![0.0, 1.0, 2.0, 0.0, 1.0, 2.0, 0.0, 1.0, 2.0]).unwrap();
+        let r = ray(Point3f::new(1.5, 0.5, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t_hit, si) = h.intersect(&r).unwrap();
+        assert!((t_hit - 3.5).abs() < 1e-5);
+        assert!((si.uv - Point2f::new(0.75, 0.25)).length() < 1e-5);
+        // a grazing ray crosses several cells before reaching the surface
+        let r = ray(Point3f::new(-1.0, 1.2, 0.6), Vector3f::new(1.0, 0.0, -0.1));
+        let (t_hit, _) = h.intersect(&r).unwrap();
+        assert!((t_hit - 1.6 / 1.1).abs() < 1e-5);
+        let miss = ray(Point3f::new(1.5, 0.5, 5.0), Vector3f::new(0.0, 0.0, 1.0));
+        assert!(!h.intersect_p(&miss));
+        assert!((h.area() - 4.0 * (2.0 as Float).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn grid_must_match_the_heights() {
+        assert!(heightfield(1, 3, vec![0.0; 3]).is_err());
+        assert!(heightfield(2, 2, vec![0.0; 3]).is_err());
+        assert!(heightfield(usize::MAX, 2, vec![0.0; 4]).is_err());
+        assert!(heightfield(2, 2, vec![0.0; 4]).is_ok());
+    }
+
+    #[test]
+    fn samples_lie_on_the_surface() {
+        let h = heightfield(3, 3, vec![0.0, 1.0, 2.0, 0.0, 1.0, 2.0, 0.0, 1.0, 2.0]).unwrap();
+        for u in [
+            Point2f::new(0.1, 0.2),
+            Point2f::new(0.5, 0.9),
+            Point2f::new(0.95, 0.4),
+        ] {
+            let (it, pdf) = h.sample_area(&u);
+            let p = it.p();
+            assert!((p.z - p.x).abs() < 1e-5);
+            assert!((0.0..=2.0).contains(&p.x) && (0.0..=2.0).contains(&p.y));
+            assert!((pdf * h.area() - 1.0).abs() < 1e-6);
+            // facing +z like the hits
+            assert!(it.n.z > 0.0);
+        }
+    }
+}
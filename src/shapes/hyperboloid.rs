@@ -0,0 +1,386 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{Vector3f, Vector3fi},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{clamp, gamma, quadratic_interval, radians, Float, PI},
+    shape::{quadric_sample, weingarten, QuadricIntersection, Shape},
+};
+
+// Hyperboloid
+// the surface swept by rotating the line segment from p1 to p2 around the
+// object space z axis by up to phi_max, described implicitly as
+// x^2 + y^2 = ah z^2 + bh z + ch
+pub struct Hyperboloid {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub p1: Point3f,
+    pub p2: Point3f,
+    pub z_min: Float,
+    pub z_max: Float,
+    pub phi_max: Float,
+    pub r_max: Float,
+    pub ah: Float,
+    pub bh: Float,
+    pub ch: Float,
+}
+
+impl Hyperboloid {
+    // phi_max is in degrees
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        point1: &Point3f,
+        point2: &Point3f,
+        phi_max: Float,
+    ) -> Result<Hyperboloid, String> {
+        let (p1, p2) = (*point1, *point2);
+        if p1.z == p2.z {
+            return Err(format!(
+                "Hyperboloid end points must be at different heights, both have z = {}",
+                p1.z
+            ));
+        }
+        let radius1 = (p1.x * p1.x + p1.y * p1.y).sqrt();
+        let radius2 = (p2.x * p2.x + p2.y * p2.y).sqrt();
+
+        // compute implicit function coefficients for hyperboloid
+        // substitute v = (z - p1.z) / (p2.z - p1.z) into the squared distance
+        // of the segment point p1 + v (p2 - p1) from the z axis
+        let d = p2 - p1;
+        let dxy2 = d.x * d.x + d.y * d.y;
+        let e = p1.x * d.x + p1.y * d.y;
+        let k = dxy2 / (d.z * d.z);
+        let l = 2.0 * e / d.z;
+        let ah = k;
+        let bh = l - 2.0 * k * p1.z;
+        let ch = radius1 * radius1 - l * p1.z + k * p1.z * p1.z;
+
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Ok(Hyperboloid {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            p1,
+            p2,
+            z_min: p1.z.min(p2.z),
+            z_max: p1.z.max(p2.z),
+            phi_max: radians(clamp(phi_max, 0.0, 360.0)),
+            r_max: radius1.max(radius2),
+            ah,
+            bh,
+            ch,
+        })
+    }
+
+    // coefficients of qa v^2 + qb v + qc, the square of |dp/dphi x dp/dv|
+    // along the p1 p2 segment
+    fn area_quadratic(&self) -> (Float, Float, Float) {
+        let d = self.p2 - self.p1;
+        let dxy2 = d.x * d.x + d.y * d.y;
+        let dz2 = d.z * d.z;
+        let e = self.p1.x * d.x + self.p1.y * d.y;
+        let r12 = self.p1.x * self.p1.x + self.p1.y * self.p1.y;
+        (
+            dz2 * dxy2 + dxy2 * dxy2,
+            2.0 * e * (dz2 + dxy2),
+            dz2 * r12 + e * e,
+        )
+    }
+
+    // |dpdu x dpdv| at v, it does not depend on u
+    fn uv_jacobian(&self, v: Float) -> Float {
+        let (qa, qb, qc) = self.area_quadratic();
+        self.phi_max * (qa * v * v + qb * v + qc).max(0.0).sqrt()
+    }
+
+    // phi of the hit point measured from the point of the p1 p2 segment at
+    // the same height, or None if it lies in a part of the hyperboloid that
+    // was cut away
+    fn unclipped_phi(&self, p_hit: &Point3f) -> Option<Float> {
+        let v = (p_hit.z - self.p1.z) / (self.p2.z - self.p1.z);
+        let pr = self.p1 * (1.0 - v) + self.p2 * v;
+        let mut phi = (pr.x * p_hit.y - p_hit.x * pr.y).atan2(p_hit.x * pr.x + p_hit.y * pr.y);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        if p_hit.z < self.z_min || p_hit.z > self.z_max || phi > self.phi_max {
+            None
+        } else {
+            Some(phi)
+        }
+    }
+
+    fn basic_intersect(&self, r: &Ray) -> Option<QuadricIntersection> {
+        let t_max = r.t_max.get();
+        // transform ray origin and direction to object space
+        let oi = self
+            .world_to_object
+            .transform_point3fi(&Point3fi::from(r.o));
+        let di = self
+            .world_to_object
+            .transform_vector3fi(&Vector3fi::from(r.d));
+
+        // compute quadratic hyperboloid coefficients
+        let a = di.x.sqr() + di.y.sqr() - self.ah * di.z.sqr();
+        let b = 2.0 * (di.x * oi.x + di.y * oi.y - self.ah * di.z * oi.z) - self.bh * di.z;
+        let c = oi.x.sqr() + oi.y.sqr() - self.ah * oi.z.sqr() - self.bh * oi.z - self.ch;
+
+        // solve quadratic equation for t values
+        let (t0, t1) = quadratic_interval(a, b, c)?;
+
+        // check quadric shape t0 and t1 for nearest intersection
+        if t0.upper_bound() > t_max || t1.lower_bound() <= 0.0 {
+            return None;
+        }
+        let mut t_shape_hit = t0;
+        if t_shape_hit.lower_bound() <= 0.0 {
+            t_shape_hit = t1;
+            if t_shape_hit.upper_bound() > t_max {
+                return None;
+            }
+        }
+        let mut pi = oi + di * t_shape_hit;
+        let mut phi = self.unclipped_phi(&pi.midpoint());
+
+        // test hyperboloid intersection against clipping parameters
+        if phi.is_none() {
+            if t_shape_hit == t1 || t1.upper_bound() > t_max {
+                return None;
+            }
+            t_shape_hit = t1;
+            pi = oi + di * t_shape_hit;
+            phi = self.unclipped_phi(&pi.midpoint());
+        }
+        Some(QuadricIntersection {
+            t_hit: Float::from(t_shape_hit),
+            p_obj: pi.midpoint(),
+            p_error: pi.error(),
+            phi: phi?,
+        })
+    }
+
+    fn interaction_from_intersection(
+        &self,
+        isect: &QuadricIntersection,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction {
+        let p_hit = isect.p_obj;
+        // find parametric representation of hyperboloid hit
+        let u = isect.phi / self.phi_max;
+        let v = (p_hit.z - self.p1.z) / (self.p2.z - self.p1.z);
+
+        // compute hyperboloid dpdu and dpdv
+        let (sin_phi, cos_phi) = isect.phi.sin_cos();
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let dpdv = Vector3f::new(
+            (self.p2.x - self.p1.x) * cos_phi - (self.p2.y - self.p1.y) * sin_phi,
+            (self.p2.x - self.p1.x) * sin_phi + (self.p2.y - self.p1.y) * cos_phi,
+            self.p2.z - self.p1.z,
+        );
+
+        // compute hyperboloid dndu and dndv
+        let d2pduu = Vector3f::new(p_hit.x, p_hit.y, 0.0) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::new(-dpdv.y, dpdv.x, 0.0) * self.phi_max;
+        let d2pdvv = Vector3f::default();
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &d2pduv, &d2pdvv);
+
+        // return SurfaceInteraction for quadric intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let wo_object = self.world_to_object.transform_vector3f(wo);
+        self.object_to_world
+            .transform_surface_interaction(&SurfaceInteraction::new(
+                Point3fi::new_with_error(&p_hit, &isect.p_error),
+                Point2f::new(u, v),
+                wo_object,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+                time,
+                flip_normal,
+            ))
+    }
+}
+
+impl Shape for Hyperboloid {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(-self.r_max, -self.r_max, self.z_min),
+            Point3f::new(self.r_max, self.r_max, self.z_max),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let isect = self.basic_intersect(ray)?;
+        let si = self.interaction_from_intersection(&isect, &-ray.d, ray.time);
+        Some((isect.t_hit, si))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.basic_intersect(ray).is_some()
+    }
+
+    // phi_max times the integral over v of |dp/dphi x dp/dv|, whose square
+    // is the quadratic qa v^2 + qb v + qc along the p1 p2 segment
+    fn area(&self) -> Float {
+        let (qa, qb, qc) = self.area_quadratic();
+        if qa == 0.0 {
+            // the segment is parallel to the axis
+            return self.phi_max * qc.sqrt();
+        }
+        let q = |v: Float| (qa * v * v + qb * v + qc).max(0.0).sqrt();
+        let sqrt_qa = qa.sqrt();
+        let log_arg = |v: Float| 2.0 * sqrt_qa * q(v) + 2.0 * qa * v + qb;
+        let mut integral = ((2.0 * qa + qb) * q(1.0) - qb * q(0.0)) / (4.0 * qa);
+        let disc = 4.0 * qa * qc - qb * qb;
+        let (l0, l1) = (log_arg(0.0), log_arg(1.0));
+        if disc > 0.0 && l0 > 0.0 && l1 > 0.0 {
+            integral += disc / (8.0 * qa * sqrt_qa) * (l1 / l0).ln();
+        }
+        self.phi_max * integral
+    }
+
+    // uniform in (phi, v) rather than by area, so the pdf is 1 over the
+    // jacobian of the parameterization
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let v = u.x;
+        let phi = u.y * self.phi_max;
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        // rotate the point of the p1 p2 segment at v by phi
+        let pr = self.p1 * (1.0 - v) + self.p2 * v;
+        let p_obj = Point3f::new(
+            pr.x * cos_phi - pr.y * sin_phi,
+            pr.x * sin_phi + pr.y * cos_phi,
+            pr.z,
+        );
+        let p_error = Vector3f::from(p_obj.abs()) * gamma(5);
+
+        // the normal is dpdu x dpdv as for intersections
+        let d = self.p2 - self.p1;
+        let dpdu = Vector3f::new(-self.phi_max * p_obj.y, self.phi_max * p_obj.x, 0.0);
+        let dpdv = Vector3f::new(
+            d.x * cos_phi - d.y * sin_phi,
+            d.x * sin_phi + d.y * cos_phi,
+            d.z,
+        );
+        let n = Normal3f::from(dpdu.cross(&dpdv).normalize());
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+        (it, 1.0 / self.uv_jacobian(v))
+    }
+
+    // pdf_ref_by_area with the area pdf of sample_area at the hit point
+    fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        // intersect sample ray with shape geometry
+        let ray = ref_it.spawn_ray(wi);
+        let Some((_, isect)) = self.intersect(&ray) else {
+            return 0.0;
+        };
+        let p_obj = self.world_to_object.transform_point3f(&isect.p());
+        let v = (p_obj.z - self.p1.z) / (self.p2.z - self.p1.z);
+
+        // convert light sample weight to solid angle measure
+        let pdf = ref_it.p().distance_squared(&isect.p())
+            / (Vector3f::from(isect.n()).abs_dot(&wi.normalize()) * self.uv_jacobian(v));
+        if pdf.is_infinite() {
+            0.0
+        } else {
+            pdf
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::core::medium::MediumInterface;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    fn hyperboloid(p1: Point3f, p2: Point3f) -> Result<Hyperboloid, String> {
+        Hyperboloid::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            &p1,
+            &p2,
+            360.0,
+        )
+    }
+
+    #[test]
+    fn vertical_segment_sweeps_a_cylinder() {
+        let h = hyperboloid(Point3f::new(1.0, 0.0, -1.0), Point3f::new(1.0, 0.0, 1.0)).unwrap();
+        let r = ray(Point3f::new(-5.0, 0.0, 0.5), Vector3f::new(1.0, 0.0, 0.0));
+        let (t_hit, si) = h.intersect(&r).unwrap();
+        assert!((t_hit - 4.0).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(-1.0, 0.0, 0.5)).length() < 1e-6);
+        assert!((h.area() - 4.0 * PI).abs() < 1e-5);
+    }
+
+    #[test]
+    fn skewed_segment_has_a_waist() {
+        // the segment from (1, -1, -1) to (1, 1, 1) passes the z axis at
+        // distance 1 at z = 0 and sqrt(1.25) at z = 0.5
+        let h = hyperboloid(Point3f::new(1.0, -1.0, -1.0), Point3f::new(1.0, 1.0, 1.0)).unwrap();
+        let r = ray(Point3f::new(-5.0, 0.0, 0.0), Vector3f::new(1.0, 0.0, 0.0));
+        assert!((h.intersect(&r).unwrap().0 - 4.0).abs() < 1e-5);
+        let r = ray(Point3f::new(-5.0, 0.0, 0.5), Vector3f::new(1.0, 0.0, 0.0));
+        let t_hit = h.intersect(&r).unwrap().0;
+        assert!((t_hit - (5.0 - (1.25 as Float).sqrt())).abs() < 1e-5);
+        assert!(hyperboloid(Point3f::new(1.0, 0.0, 1.0), Point3f::new(0.0, 1.0, 1.0)).is_err());
+    }
+
+    #[test]
+    fn samples_lie_on_the_surface_and_match_pdf_ref() {
+        let h = hyperboloid(Point3f::new(1.0, -1.0, -1.0), Point3f::new(1.0, 1.0, 1.0)).unwrap();
+        let ref_it = Interaction::new(
+            Point3fi::from(Point3f::new(5.0, 0.0, 0.0)),
+            Normal3f::default(),
+            0.0,
+            Vector3f::default(),
+            MediumInterface::default(),
+        );
+        for u in [Point2f::new(0.5, 0.0), Point2f::new(0.6, 0.02)] {
+            let (it, pdf) = h.sample_area(&u);
+            let p = it.p();
+            let implicit = h.ah * p.z * p.z + h.bh * p.z + h.ch;
+            assert!((p.x * p.x + p.y * p.y - implicit).abs() < 1e-5);
+            assert!(pdf > 0.0);
+            // the point faces the reference point, so the pdf of sampling it
+            // from there is the one of the ray hitting it first
+            let (it, pdf) = h.sample_ref(&ref_it, &u).unwrap();
+            let wi = it.p() - ref_it.p();
+            assert!((h.pdf_ref(&ref_it, &wi) - pdf).abs() < 1e-4 * pdf);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    accelerators::bvh::BvhAccel,
+    core::{
+        geometry::{bounds::Bounds3f, point::Point2f, ray::Ray, transform::Transform},
+        interaction::{Interaction, SurfaceInteraction},
+        pbrt::Float,
+        shape::Shape,
+    },
+};
+
+// ObjectDefinitions
+// named groups of shapes for instancing. The shapes of a definition live in
+// its own object space and share one BVH between all its instances
+#[derive(Default)]
+pub struct ObjectDefinitions {
+    pub objects: HashMap<String, Arc<BvhAccel>>,
+}
+
+impl ObjectDefinitions {
+    pub fn new() -> ObjectDefinitions {
+        ObjectDefinitions::default()
+    }
+
+    pub fn define(&mut self, name: &str, shapes: Vec<Arc<dyn Shape>>) -> Result<(), String> {
+        if self.objects.contains_key(name) {
+            return Err(format!("object \"{}\" redefined", name));
+        }
+        self.objects
+            .insert(name.to_string(), Arc::new(BvhAccel::new(shapes)));
+        Ok(())
+    }
+
+    pub fn instance(
+        &self,
+        name: &str,
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+    ) -> Result<ObjectInstance, String> {
+        let Some(object) = self.objects.get(name) else {
+            return Err(format!("object \"{}\" instanced but never defined", name));
+        };
+        Ok(ObjectInstance::new(
+            object.clone(),
+            object_to_world,
+            world_to_object,
+        ))
+    }
+}
+
+// ObjectInstance
+// a copy of an object definition placed in the world by its own transform.
+// Rays are transformed into the definition's object space, which keeps t
+// since ray directions are not normalized
+pub struct ObjectInstance {
+    pub object: Arc<BvhAccel>,
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub world_bound: Bounds3f,
+}
+
+impl ObjectInstance {
+    pub fn new(
+        object: Arc<BvhAccel>,
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+    ) -> ObjectInstance {
+        let world_bound = object_to_world.transform_bounds3f(&object.world_bound());
+        ObjectInstance {
+            object,
+            object_to_world,
+            world_to_object,
+            world_bound,
+        }
+    }
+
+    // factor by which object_to_world scales areas
+    fn area_scale(&self) -> Float {
+        self.object_to_world.determinant().abs().powf(2.0 / 3.0)
+    }
+}
+
+impl Shape for ObjectInstance {
+    fn object_bound(&self) -> Bounds3f {
+        self.object.world_bound()
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.world_bound
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let r = self.world_to_object.transform_ray(ray);
+        let (t_hit, isect) = self.object.intersect(&r)?;
+        Some((
+            t_hit,
+            self.object_to_world.transform_surface_interaction(&isect),
+        ))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.object
+            .intersect_p(&self.world_to_object.transform_ray(ray))
+    }
+
+    // the definition's area scaled by the transform, exact for rotations,
+    // translations and uniform scales and an approximation otherwise
+    fn area(&self) -> Float {
+        self.object.area() * self.area_scale()
+    }
+
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        // sample the definition and transform the sample to world space
+        let (mut it, pdf) = self.object.sample_area(u);
+        if pdf == 0.0 {
+            return (it, pdf);
+        }
+        it.pi = self.object_to_world.transform_point3fi(&it.pi);
+        it.n = self.object_to_world.transform_normal3f(&it.n).normalize();
+        (it, pdf / self.area_scale())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        core::{
+            geometry::{point::Point3f, vector::Vector3f},
+            pbrt::PI,
+        },
+        shapes::sphere::Sphere,
+    };
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    fn unit_sphere() -> Arc<dyn Shape> {
+        Arc::new(Sphere::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            1.0,
+            -1.0,
+            1.0,
+            360.0,
+        ))
+    }
+
+    fn placed(
+        definitions: &ObjectDefinitions,
+        name: &str,
+        t: Transform,
+    ) -> Result<ObjectInstance, String> {
+        let inverse = t.inverse();
+        definitions.instance(name, Arc::new(t), Arc::new(inverse))
+    }
+
+    #[test]
+    fn instances_share_a_definition() {
+        let mut definitions = ObjectDefinitions::new();
+        definitions.define("ball", vec![unit_sphere()]).unwrap();
+        let moved = placed(
+            &definitions,
+            "ball",
+            Transform::translate(&Vector3f::new(10.0, 0.0, 0.0)),
+        )
+        .unwrap();
+        let scaled = placed(&definitions, "ball", Transform::scale(2.0, 2.0, 2.0)).unwrap();
+        assert!(Arc::ptr_eq(&moved.object, &scaled.object));
+
+        let r = ray(Point3f::new(10.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t_hit, si) = moved.intersect(&r).unwrap();
+        assert!((t_hit - 4.0).abs() < 1e-5);
+        assert!((si.p() - Point3f::new(10.0, 0.0, 1.0)).length() < 1e-4);
+        assert!(!scaled.intersect_p(&r));
+        let r = ray(Point3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!((scaled.intersect(&r).unwrap().0 - 3.0).abs() < 1e-5);
+
+        // areas and samples follow the instance transform
+        assert!((scaled.area() - 16.0 * PI).abs() < 1e-3);
+        let (it, pdf) = scaled.sample_area(&Point2f::new(0.3, 0.6));
+        assert!((Vector3f::from(it.p()).length() - 2.0).abs() < 1e-4);
+        assert!((pdf * scaled.area() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn unknown_and_redefined_objects_are_errors() {
+        let mut definitions = ObjectDefinitions::new();
+        definitions.define("ball", vec![unit_sphere()]).unwrap();
+        assert!(definitions.define("ball", vec![unit_sphere()]).is_err());
+        assert!(placed(&definitions, "cube", Transform::default()).is_err());
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::core::{
+    geometry::{normal::Normal3f, point::Point3f, transform::Transform, vector::Vector3f},
+    pbrt::{Float, PI},
+};
+
+use super::triangle::TriangleMesh;
+
+// SDVertex
+// a vertex of the subdivision mesh, faces and vertices refer to each other by
+// their index into the LoopSubdiv arenas
+#[derive(Debug, Default, Copy, Clone)]
+struct SDVertex {
+    p: Point3f,
+    start_face: Option<usize>,
+    child: Option<usize>,
+    regular: bool,
+    boundary: bool,
+}
+
+// SDFace
+// f[i] is the neighbor across the edge from v[i] to v[next(i)], children[i]
+// is the child face at v[i] and children[3] the one in the middle
+#[derive(Debug, Default, Copy, Clone)]
+struct SDFace {
+    v: [usize; 3],
+    f: [Option<usize>; 3],
+    children: [usize; 4],
+}
+
+// the face and vertex walks below can only fail on a malformed cage, which
+// validate_control_mesh rejects before LoopSubdiv is built
+impl SDFace {
+    fn vnum(&self, vert: usize) -> usize {
+        match self.v.iter().position(|&v| v == vert) {
+            Some(i) => i,
+            None => panic!("SDFace::vnum vertex {} is not part of the face", vert),
+        }
+    }
+
+    fn next_face(&self, vert: usize) -> Option<usize> {
+        self.f[self.vnum(vert)]
+    }
+
+    fn prev_face(&self, vert: usize) -> Option<usize> {
+        self.f[prev(self.vnum(vert))]
+    }
+
+    fn next_vert(&self, vert: usize) -> usize {
+        self.v[next(self.vnum(vert))]
+    }
+
+    fn prev_vert(&self, vert: usize) -> usize {
+        self.v[prev(self.vnum(vert))]
+    }
+
+    fn other_vert(&self, v0: usize, v1: usize) -> usize {
+        match self.v.iter().find(|&&v| v != v0 && v != v1) {
+            Some(&v) => v,
+            None => panic!("SDFace::other_vert face is degenerate"),
+        }
+    }
+}
+
+fn next(i: usize) -> usize {
+    (i + 1) % 3
+}
+
+fn prev(i: usize) -> usize {
+    (i + 2) % 3
+}
+
+// an undirected edge, keyed by its vertices in ascending order
+fn sd_edge(v0: usize, v1: usize) -> (usize, usize) {
+    (v0.min(v1), v0.max(v1))
+}
+
+fn beta(valence: usize) -> Float {
+    if valence == 3 {
+        3.0 / 16.0
+    } else {
+        3.0 / (8.0 * valence as Float)
+    }
+}
+
+fn loop_gamma(valence: usize) -> Float {
+    1.0 / (valence as Float + 3.0 / (8.0 * beta(valence)))
+}
+
+// LoopSubdiv
+// all vertices and faces of every subdivision level
+struct LoopSubdiv {
+    vertices: Vec<SDVertex>,
+    faces: Vec<SDFace>,
+}
+
+impl LoopSubdiv {
+    fn valence(&self, vert: usize) -> usize {
+        let start_face = self.vertices[vert].start_face.unwrap();
+        let mut f = Some(start_face);
+        let mut nf = 1;
+        if !self.vertices[vert].boundary {
+            // compute valence of interior vertex
+            loop {
+                f = self.faces[f.unwrap()].next_face(vert);
+                if f == Some(start_face) {
+                    return nf;
+                }
+                nf += 1;
+            }
+        }
+        // compute valence of boundary vertex
+        while let Some(face) = self.faces[f.unwrap()].next_face(vert) {
+            nf += 1;
+            f = Some(face);
+        }
+        f = Some(start_face);
+        while let Some(face) = self.faces[f.unwrap()].prev_face(vert) {
+            nf += 1;
+            f = Some(face);
+        }
+        nf + 1
+    }
+
+    // number of faces reached by walking around vert from its start face
+    fn fan_size(&self, vert: usize) -> usize {
+        let start_face = self.vertices[vert].start_face.unwrap();
+        let mut n_faces = 1;
+        let mut f = self.faces[start_face].next_face(vert);
+        while let Some(face) = f {
+            if face == start_face {
+                return n_faces;
+            }
+            n_faces += 1;
+            f = self.faces[face].next_face(vert);
+        }
+        // boundary vertex, also walk backwards from the start face
+        let mut f = self.faces[start_face].prev_face(vert);
+        while let Some(face) = f {
+            n_faces += 1;
+            f = self.faces[face].prev_face(vert);
+        }
+        n_faces
+    }
+
+    // positions of the neighbors of vert, in order around it and starting at
+    // the boundary for boundary vertices
+    fn one_ring(&self, vert: usize) -> Vec<Point3f> {
+        let start_face = self.vertices[vert].start_face.unwrap();
+        let mut ring = Vec::new();
+        if !self.vertices[vert].boundary {
+            // get one-ring vertices for interior vertex
+            let mut face = start_face;
+            loop {
+                ring.push(self.vertices[self.faces[face].next_vert(vert)].p);
+                face = self.faces[face].next_face(vert).unwrap();
+                if face == start_face {
+                    break;
+                }
+            }
+        } else {
+            // get one-ring vertices for boundary vertex
+            let mut face = start_face;
+            while let Some(f2) = self.faces[face].next_face(vert) {
+                face = f2;
+            }
+            ring.push(self.vertices[self.faces[face].next_vert(vert)].p);
+            let mut f = Some(face);
+            while let Some(face) = f {
+                ring.push(self.vertices[self.faces[face].prev_vert(vert)].p);
+                f = self.faces[face].prev_face(vert);
+            }
+        }
+        ring
+    }
+
+    fn weight_one_ring(&self, vert: usize, beta: Float) -> Point3f {
+        let ring = self.one_ring(vert);
+        let mut p = self.vertices[vert].p * (1.0 - ring.len() as Float * beta);
+        for q in &ring {
+            p += *q * beta;
+        }
+        p
+    }
+
+    fn weight_boundary(&self, vert: usize, beta: Float) -> Point3f {
+        let ring = self.one_ring(vert);
+        let mut p = self.vertices[vert].p * (1.0 - 2.0 * beta);
+        p += ring[0] * beta;
+        p += ring[ring.len() - 1] * beta;
+        p
+    }
+
+    fn new_vertex(&mut self, vertex: SDVertex) -> usize {
+        self.vertices.push(vertex);
+        self.vertices.len() - 1
+    }
+
+    fn new_face(&mut self) -> usize {
+        self.faces.push(SDFace::default());
+        self.faces.len() - 1
+    }
+}
+
+// checks that the control mesh is a consistently oriented manifold with
+// valid indices, which the face and vertex walks of LoopSubdiv rely on
+fn validate_control_mesh(vertex_indices: &[usize], n_vertices: usize) -> Result<(), String> {
+    if !vertex_indices.len().is_multiple_of(3) {
+        return Err(format!(
+            "loop_subdivide indices must come in groups of three, got {}",
+            vertex_indices.len()
+        ));
+    }
+    let mut edge_faces: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut directed_edges = HashSet::new();
+    for (face, vp) in vertex_indices.chunks(3).enumerate() {
+        if let Some(&v) = vp.iter().find(|&&v| v >= n_vertices) {
+            return Err(format!(
+                "loop_subdivide face {} uses vertex {} but there are only {} vertices",
+                face, v, n_vertices
+            ));
+        }
+        if vp[0] == vp[1] || vp[1] == vp[2] || vp[2] == vp[0] {
+            return Err(format!("loop_subdivide face {} is degenerate", face));
+        }
+        for j in 0..3 {
+            let (v0, v1) = (vp[j], vp[next(j)]);
+            let n_faces = edge_faces.entry(sd_edge(v0, v1)).or_insert(0);
+            *n_faces += 1;
+            if *n_faces > 2 {
+                return Err(format!(
+                    "loop_subdivide edge {}-{} is shared by more than two faces",
+                    v0, v1
+                ));
+            }
+            if !directed_edges.insert((v0, v1)) {
+                return Err(format!(
+                    "loop_subdivide faces sharing edge {}-{} are inconsistently oriented",
+                    v0, v1
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// subdivides the control mesh n_levels times and moves the vertices to the
+// limit surface, returns the refined mesh with limit surface normals or an
+// error if the control mesh is malformed
+pub fn loop_subdivide(
+    object_to_world: &Transform,
+    reverse_orientation: bool,
+    n_levels: usize,
+    vertex_indices: &[usize],
+    p: &[Point3f],
+) -> Result<TriangleMesh, String> {
+    validate_control_mesh(vertex_indices, p.len())?;
+    let mut mesh = LoopSubdiv {
+        vertices: p
+            .iter()
+            .map(|p| SDVertex {
+                p: *p,
+                ..Default::default()
+            })
+            .collect(),
+        faces: Vec::with_capacity(vertex_indices.len() / 3),
+    };
+
+    // set face to vertex pointers
+    let mut n_vertex_faces = vec![0; p.len()];
+    for vp in vertex_indices.chunks(3) {
+        let face = mesh.new_face();
+        for (j, &v) in vp.iter().enumerate() {
+            mesh.faces[face].v[j] = v;
+            mesh.vertices[v].start_face = Some(face);
+            n_vertex_faces[v] += 1;
+        }
+    }
+
+    // set neighbor pointers in faces
+    let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    for face in 0..mesh.faces.len() {
+        for edge_num in 0..3 {
+            let v = mesh.faces[face].v;
+            let edge = sd_edge(v[edge_num], v[next(edge_num)]);
+            match edges.remove(&edge) {
+                Some((f0, f0_edge_num)) => {
+                    // handle previously seen edge
+                    mesh.faces[f0].f[f0_edge_num] = Some(face);
+                    mesh.faces[face].f[edge_num] = Some(f0);
+                }
+                None => {
+                    // handle new edge
+                    edges.insert(edge, (face, edge_num));
+                }
+            }
+        }
+    }
+
+    // finish vertex initialization
+    for (vert, &n_faces) in n_vertex_faces.iter().enumerate() {
+        let Some(start_face) = mesh.vertices[vert].start_face else {
+            continue;
+        };
+        let mut f = Some(start_face);
+        loop {
+            f = mesh.faces[f.unwrap()].next_face(vert);
+            if f.is_none() || f == Some(start_face) {
+                break;
+            }
+        }
+        let boundary = f.is_none();
+        mesh.vertices[vert].boundary = boundary;
+        // faces around a vertex must form a single fan
+        if mesh.fan_size(vert) != n_faces {
+            return Err(format!("loop_subdivide vertex {} is non-manifold", vert));
+        }
+        let valence = mesh.valence(vert);
+        mesh.vertices[vert].regular = (!boundary && valence == 6) || (boundary && valence == 4);
+    }
+
+    // refine LoopSubdiv into triangles
+    let mut f: Vec<usize> = (0..mesh.faces.len()).collect();
+    // vertices that are not part of any face are dropped
+    let mut v: Vec<usize> = (0..mesh.vertices.len())
+        .filter(|&vert| mesh.vertices[vert].start_face.is_some())
+        .collect();
+    for _ in 0..n_levels {
+        // update f and v for next level of subdivision
+        let mut new_faces = Vec::with_capacity(4 * f.len());
+        let mut new_vertices = Vec::with_capacity(2 * v.len());
+
+        // allocate next level of children in mesh tree
+        for &vertex in &v {
+            let child = mesh.new_vertex(SDVertex {
+                regular: mesh.vertices[vertex].regular,
+                boundary: mesh.vertices[vertex].boundary,
+                ..Default::default()
+            });
+            mesh.vertices[vertex].child = Some(child);
+            new_vertices.push(child);
+        }
+        for &face in &f {
+            for k in 0..4 {
+                let child = mesh.new_face();
+                mesh.faces[face].children[k] = child;
+                new_faces.push(child);
+            }
+        }
+
+        // update vertex positions for even vertices
+        for &vertex in &v {
+            let p = if !mesh.vertices[vertex].boundary {
+                // apply one-ring rule for even vertex
+                if mesh.vertices[vertex].regular {
+                    mesh.weight_one_ring(vertex, 1.0 / 16.0)
+                } else {
+                    mesh.weight_one_ring(vertex, beta(mesh.valence(vertex)))
+                }
+            } else {
+                // apply boundary rule for even vertex
+                mesh.weight_boundary(vertex, 1.0 / 8.0)
+            };
+            let child = mesh.vertices[vertex].child.unwrap();
+            mesh.vertices[child].p = p;
+        }
+
+        // compute new odd edge vertices
+        let mut edge_verts: HashMap<(usize, usize), usize> = HashMap::new();
+        for &face in &f {
+            for k in 0..3 {
+                // compute odd vertex on kth edge
+                let SDFace { v: fv, f: ff, .. } = mesh.faces[face];
+                let edge = sd_edge(fv[k], fv[next(k)]);
+                if edge_verts.contains_key(&edge) {
+                    continue;
+                }
+                // apply edge rules to compute new vertex position
+                let (p0, p1) = (mesh.vertices[edge.0].p, mesh.vertices[edge.1].p);
+                let p = match ff[k] {
+                    None => p0 * 0.5 + p1 * 0.5,
+                    Some(neighbor) => {
+                        let p2 = mesh.vertices[mesh.faces[face].other_vert(edge.0, edge.1)].p;
+                        let p3 = mesh.vertices[mesh.faces[neighbor].other_vert(edge.0, edge.1)].p;
+                        p0 * (3.0 / 8.0) + p1 * (3.0 / 8.0) + p2 * (1.0 / 8.0) + p3 * (1.0 / 8.0)
+                    }
+                };
+                let vert = mesh.new_vertex(SDVertex {
+                    p,
+                    start_face: Some(mesh.faces[face].children[3]),
+                    child: None,
+                    regular: true,
+                    boundary: ff[k].is_none(),
+                });
+                new_vertices.push(vert);
+                edge_verts.insert(edge, vert);
+            }
+        }
+
+        // update even vertex face pointers
+        for &vertex in &v {
+            let start_face = mesh.vertices[vertex].start_face.unwrap();
+            let vert_num = mesh.faces[start_face].vnum(vertex);
+            let child = mesh.vertices[vertex].child.unwrap();
+            mesh.vertices[child].start_face = Some(mesh.faces[start_face].children[vert_num]);
+        }
+
+        // update face neighbor pointers
+        for &face in &f {
+            let SDFace {
+                v: fv,
+                f: ff,
+                children,
+            } = mesh.faces[face];
+            for j in 0..3 {
+                // update children f pointers for siblings
+                mesh.faces[children[3]].f[j] = Some(children[next(j)]);
+                mesh.faces[children[j]].f[next(j)] = Some(children[3]);
+
+                // update children f pointers for neighbor children
+                mesh.faces[children[j]].f[j] = ff[j].map(|f2| {
+                    let f2 = &mesh.faces[f2];
+                    f2.children[f2.vnum(fv[j])]
+                });
+                mesh.faces[children[j]].f[prev(j)] = ff[prev(j)].map(|f2| {
+                    let f2 = &mesh.faces[f2];
+                    f2.children[f2.vnum(fv[j])]
+                });
+            }
+        }
+
+        // update face vertex pointers
+        for &face in &f {
+            let SDFace {
+                v: fv, children, ..
+            } = mesh.faces[face];
+            for j in 0..3 {
+                // update child vertex pointer to new even vertex
+                mesh.faces[children[j]].v[j] = mesh.vertices[fv[j]].child.unwrap();
+
+                // update child vertex pointer to new odd vertex
+                let vert = edge_verts[&sd_edge(fv[j], fv[next(j)])];
+                mesh.faces[children[j]].v[next(j)] = vert;
+                mesh.faces[children[next(j)]].v[j] = vert;
+                mesh.faces[children[3]].v[j] = vert;
+            }
+        }
+
+        // prepare for next level of subdivision
+        f = new_faces;
+        v = new_vertices;
+    }
+
+    // push vertices to limit surface
+    let p_limit: Vec<Point3f> = v
+        .iter()
+        .map(|&vert| {
+            if mesh.vertices[vert].boundary {
+                mesh.weight_boundary(vert, 1.0 / 5.0)
+            } else {
+                mesh.weight_one_ring(vert, loop_gamma(mesh.valence(vert)))
+            }
+        })
+        .collect();
+    for (&vert, p) in v.iter().zip(&p_limit) {
+        mesh.vertices[vert].p = *p;
+    }
+
+    // compute vertex tangents on limit surface
+    let ns: Vec<Normal3f> = v
+        .iter()
+        .map(|&vert| {
+            let ring = mesh.one_ring(vert);
+            let valence = ring.len();
+            let p = Vector3f::from(mesh.vertices[vert].p);
+            let mut s = Vector3f::default();
+            let mut t = Vector3f::default();
+            if !mesh.vertices[vert].boundary {
+                // compute tangents of interior face
+                for (j, q) in ring.iter().enumerate() {
+                    let angle = 2.0 * PI * j as Float / valence as Float;
+                    s += Vector3f::from(*q) * angle.cos();
+                    t += Vector3f::from(*q) * angle.sin();
+                }
+            } else {
+                // compute tangents of boundary face
+                s = ring[valence - 1] - ring[0];
+                let ring: Vec<Vector3f> = ring.iter().map(|q| Vector3f::from(*q)).collect();
+                t = match valence {
+                    2 => ring[0] + ring[1] - p * 2.0,
+                    3 => ring[1] - p,
+                    // regular
+                    4 => ring[0] * -1.0 + ring[1] * 2.0 + ring[2] * 2.0 + ring[3] * -1.0 - p * 2.0,
+                    _ => {
+                        let theta = PI / (valence - 1) as Float;
+                        let mut t = (ring[0] + ring[valence - 1]) * theta.sin();
+                        for (k, q) in ring.iter().enumerate().take(valence - 1).skip(1) {
+                            let wt = (2.0 * theta.cos() - 2.0) * (k as Float * theta).sin();
+                            t += *q * wt;
+                        }
+                        -t
+                    }
+                };
+            }
+            // t x s so the normal agrees with counter-clockwise face winding
+            Normal3f::from(t.cross(&s))
+        })
+        .collect();
+
+    // create triangle mesh from subdivision mesh
+    let mut used_verts = HashMap::with_capacity(v.len());
+    for (i, &vert) in v.iter().enumerate() {
+        used_verts.insert(vert, i);
+    }
+    let indices = f
+        .iter()
+        .flat_map(|&face| mesh.faces[face].v.map(|vert| used_verts[&vert]))
+        .collect();
+    TriangleMesh::new(
+        object_to_world,
+        reverse_orientation,
+        indices,
+        p_limit,
+        Some(ns),
+        None,
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tetrahedron() -> (Vec<usize>, Vec<Point3f>) {
+        (
+            vec![0, 1, 2, 0, 2, 3, 0, 3, 1, 1, 3, 2],
+            vec![
+                Point3f::new(1.0, 1.0, 1.0),
+                Point3f::new(1.0, -1.0, -1.0),
+                Point3f::new(-1.0, 1.0, -1.0),
+                Point3f::new(-1.0, -1.0, 1.0),
+            ],
+        )
+    }
+
+    #[test]
+    fn subdivided_tetrahedron_shrinks_with_outward_normals() {
+        let (indices, p) = tetrahedron();
+        let mesh = loop_subdivide(&Transform::default(), false, 2, &indices, &p).unwrap();
+        // every level splits each face in four and adds a vertex per edge
+        assert_eq!(mesh.n_triangles, 4 * 16);
+        assert_eq!(mesh.p.len(), 4 + 6 + 24);
+        let n = mesh.n.as_ref().unwrap();
+        for (p, n) in mesh.p.iter().zip(n) {
+            assert!(Vector3f::from(*p).length() < 3.0_f64.sqrt() as Float);
+            assert!(Vector3f::from(*p).dot(&Vector3f::from(*n)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn malformed_cages_are_rejected() {
+        let (indices, p) = tetrahedron();
+        let subdivide =
+            |indices: &[usize]| loop_subdivide(&Transform::default(), false, 1, indices, &p);
+        assert!(subdivide(&indices[..10]).is_err());
+        assert!(subdivide(&[0, 1, 4]).is_err());
+        assert!(subdivide(&[0, 1, 1]).is_err());
+        // the second face repeats the first one's directed edge 0-1
+        assert!(subdivide(&[0, 1, 2, 0, 1, 3]).is_err());
+        assert!(subdivide(&[0, 1, 2, 1, 0, 3, 0, 1, 3]).is_err());
+        // a single open triangle is a valid boundary mesh
+        assert!(subdivide(&[0, 1, 2]).is_ok());
+    }
+}
@@ -0,0 +1,14 @@
+pub mod alpha_mask;
+pub mod bilinear_patch;
+pub mod cone;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod heightfield;
+pub mod hyperboloid;
+pub mod instance;
+pub mod loop_subdiv;
+pub mod paraboloid;
+pub mod sdf;
+pub mod sphere;
+pub mod triangle;
@@ -0,0 +1,289 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{Vector3f, Vector3fi},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{clamp, gamma, lerp, quadratic_interval, radians, Float, PI},
+    shape::{quadric_sample, weingarten, QuadricIntersection, Shape},
+};
+
+// Paraboloid
+// z = z_max (x^2 + y^2) / radius^2 between z_min and z_max, optionally cut
+// off beyond phi_max
+pub struct Paraboloid {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub radius: Float,
+    pub z_min: Float,
+    pub z_max: Float,
+    pub phi_max: Float,
+}
+
+impl Paraboloid {
+    // phi_max is in degrees
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+        phi_max: Float,
+    ) -> Paraboloid {
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Paraboloid {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            radius,
+            z_min: z_min.min(z_max),
+            z_max: z_min.max(z_max),
+            phi_max: radians(clamp(phi_max, 0.0, 360.0)),
+        }
+    }
+
+    // phi of the hit point, or None if it lies in a part of the paraboloid
+    // that was cut away
+    fn unclipped_phi(&self, p_hit: &Point3f) -> Option<Float> {
+        let mut phi = p_hit.y.atan2(p_hit.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        if p_hit.z < self.z_min || p_hit.z > self.z_max || phi > self.phi_max {
+            None
+        } else {
+            Some(phi)
+        }
+    }
+
+    fn basic_intersect(&self, r: &Ray) -> Option<QuadricIntersection> {
+        let t_max = r.t_max.get();
+        // transform ray origin and direction to object space
+        let oi = self
+            .world_to_object
+            .transform_point3fi(&Point3fi::from(r.o));
+        let di = self
+            .world_to_object
+            .transform_vector3fi(&Vector3fi::from(r.d));
+
+        // compute quadratic paraboloid coefficients
+        let k = self.z_max / (self.radius * self.radius);
+        let a = k * (di.x.sqr() + di.y.sqr());
+        let b = 2.0 * k * (di.x * oi.x + di.y * oi.y) - di.z;
+        let c = k * (oi.x.sqr() + oi.y.sqr()) - oi.z;
+
+        // solve quadratic equation for t values
+        let (t0, t1) = quadratic_interval(a, b, c)?;
+
+        // check quadric shape t0 and t1 for nearest intersection
+        if t0.upper_bound() > t_max || t1.lower_bound() <= 0.0 {
+            return None;
+        }
+        let mut t_shape_hit = t0;
+        if t_shape_hit.lower_bound() <= 0.0 {
+            t_shape_hit = t1;
+            if t_shape_hit.upper_bound() > t_max {
+                return None;
+            }
+        }
+        let mut pi = oi + di * t_shape_hit;
+        let mut phi = self.unclipped_phi(&pi.midpoint());
+
+        // test paraboloid intersection against clipping parameters
+        if phi.is_none() {
+            if t_shape_hit == t1 || t1.upper_bound() > t_max {
+                return None;
+            }
+            t_shape_hit = t1;
+            pi = oi + di * t_shape_hit;
+            phi = self.unclipped_phi(&pi.midpoint());
+        }
+        Some(QuadricIntersection {
+            t_hit: Float::from(t_shape_hit),
+            p_obj: pi.midpoint(),
+            p_error: pi.error(),
+            phi: phi?,
+        })
+    }
+
+    fn interaction_from_intersection(
+        &self,
+        isect: &QuadricIntersection,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction {
+        let p_hit = isect.p_obj;
+        // find parametric representation of paraboloid hit
+        let u = isect.phi / self.phi_max;
+        let v = (p_hit.z - self.z_min) / (self.z_max - self.z_min);
+
+        // compute paraboloid dpdu and dpdv
+        let z_range = self.z_max - self.z_min;
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let dpdv =
+            Vector3f::new(p_hit.x / (2.0 * p_hit.z), p_hit.y / (2.0 * p_hit.z), 1.0) * z_range;
+
+        // compute paraboloid dndu and dndv
+        let d2pduu = Vector3f::new(p_hit.x, p_hit.y, 0.0) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::new(-p_hit.y / (2.0 * p_hit.z), p_hit.x / (2.0 * p_hit.z), 0.0)
+            * (z_range * self.phi_max);
+        let d2pdvv = Vector3f::new(
+            p_hit.x / (4.0 * p_hit.z * p_hit.z),
+            p_hit.y / (4.0 * p_hit.z * p_hit.z),
+            0.0,
+        ) * (-z_range * z_range);
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &d2pduv, &d2pdvv);
+
+        // return SurfaceInteraction for quadric intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let wo_object = self.world_to_object.transform_vector3f(wo);
+        self.object_to_world
+            .transform_surface_interaction(&SurfaceInteraction::new(
+                Point3fi::new_with_error(&p_hit, &isect.p_error),
+                Point2f::new(u, v),
+                wo_object,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+                time,
+                flip_normal,
+            ))
+    }
+}
+
+impl Shape for Paraboloid {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(-self.radius, -self.radius, self.z_min),
+            Point3f::new(self.radius, self.radius, self.z_max),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let isect = self.basic_intersect(ray)?;
+        let si = self.interaction_from_intersection(&isect, &-ray.d, ray.time);
+        Some((isect.t_hit, si))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.basic_intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        let radius2 = self.radius * self.radius;
+        let k = 4.0 * self.z_max / radius2;
+        (radius2 * radius2 * self.phi_max / (12.0 * self.z_max * self.z_max))
+            * ((k * self.z_max + 1.0).powf(1.5) - (k * self.z_min + 1.0).powf(1.5))
+    }
+
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        // the area below z grows with (k z + 1)^1.5, so inverting it picks z
+        // with the density of the surface and (phi, z) is uniform by area
+        let k = 4.0 * self.z_max / (self.radius * self.radius);
+        let s = lerp(
+            u.x,
+            (k * self.z_min + 1.0).powf(1.5),
+            (k * self.z_max + 1.0).powf(1.5),
+        );
+        let z = (s.powf(2.0 / 3.0) - 1.0) / k;
+        let r = self.radius * (z / self.z_max).max(0.0).sqrt();
+        let phi = u.y * self.phi_max;
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        // compute paraboloid sample position and its error, the normal is
+        // dpdu x dpdv as for intersections
+        let p_obj = Point3f::new(r * cos_phi, r * sin_phi, z);
+        let p_error = Vector3f::from(p_obj.abs()) * gamma(5);
+        let n = Normal3f::new(
+            p_obj.x,
+            p_obj.y,
+            -self.radius * self.radius / (2.0 * self.z_max),
+        )
+        .normalize();
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+        (it, 1.0 / self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hits_the_bowl_at_its_height() {
+        let paraboloid = Paraboloid::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            1.0,
+            0.0,
+            1.0,
+            360.0,
+        );
+        // z = x^2 + y^2 for radius 1 and z_max 1
+        let r = ray(Point3f::new(0.5, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t_hit, si) = paraboloid.intersect(&r).unwrap();
+        assert!((t_hit - 4.75).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(0.5, 0.0, 0.25)).length() < 1e-6);
+        let r = ray(Point3f::new(1.5, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!(!paraboloid.intersect_p(&r));
+        // (pi / 6) (5^1.5 - 1)
+        let area = PI / 6.0 * ((5.0 as Float).powf(1.5) - 1.0);
+        assert!((paraboloid.area() - area).abs() < 1e-5);
+    }
+
+    #[test]
+    fn samples_are_uniform_by_area() {
+        let paraboloid = Paraboloid::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            1.0,
+            0.0,
+            1.0,
+            360.0,
+        );
+        // the area below z is proportional to (4 z + 1)^1.5 - 1, so this u
+        // splits the area at z = 0.5
+        let three: Float = 3.0;
+        let five: Float = 5.0;
+        let ux = (three.powf(1.5) - 1.0) / (five.powf(1.5) - 1.0);
+        let (it, pdf) = paraboloid.sample_area(&Point2f::new(ux, 0.3));
+        let p = it.p();
+        assert!((p.z - 0.5).abs() < 1e-5);
+        assert!((p.x * p.x + p.y * p.y - p.z).abs() < 1e-5);
+        assert!((pdf * paraboloid.area() - 1.0).abs() < 1e-6);
+        // the normal faces like the normal of a hit at the same point
+        let r = ray(Point3f::new(p.x, p.y, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (_, si) = paraboloid.intersect(&r).unwrap();
+        assert!(si.n().dot(&it.n) > 0.999);
+    }
+}
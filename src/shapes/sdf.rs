@@ -0,0 +1,317 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        transform::Transform,
+        vector::{coordinate_system, Vector3f},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    pbrt::{hash_float, Float},
+    shape::{quadric_sample, Shape},
+};
+
+// DistanceFunction
+// signed distance from p to the surface in object space, negative inside. It
+// may underestimate but must never overestimate the true distance, otherwise
+// sphere tracing can step through the surface
+pub trait DistanceFunction: Send + Sync {
+    fn distance(&self, p: &Point3f) -> Float;
+}
+
+impl<F> DistanceFunction for F
+where
+    F: Fn(&Point3f) -> Float + Send + Sync,
+{
+    fn distance(&self, p: &Point3f) -> Float {
+        self(p)
+    }
+}
+
+// SdfShape
+// the zero set of a distance function inside a conservative object space
+// bound, intersected by sphere tracing. The surface has no parameterization,
+// so (u,v) is always zero and dpdu and dpdv are an arbitrary tangent frame
+pub struct SdfShape {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub distance: Arc<dyn DistanceFunction>,
+    pub bounds: Bounds3f,
+    // points closer than epsilon to the surface count as hits, it is also the
+    // finite difference step for normals
+    pub epsilon: Float,
+    pub max_steps: usize,
+    // approximate area from estimate_area, only computed the first time area
+    // is called since it evaluates the distance function 64^3 times
+    pub area: OnceLock<Float>,
+}
+
+impl SdfShape {
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        distance: Arc<dyn DistanceFunction>,
+        bounds: Bounds3f,
+        epsilon: Float,
+    ) -> Result<SdfShape, String> {
+        if epsilon.is_nan() || epsilon <= 0.0 {
+            return Err(format!(
+                "SdfShape epsilon must be positive, got {}",
+                epsilon
+            ));
+        }
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        Ok(SdfShape {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            distance,
+            bounds,
+            epsilon,
+            max_steps: 512,
+            area: OnceLock::new(),
+        })
+    }
+
+    // sphere traces the object space ray through the bounds and returns the
+    // parametric distance of the first point within epsilon of the surface
+    fn sphere_trace(&self, r: &Ray) -> Option<Float> {
+        let mut t0 = 0.0;
+        let mut t1 = 0.0;
+        if !self.bounds.intersect_b(r, &mut t0, &mut t1) {
+            return None;
+        }
+        let inv_ray_length = 1.0 / r.d.length();
+        let mut t = t0;
+        for _ in 0..self.max_steps {
+            // rays leaving the inside of the surface march on |distance| too
+            let distance = self.distance.distance(&r.position(t)).abs();
+            if distance < self.epsilon {
+                return Some(t);
+            }
+            t += distance * inv_ray_length;
+            if t > t1 {
+                return None;
+            }
+        }
+        None
+    }
+
+    // gradient of the distance function by central differences
+    fn normal(&self, p: &Point3f) -> Normal3f {
+        let h = self.epsilon;
+        let f = |dx: Float, dy: Float, dz: Float| {
+            self.distance
+                .distance(&Point3f::new(p.x + dx, p.y + dy, p.z + dz))
+        };
+        let n = Vector3f::new(
+            f(h, 0.0, 0.0) - f(-h, 0.0, 0.0),
+            f(0.0, h, 0.0) - f(0.0, -h, 0.0),
+            f(0.0, 0.0, h) - f(0.0, 0.0, -h),
+        );
+        if n.length_squared() == 0.0 {
+            // flat region of the distance function, any normal will do
+            Normal3f::new(0.0, 0.0, 1.0)
+        } else {
+            Normal3f::from(n.normalize())
+        }
+    }
+}
+
+impl Shape for SdfShape {
+    fn object_bound(&self) -> Bounds3f {
+        self.bounds
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        // transform ray to object space
+        let r = self.world_to_object.transform_ray(ray);
+        let t_hit = self.sphere_trace(&r)?;
+        let p_hit = r.position(t_hit);
+
+        // the hit point is only known to within epsilon of the surface, so
+        // spawned rays are offset past that band
+        let p_error = Vector3f::new(self.epsilon, self.epsilon, self.epsilon) * 2.0;
+
+        // build a tangent frame around the finite difference normal
+        let n = self.normal(&p_hit);
+        let (dpdu, dpdv) = coordinate_system(&Vector3f::from(n));
+
+        // return SurfaceInteraction for distance field hit
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let isect = SurfaceInteraction::new(
+            Point3fi::new_with_error(&p_hit, &p_error),
+            Point2f::default(),
+            -r.d,
+            dpdu,
+            dpdv,
+            Normal3f::default(),
+            Normal3f::default(),
+            ray.time,
+            flip_normal,
+        );
+        Some((
+            t_hit,
+            self.object_to_world.transform_surface_interaction(&isect),
+        ))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        let r = self.world_to_object.transform_ray(ray);
+        self.sphere_trace(&r).is_some()
+    }
+
+    // an approximation, see estimate_area
+    fn area(&self) -> Float {
+        *self
+            .area
+            .get_or_init(|| estimate_area(self.distance.as_ref(), &self.bounds))
+    }
+
+    // rejection samples points of the bounds until one lies in the band
+    // around the surface that estimate_area measures, then projects it onto
+    // the surface, the candidates are hashed from u. Returns a zero pdf if
+    // no candidate is accepted
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        const MAX_CANDIDATES: usize = 4096;
+        let h = band_half_width(&self.bounds);
+        for i in 0..MAX_CANDIDATES {
+            let candidate = |axis: usize| hash_float(&[u.x, u.y, i as Float, axis as Float]);
+            let p = self
+                .bounds
+                .lerp(&Point3f::new(candidate(0), candidate(1), candidate(2)));
+            let distance = self.distance.distance(&p);
+            if distance.abs() >= h {
+                continue;
+            }
+            let n = self.normal(&p);
+            let p_obj = p - Vector3f::from(n) * distance;
+            let p_error = Vector3f::new(self.epsilon, self.epsilon, self.epsilon) * 2.0;
+            let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+            let it = quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal);
+            return (it, 1.0 / self.area());
+        }
+        (Interaction::default(), 0.0)
+    }
+}
+
+// grid resolution of estimate_area along each axis
+const AREA_GRID_RES: usize = 64;
+
+// half width of the band around the surface that estimate_area counts, a
+// grid cell along the longest axis of bounds
+fn band_half_width(bounds: &Bounds3f) -> Float {
+    (bounds.diagonal() / (AREA_GRID_RES - 4) as Float).max_component()
+}
+
+// approximates the surface area inside bounds as the volume of the band where
+// |distance| < h divided by its width 2h, sampled on a regular grid. The grid
+// covers the bounds grown by the band so surfaces lying on them count fully
+fn estimate_area(distance: &dyn DistanceFunction, bounds: &Bounds3f) -> Float {
+    const N: usize = AREA_GRID_RES;
+    let h = band_half_width(bounds);
+    if h == 0.0 {
+        return 0.0;
+    }
+    let grid = bounds.expand(2.0 * h);
+    let cell = grid.diagonal() / N as Float;
+    let mut n_band = 0;
+    for z in 0..N {
+        for y in 0..N {
+            for x in 0..N {
+                let p = grid.lerp(&Point3f::new(
+                    (x as Float + 0.5) / N as Float,
+                    (y as Float + 0.5) / N as Float,
+                    (z as Float + 0.5) / N as Float,
+                ));
+                if distance.distance(&p).abs() < h {
+                    n_band += 1;
+                }
+            }
+        }
+    }
+    n_band as Float * cell.x * cell.y * cell.z / (2.0 * h)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::core::pbrt::PI;
+
+    use super::*;
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    fn unit_sphere(epsilon: Float) -> Result<SdfShape, String> {
+        SdfShape::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            Arc::new(|p: &Point3f| Vector3f::from(*p).length() - 1.0),
+            Bounds3f::new_with_points(Point3f::new(-1.0, -1.0, -1.0), Point3f::new(1.0, 1.0, 1.0)),
+            epsilon,
+        )
+    }
+
+    #[test]
+    fn sphere_tracing_finds_the_unit_sphere() {
+        let sdf = unit_sphere(1e-4).unwrap();
+        let r = ray(Point3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -2.0));
+        let (t_hit, si) = sdf.intersect(&r).unwrap();
+        assert!((t_hit - 2.0).abs() < 1e-3);
+        assert!((Vector3f::from(si.n()) - Vector3f::new(0.0, 0.0, 1.0)).length() < 1e-3);
+        let miss = ray(Point3f::new(1.5, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!(!sdf.intersect_p(&miss));
+        assert!((sdf.area() - 4.0 * PI).abs() < 0.05 * 4.0 * PI);
+    }
+
+    #[test]
+    fn epsilon_must_be_positive() {
+        assert!(unit_sphere(0.0).is_err());
+        assert!(unit_sphere(-1e-3).is_err());
+        assert!(unit_sphere(Float::NAN).is_err());
+    }
+
+    #[test]
+    fn samples_are_projected_onto_the_surface() {
+        let sdf = unit_sphere(1e-4).unwrap();
+        for u in [Point2f::new(0.1, 0.2), Point2f::new(0.7, 0.4)] {
+            let (it, pdf) = sdf.sample_area(&u);
+            assert!(pdf > 0.0);
+            assert!((Vector3f::from(it.p()).length() - 1.0).abs() < 1e-3);
+            assert!(Vector3f::from(it.p()).dot(&Vector3f::from(it.n)) > 0.99);
+        }
+        // a distance function without a zero set in the bounds has nothing
+        // to sample
+        let empty = SdfShape::new(
+            Arc::new(Transform::default()),
+            Arc::new(Transform::default()),
+            false,
+            Arc::new(|_: &Point3f| 1.0),
+            Bounds3f::new_with_points(Point3f::new(-1.0, -1.0, -1.0), Point3f::new(1.0, 1.0, 1.0)),
+            1e-4,
+        )
+        .unwrap();
+        assert_eq!(empty.sample_area(&Point2f::new(0.5, 0.5)).1, 0.0);
+    }
+}
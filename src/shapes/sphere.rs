@@ -0,0 +1,418 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        frame::Frame,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        spherical::spherical_direction,
+        transform::Transform,
+        vector::{Vector3f, Vector3fi},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    interval::Interval,
+    pbrt::{clamp, gamma, lerp, radians, safe_acos, safe_sqrt, Float, PI},
+    shape::{
+        pdf_ref_by_area, quadric_sample, sample_ref_by_area, weingarten, QuadricIntersection, Shape,
+    },
+};
+
+// sin^2 of 1.5 degrees, below which cones are sampled by Taylor expansion
+// since 1 - cos theta_max loses too much precision
+const SMALL_CONE_SIN2_THETA_MAX: Float = 0.00068523;
+
+// Sphere
+// centered at the object space origin, optionally cut off below z_min, above
+// z_max and beyond phi_max around the z axis
+pub struct Sphere {
+    pub object_to_world: Arc<Transform>,
+    pub world_to_object: Arc<Transform>,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub radius: Float,
+    pub z_min: Float,
+    pub z_max: Float,
+    pub theta_z_min: Float,
+    pub theta_z_max: Float,
+    pub phi_max: Float,
+}
+
+impl Sphere {
+    // z_min and z_max are clamped to the sphere, phi_max is in degrees
+    pub fn new(
+        object_to_world: Arc<Transform>,
+        world_to_object: Arc<Transform>,
+        reverse_orientation: bool,
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+        phi_max: Float,
+    ) -> Sphere {
+        let transform_swaps_handedness = object_to_world.swaps_handedness();
+        let (z0, z1) = (z_min.min(z_max), z_min.max(z_max));
+        Sphere {
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness,
+            radius,
+            z_min: clamp(z0, -radius, radius),
+            z_max: clamp(z1, -radius, radius),
+            theta_z_min: clamp(z0 / radius, -1.0, 1.0).acos(),
+            theta_z_max: clamp(z1 / radius, -1.0, 1.0).acos(),
+            phi_max: radians(clamp(phi_max, 0.0, 360.0)),
+        }
+    }
+
+    // true if the hit point lies in a part of the sphere that was cut away
+    fn is_clipped(&self, p_hit: &Point3f, phi: Float) -> bool {
+        (self.z_min > -self.radius && p_hit.z < self.z_min)
+            || (self.z_max < self.radius && p_hit.z > self.z_max)
+            || phi > self.phi_max
+    }
+
+    // refine the sphere intersection point and compute its phi
+    fn hit_point(&self, oi: &Point3fi, di: &Vector3fi, t: Float) -> (Point3f, Float) {
+        let mut p_hit = oi.midpoint() + di.midpoint() * t;
+        p_hit *= self.radius / Vector3f::from(p_hit).length();
+        if p_hit.x == 0.0 && p_hit.y == 0.0 {
+            p_hit.x = 1e-5 * self.radius;
+        }
+        let mut phi = p_hit.y.atan2(p_hit.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        (p_hit, phi)
+    }
+
+    fn basic_intersect(&self, r: &Ray) -> Option<QuadricIntersection> {
+        let t_max = r.t_max.get();
+        // transform ray origin and direction to object space
+        let oi = self
+            .world_to_object
+            .transform_point3fi(&Point3fi::from(r.o));
+        let di = self
+            .world_to_object
+            .transform_vector3fi(&Vector3fi::from(r.d));
+
+        // solve quadratic equation to compute sphere t0 and t1
+        let a = di.length_squared();
+        let b = 2.0 * (di.x * oi.x + di.y * oi.y + di.z * oi.z);
+        let c = oi.x.sqr() + oi.y.sqr() + oi.z.sqr() - Interval::from(self.radius).sqr();
+        // compute the discriminant from the distance between the sphere center
+        // and the closest point on the ray, which is more accurate than b^2 - 4ac
+        let v = Vector3fi::new(oi.x, oi.y, oi.z) - di * (b / (2.0 * a));
+        let length = v.length();
+        let discrim = 4.0
+            * a
+            * (Interval::from(self.radius) + length)
+            * (Interval::from(self.radius) - length);
+        if discrim.lower_bound() < 0.0 {
+            return None;
+        }
+        let root_discrim = discrim.sqrt();
+        let q = if Float::from(b) < 0.0 {
+            -0.5 * (b - root_discrim)
+        } else {
+            -0.5 * (b + root_discrim)
+        };
+        let mut t0 = q / a;
+        let mut t1 = c / q;
+        if t0.lower_bound() > t1.lower_bound() {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+
+        // check quadric shape t0 and t1 for nearest intersection
+        if t0.upper_bound() > t_max || t1.lower_bound() <= 0.0 {
+            return None;
+        }
+        let mut t_shape_hit = t0;
+        if t_shape_hit.lower_bound() <= 0.0 {
+            t_shape_hit = t1;
+            if t_shape_hit.upper_bound() > t_max {
+                return None;
+            }
+        }
+        let (mut p_hit, mut phi) = self.hit_point(&oi, &di, Float::from(t_shape_hit));
+
+        // test sphere intersection against clipping parameters
+        if self.is_clipped(&p_hit, phi) {
+            if t_shape_hit == t1 || t1.upper_bound() > t_max {
+                return None;
+            }
+            t_shape_hit = t1;
+            (p_hit, phi) = self.hit_point(&oi, &di, Float::from(t_shape_hit));
+            if self.is_clipped(&p_hit, phi) {
+                return None;
+            }
+        }
+        Some(QuadricIntersection {
+            t_hit: Float::from(t_shape_hit),
+            p_obj: p_hit,
+            // the error of the reprojected point is independent of the ray
+            p_error: Vector3f::from(p_hit.abs()) * gamma(5),
+            phi,
+        })
+    }
+
+    // world space center and sin^2 of the half angle of the cone of
+    // directions the sphere subtends from ref_it, if the sphere is sampled by
+    // solid angle from there. That needs a full sphere with ref_it outside
+    fn sampling_cone(&self, ref_it: &Interaction) -> Option<(Point3f, Float)> {
+        let full =
+            self.z_min <= -self.radius && self.z_max >= self.radius && self.phi_max >= 2.0 * PI;
+        if !full {
+            return None;
+        }
+        let p_center = self.object_to_world.transform_point3f(&Point3f::default());
+        let p_origin = ref_it.offset_ray_origin(&(p_center - ref_it.p()));
+        if p_origin.distance_squared(&p_center) <= self.radius * self.radius {
+            return None;
+        }
+        let sin2_theta_max = self.radius * self.radius / ref_it.p().distance_squared(&p_center);
+        Some((p_center, sin2_theta_max))
+    }
+
+    // Interaction for the point p_obj on the sphere in object space
+    fn sample_interaction(&self, p_obj: &Point3f) -> Interaction {
+        // reproject p_obj to sphere surface and compute its error
+        let p_obj = *p_obj * (self.radius / Vector3f::from(*p_obj).length());
+        let p_error = Vector3f::from(p_obj.abs()) * gamma(5);
+        let n = Normal3f::from(Vector3f::from(p_obj)) / self.radius;
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        quadric_sample(&self.object_to_world, &p_obj, &p_error, &n, flip_normal)
+    }
+
+    fn interaction_from_intersection(
+        &self,
+        isect: &QuadricIntersection,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction {
+        let p_hit = isect.p_obj;
+        let phi = isect.phi;
+        // find parametric representation of sphere hit
+        let u = phi / self.phi_max;
+        let cos_theta = p_hit.z / self.radius;
+        let theta = safe_acos(cos_theta);
+        let v = (theta - self.theta_z_min) / (self.theta_z_max - self.theta_z_min);
+
+        // compute sphere dpdu and dpdv
+        let z_radius = (p_hit.x * p_hit.x + p_hit.y * p_hit.y).sqrt();
+        let cos_phi = p_hit.x / z_radius;
+        let sin_phi = p_hit.y / z_radius;
+        let dpdu = Vector3f::new(-self.phi_max * p_hit.y, self.phi_max * p_hit.x, 0.0);
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let theta_range = self.theta_z_max - self.theta_z_min;
+        let dpdv = Vector3f::new(
+            p_hit.z * cos_phi,
+            p_hit.z * sin_phi,
+            -self.radius * sin_theta,
+        ) * theta_range;
+
+        // compute sphere dndu and dndv
+        let d2pduu = Vector3f::new(p_hit.x, p_hit.y, 0.0) * (-self.phi_max * self.phi_max);
+        let d2pduv = Vector3f::new(-sin_phi, cos_phi, 0.0) * (theta_range * p_hit.z * self.phi_max);
+        let d2pdvv = Vector3f::from(p_hit) * (-theta_range * theta_range);
+        let (dndu, dndv) = weingarten(&dpdu, &dpdv, &d2pduu, &d2pduv, &d2pdvv);
+
+        // return SurfaceInteraction for quadric intersection
+        let flip_normal = self.reverse_orientation ^ self.transform_swaps_handedness;
+        let wo_object = self.world_to_object.transform_vector3f(wo);
+        self.object_to_world
+            .transform_surface_interaction(&SurfaceInteraction::new(
+                Point3fi::new_with_error(&p_hit, &isect.p_error),
+                Point2f::new(u, v),
+                wo_object,
+                dpdu,
+                dpdv,
+                dndu,
+                dndv,
+                time,
+                flip_normal,
+            ))
+    }
+}
+
+impl Shape for Sphere {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new_with_points(
+            Point3f::new(-self.radius, -self.radius, self.z_min),
+            Point3f::new(self.radius, self.radius, self.z_max),
+        )
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        self.object_to_world
+            .transform_bounds3f(&self.object_bound())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let isect = self.basic_intersect(ray)?;
+        let si = self.interaction_from_intersection(&isect, &-ray.d, ray.time);
+        Some((isect.t_hit, si))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.basic_intersect(ray).is_some()
+    }
+
+    fn area(&self) -> Float {
+        self.phi_max * self.radius * (self.z_max - self.z_min)
+    }
+
+    // uniform in z and phi, which is uniform over the area of a sphere zone
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let z = lerp(u.x, self.z_min, self.z_max);
+        let phi = u.y * self.phi_max;
+        let z_radius = safe_sqrt(self.radius * self.radius - z * z);
+        let p_obj = Point3f::new(z_radius * phi.cos(), z_radius * phi.sin(), z);
+        (self.sample_interaction(&p_obj), 1.0 / self.area())
+    }
+
+    // samples the cone of directions the sphere subtends from ref_it
+    // uniformly, and the sphere by area if that cone is not available
+    fn sample_ref(&self, ref_it: &Interaction, u: &Point2f) -> Option<(Interaction, Float)> {
+        let Some((p_center, sin2_theta_max)) = self.sampling_cone(ref_it) else {
+            return sample_ref_by_area(self, ref_it, u);
+        };
+
+        // compute theta and phi values for sample in cone
+        let sin_theta_max = sin2_theta_max.sqrt();
+        let cos_theta_max = safe_sqrt(1.0 - sin2_theta_max);
+        let mut one_minus_cos_theta_max = 1.0 - cos_theta_max;
+        let mut cos_theta = (cos_theta_max - 1.0) * u.x + 1.0;
+        let mut sin2_theta = 1.0 - cos_theta * cos_theta;
+        if sin2_theta_max < SMALL_CONE_SIN2_THETA_MAX {
+            // compute cone sample via Taylor series expansion for small angles
+            sin2_theta = sin2_theta_max * u.x;
+            cos_theta = (1.0 - sin2_theta).sqrt();
+            one_minus_cos_theta_max = sin2_theta_max / 2.0;
+        }
+
+        // compute angle alpha from center of sphere to sampled point on surface
+        let cos_alpha = sin2_theta / sin_theta_max
+            + cos_theta * safe_sqrt(1.0 - sin2_theta / (sin_theta_max * sin_theta_max));
+        let sin_alpha = safe_sqrt(1.0 - cos_alpha * cos_alpha);
+
+        // compute surface normal and sampled point on sphere
+        let phi = u.y * 2.0 * PI;
+        let w = spherical_direction(sin_alpha, cos_alpha, phi);
+        let sampling_frame = Frame::from_z(&(p_center - ref_it.p()).normalize());
+        let p = p_center + sampling_frame.from_local(&-w) * self.radius;
+        let p_obj = self.world_to_object.transform_point3f(&p);
+        let mut it = self.sample_interaction(&p_obj);
+        it.time = ref_it.time;
+        Some((it, 1.0 / (2.0 * PI * one_minus_cos_theta_max)))
+    }
+
+    fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        let Some((_, sin2_theta_max)) = self.sampling_cone(ref_it) else {
+            return pdf_ref_by_area(self, ref_it, wi);
+        };
+        if !self.intersect_p(&ref_it.spawn_ray(wi)) {
+            return 0.0;
+        }
+
+        // compute general solid angle sphere pdf
+        let one_minus_cos_theta_max = if sin2_theta_max < SMALL_CONE_SIN2_THETA_MAX {
+            sin2_theta_max / 2.0
+        } else {
+            1.0 - safe_sqrt(1.0 - sin2_theta_max)
+        };
+        1.0 / (2.0 * PI * one_minus_cos_theta_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::core::medium::MediumInterface;
+
+    use super::*;
+
+    fn sphere_at(center: Vector3f, z_min: Float, z_max: Float, phi_max: Float) -> Sphere {
+        let object_to_world = Transform::translate(&center);
+        let world_to_object = object_to_world.inverse();
+        Sphere::new(
+            Arc::new(object_to_world),
+            Arc::new(world_to_object),
+            false,
+            2.0,
+            z_min,
+            z_max,
+            phi_max,
+        )
+    }
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hits_translated_sphere() {
+        let sphere = sphere_at(Vector3f::new(0.0, 0.0, 5.0), -2.0, 2.0, 360.0);
+        let r = ray(Point3f::new(0.0, 0.0, 0.0), Vector3f::new(0.0, 0.0, 1.0));
+        let (t_hit, si) = sphere.intersect(&r).unwrap();
+        assert!((t_hit - 3.0).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(0.0, 0.0, 3.0)).length() < 1e-4);
+        let outward = Vector3f::new(0.0, 0.0, -1.0);
+        assert!(Vector3f::from(si.interaction.n).dot(&outward) > 0.99);
+    }
+
+    #[test]
+    fn respects_t_max_and_clipping() {
+        let sphere = sphere_at(Vector3f::default(), -2.0, 2.0, 360.0);
+        let r = ray(Point3f::new(0.0, 0.0, -5.0), Vector3f::new(0.0, 0.0, 1.0));
+        r.t_max.set(2.5);
+        assert!(!sphere.intersect_p(&r));
+
+        // with the bottom cap cut away the ray hits the far side from inside
+        let open = sphere_at(Vector3f::default(), -1.0, 2.0, 360.0);
+        let r = ray(Point3f::new(0.0, 0.0, -5.0), Vector3f::new(0.0, 0.0, 1.0));
+        let (t_hit, _) = open.intersect(&r).unwrap();
+        assert!((t_hit - 7.0).abs() < 1e-6);
+
+        // half sphere with phi in [0, 180] degrees is missed from negative y
+        let half = sphere_at(Vector3f::default(), -2.0, 2.0, 180.0);
+        let r = ray(Point3f::new(1.0, -5.0, 0.0), Vector3f::new(0.0, 1.0, 0.0));
+        let (t_hit, si) = half.intersect(&r).unwrap();
+        assert!(si.p().y > 0.0);
+        assert!(t_hit > 5.0);
+    }
+
+    #[test]
+    fn area_of_full_sphere() {
+        let sphere = sphere_at(Vector3f::default(), -2.0, 2.0, 360.0);
+        assert!((sphere.area() - 16.0 * PI).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sample_ref_agrees_with_pdf_ref() {
+        let sphere = sphere_at(Vector3f::new(0.0, 0.0, 5.0), -2.0, 2.0, 360.0);
+        let ref_it = Interaction::new(
+            Point3fi::from(Point3f::default()),
+            Normal3f::default(),
+            0.0,
+            Vector3f::default(),
+            MediumInterface::default(),
+        );
+        for u in [Point2f::new(0.2, 0.7), Point2f::new(0.9, 0.1)] {
+            let (it, pdf) = sphere.sample_ref(&ref_it, &u).unwrap();
+            assert!((it.p() - Point3f::new(0.0, 0.0, 5.0)).length() - 2.0 < 1e-4);
+            // the sampled point is on the side facing the reference point
+            assert!(it.p().z < 5.0);
+            let wi = it.p() - ref_it.p();
+            assert!((sphere.pdf_ref(&ref_it, &wi) - pdf).abs() < 1e-4 * pdf);
+        }
+        assert_eq!(sphere.pdf_ref(&ref_it, &Vector3f::new(0.0, 1.0, 0.0)), 0.0);
+    }
+}
@@ -0,0 +1,658 @@
+use std::sync::Arc;
+
+use crate::core::{
+    geometry::{
+        bounds::Bounds3f,
+        normal::Normal3f,
+        point::{Point2f, Point3f, Point3fi},
+        ray::Ray,
+        spherical::spherical_triangle_area,
+        transform::Transform,
+        vector::{coordinate_system, Vector3f},
+    },
+    interaction::{Interaction, SurfaceInteraction},
+    medium::MediumInterface,
+    pbrt::{gamma, Float},
+    sampling::{
+        bilinear_pdf, invert_spherical_triangle_sample, sample_bilinear, sample_spherical_triangle,
+        sample_uniform_triangle,
+    },
+    shape::{
+        pdf_ref_by_area, sample_ref_by_area, Shape, MAX_SPHERICAL_SAMPLE_AREA,
+        MIN_SPHERICAL_SAMPLE_AREA,
+    },
+};
+
+// TriangleMesh
+// vertex data shared by all triangles of a mesh. Positions, normals and
+// tangents are stored in world space, indices holds three vertex indices per
+// triangle
+pub struct TriangleMesh {
+    pub world_to_object: Transform,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub n_triangles: usize,
+    pub vertex_indices: Vec<usize>,
+    pub p: Vec<Point3f>,
+    pub n: Option<Vec<Normal3f>>,
+    pub s: Option<Vec<Vector3f>>,
+    pub uv: Option<Vec<Point2f>>,
+}
+
+impl TriangleMesh {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        object_to_world: &Transform,
+        reverse_orientation: bool,
+        vertex_indices: Vec<usize>,
+        p: Vec<Point3f>,
+        n: Option<Vec<Normal3f>>,
+        s: Option<Vec<Vector3f>>,
+        uv: Option<Vec<Point2f>>,
+    ) -> Result<TriangleMesh, String> {
+        if !vertex_indices.len().is_multiple_of(3) {
+            return Err(format!(
+                "TriangleMesh has {} vertex indices, not a multiple of three",
+                vertex_indices.len()
+            ));
+        }
+        if let Some(&i) = vertex_indices.iter().find(|&&i| i >= p.len()) {
+            return Err(format!(
+                "TriangleMesh vertex index {} is out of range for {} positions",
+                i,
+                p.len()
+            ));
+        }
+        let lengths = [
+            ("normals", n.as_ref().map(|n| n.len())),
+            ("tangents", s.as_ref().map(|s| s.len())),
+            ("uvs", uv.as_ref().map(|uv| uv.len())),
+        ];
+        for (name, len) in lengths {
+            if let Some(len) = len.filter(|&len| len != p.len()) {
+                return Err(format!(
+                    "TriangleMesh has {} {} for {} positions",
+                    len,
+                    name,
+                    p.len()
+                ));
+            }
+        }
+        // transform mesh vertices to world space
+        let p = p
+            .iter()
+            .map(|p| object_to_world.transform_point3f(p))
+            .collect();
+        let n = n.map(|n| {
+            n.iter()
+                .map(|n| object_to_world.transform_normal3f(n))
+                .collect()
+        });
+        let s = s.map(|s| {
+            s.iter()
+                .map(|s| object_to_world.transform_vector3f(s))
+                .collect()
+        });
+        Ok(TriangleMesh {
+            world_to_object: object_to_world.inverse(),
+            reverse_orientation,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+            n_triangles: vertex_indices.len() / 3,
+            vertex_indices,
+            p,
+            n,
+            s,
+            uv,
+        })
+    }
+}
+
+// Triangle
+pub struct Triangle {
+    pub mesh: Arc<TriangleMesh>,
+    pub triangle_index: usize,
+}
+
+impl Triangle {
+    pub fn new(mesh: Arc<TriangleMesh>, triangle_index: usize) -> Triangle {
+        Triangle {
+            mesh,
+            triangle_index,
+        }
+    }
+
+    fn vertices(&self) -> [usize; 3] {
+        let v = &self.mesh.vertex_indices[3 * self.triangle_index..3 * self.triangle_index + 3];
+        [v[0], v[1], v[2]]
+    }
+
+    fn positions(&self) -> [Point3f; 3] {
+        let v = self.vertices();
+        [self.mesh.p[v[0]], self.mesh.p[v[1]], self.mesh.p[v[2]]]
+    }
+
+    // the mesh uvs, or a default parameterization if the mesh has none
+    fn uvs(&self) -> [Point2f; 3] {
+        match self.mesh.uv {
+            Some(ref uv) => {
+                let v = self.vertices();
+                [uv[v[0]], uv[v[1]], uv[v[2]]]
+            }
+            None => [
+                Point2f::new(0.0, 0.0),
+                Point2f::new(1.0, 0.0),
+                Point2f::new(1.0, 1.0),
+            ],
+        }
+    }
+
+    // Interaction for the point with barycentric coordinates b, with the
+    // normal facing like the shading normals if the mesh has them
+    fn interaction_at(&self, b: &[Float; 3]) -> Interaction {
+        let [p0, p1, p2] = self.positions();
+        let p = p0 * b[0] + p1 * b[1] + p2 * b[2];
+        // compute surface normal for sampled point on triangle
+        let mut n = Normal3f::from((p1 - p0).cross(&(p2 - p0)).normalize());
+        if let Some(ref mesh_n) = self.mesh.n {
+            let v = self.vertices();
+            let ns = mesh_n[v[0]] * b[0] + mesh_n[v[1]] * b[1] + mesh_n[v[2]] * b[2];
+            n = n.face_forward(&ns);
+        } else if self.mesh.reverse_orientation ^ self.mesh.transform_swaps_handedness {
+            n = -n;
+        }
+        // compute error bounds for sampled point on triangle
+        let p_error =
+            Vector3f::from((p0 * b[0]).abs() + (p1 * b[1]).abs() + (p2 * b[2]).abs()) * gamma(6);
+        Interaction::new(
+            Point3fi::new_with_error(&p, &p_error),
+            n,
+            0.0,
+            Vector3f::default(),
+            MediumInterface::default(),
+        )
+    }
+
+    // the solid angle the triangle subtends from p if it is sampled by solid
+    // angle from there, which needs a moderate solid angle
+    fn sampling_solid_angle(&self, p: &Point3f) -> Option<Float> {
+        let [a, b, c] = self.positions().map(|q| (q - *p).normalize());
+        let solid_angle = spherical_triangle_area(&a, &b, &c);
+        if (MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA).contains(&solid_angle) {
+            Some(solid_angle)
+        } else {
+            None
+        }
+    }
+
+    // corner weights that warp spherical triangle samples towards the
+    // incident cos theta factor at p with normal n
+    fn cos_weights(&self, p: &Point3f, n: &Normal3f) -> [Float; 4] {
+        let n = Vector3f::from(*n);
+        let [w0, w1, w2] = self
+            .positions()
+            .map(|q| (q - *p).normalize().abs_dot(&n).max(0.01));
+        [w1, w1, w0, w2]
+    }
+}
+
+impl Shape for Triangle {
+    fn object_bound(&self) -> Bounds3f {
+        let [p0, p1, p2] = self.positions();
+        let w2o = &self.mesh.world_to_object;
+        Bounds3f::new_with_points(w2o.transform_point3f(&p0), w2o.transform_point3f(&p1))
+            .union_point3f(&w2o.transform_point3f(&p2))
+    }
+
+    fn world_bound(&self) -> Bounds3f {
+        let [p0, p1, p2] = self.positions();
+        Bounds3f::new_with_points(p0, p1).union_point3f(&p2)
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(Float, SurfaceInteraction)> {
+        let [p0, p1, p2] = self.positions();
+        let TriangleIntersection { b0, b1, b2, t } =
+            intersect_triangle(ray, ray.t_max.get(), &p0, &p1, &p2)?;
+
+        // compute triangle partial derivatives
+        let uv = self.uvs();
+        let duv02 = uv[0] - uv[2];
+        let duv12 = uv[1] - uv[2];
+        let dp02 = p0 - p2;
+        let dp12 = p1 - p2;
+        let determinant = duv02.x * duv12.y - duv02.y * duv12.x;
+        let degenerate_uv = determinant.abs() < 1e-8;
+        let mut dpdu = Vector3f::default();
+        let mut dpdv = Vector3f::default();
+        if !degenerate_uv {
+            let invdet = 1.0 / determinant;
+            dpdu = (dp02 * duv12.y - dp12 * duv02.y) * invdet;
+            dpdv = (dp12 * duv02.x - dp02 * duv12.x) * invdet;
+        }
+        if degenerate_uv || dpdu.cross(&dpdv).length_squared() == 0.0 {
+            // handle zero determinant for triangle partial derivative matrix
+            let ng = (p2 - p0).cross(&(p1 - p0));
+            if ng.length_squared() == 0.0 {
+                // the triangle is actually degenerate
+                return None;
+            }
+            (dpdu, dpdv) = coordinate_system(&ng.normalize());
+        }
+
+        // compute error bounds for triangle intersection
+        let p_error =
+            Vector3f::from((p0 * b0).abs() + (p1 * b1).abs() + (p2 * b2).abs()) * gamma(7);
+
+        // interpolate (u,v) parametric coordinates and hit point
+        let p_hit = p0 * b0 + p1 * b1 + p2 * b2;
+        let uv_hit = Point2f::new(
+            b0 * uv[0].x + b1 * uv[1].x + b2 * uv[2].x,
+            b0 * uv[0].y + b1 * uv[1].y + b2 * uv[2].y,
+        );
+
+        // fill in SurfaceInteraction from triangle hit
+        let mut isect = SurfaceInteraction::new(
+            Point3fi::new_with_error(&p_hit, &p_error),
+            uv_hit,
+            -ray.d,
+            dpdu,
+            dpdv,
+            Normal3f::default(),
+            Normal3f::default(),
+            ray.time,
+            false,
+        );
+
+        // override surface normal in isect for triangle
+        let mut n = Normal3f::from(dp02.cross(&dp12).normalize());
+        if self.mesh.reverse_orientation ^ self.mesh.transform_swaps_handedness {
+            n = -n;
+        }
+        isect.interaction.n = n;
+        isect.shading.n = n;
+
+        if self.mesh.n.is_some() || self.mesh.s.is_some() {
+            // initialize triangle shading geometry
+            let v = self.vertices();
+
+            // compute shading normal ns for triangle
+            let ns = match self.mesh.n {
+                Some(ref mesh_n) => {
+                    let ns = mesh_n[v[0]] * b0 + mesh_n[v[1]] * b1 + mesh_n[v[2]] * b2;
+                    if ns.length_squared() > 0.0 {
+                        ns.normalize()
+                    } else {
+                        n
+                    }
+                }
+                None => n,
+            };
+
+            // compute shading tangent ss for triangle
+            let mut ss = match self.mesh.s {
+                Some(ref mesh_s) => {
+                    let ss = mesh_s[v[0]] * b0 + mesh_s[v[1]] * b1 + mesh_s[v[2]] * b2;
+                    if ss.length_squared() > 0.0 {
+                        ss
+                    } else {
+                        isect.dpdu
+                    }
+                }
+                None => isect.dpdu,
+            };
+
+            // compute shading bitangent ts for triangle and adjust ss
+            let ns_v = Vector3f::from(ns);
+            let mut ts = ss.cross(&ns_v);
+            if ts.length_squared() > 0.0 {
+                ts = ts.normalize();
+                ss = ts.cross(&ns_v);
+            } else {
+                (ss, ts) = coordinate_system(&ns_v);
+            }
+
+            // compute dndu and dndv for triangle shading geometry
+            let (dndu, dndv) = match self.mesh.n {
+                Some(ref mesh_n) => {
+                    let dn1 = mesh_n[v[0]] - mesh_n[v[2]];
+                    let dn2 = mesh_n[v[1]] - mesh_n[v[2]];
+                    if degenerate_uv {
+                        // we can still compute dndu and dndv, with respect to
+                        // the same arbitrary coordinate system we use to
+                        // compute dpdu and dpdv when this happens
+                        let dn = Vector3f::from(mesh_n[v[2]] - mesh_n[v[0]])
+                            .cross(&Vector3f::from(mesh_n[v[1]] - mesh_n[v[0]]));
+                        if dn.length_squared() == 0.0 {
+                            (Normal3f::default(), Normal3f::default())
+                        } else {
+                            let (dnu, dnv) = coordinate_system(&dn);
+                            (Normal3f::from(dnu), Normal3f::from(dnv))
+                        }
+                    } else {
+                        let invdet = 1.0 / determinant;
+                        (
+                            (dn1 * duv12.y - dn2 * duv02.y) * invdet,
+                            (dn2 * duv02.x - dn1 * duv12.x) * invdet,
+                        )
+                    }
+                }
+                None => (Normal3f::default(), Normal3f::default()),
+            };
+            // per-vertex normals decide the orientation over
+            // reverse_orientation, as in sample_area
+            isect.set_shading_geometry(&ss, &ts, &dndu, &dndv, true);
+        }
+        Some((t, isect))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        let [p0, p1, p2] = self.positions();
+        intersect_triangle(ray, ray.t_max.get(), &p0, &p1, &p2).is_some()
+    }
+
+    fn area(&self) -> Float {
+        let [p0, p1, p2] = self.positions();
+        0.5 * (p1 - p0).cross(&(p2 - p0)).length()
+    }
+
+    // picks a point uniformly over the triangle's area
+    fn sample_area(&self, u: &Point2f) -> (Interaction, Float) {
+        let b = sample_uniform_triangle(u);
+        (self.interaction_at(&b), 1.0 / self.area())
+    }
+
+    // samples the triangle uniformly over its solid angle, warped by the cos
+    // theta factor at ref_it if it has a normal, and by area otherwise
+    fn sample_ref(&self, ref_it: &Interaction, u: &Point2f) -> Option<(Interaction, Float)> {
+        let ref_p = ref_it.p();
+        if self.sampling_solid_angle(&ref_p).is_none() {
+            return sample_ref_by_area(self, ref_it, u);
+        }
+
+        // warp uniform sample u to account for incident cos theta factor
+        let mut u = *u;
+        let mut pdf = 1.0;
+        if ref_it.is_surface_interaction() {
+            let w = self.cos_weights(&ref_p, &ref_it.n);
+            u = sample_bilinear(&u, &w);
+            pdf = bilinear_pdf(&u, &w);
+        }
+
+        // sample spherical triangle from reference point
+        let (b, triangle_pdf) = sample_spherical_triangle(&self.positions(), &ref_p, &u)?;
+        pdf *= triangle_pdf;
+        if pdf == 0.0 {
+            return None;
+        }
+        let mut it = self.interaction_at(&b);
+        it.time = ref_it.time;
+        Some((it, pdf))
+    }
+
+    fn pdf_ref(&self, ref_it: &Interaction, wi: &Vector3f) -> Float {
+        let ref_p = ref_it.p();
+        let Some(solid_angle) = self.sampling_solid_angle(&ref_p) else {
+            return pdf_ref_by_area(self, ref_it, wi);
+        };
+        if !self.intersect_p(&ref_it.spawn_ray(wi)) {
+            return 0.0;
+        }
+
+        // account for the cos theta warp of sample_ref
+        let mut pdf = 1.0 / solid_angle;
+        if ref_it.is_surface_interaction() {
+            let u = invert_spherical_triangle_sample(&self.positions(), &ref_p, &wi.normalize());
+            pdf *= bilinear_pdf(&u, &self.cos_weights(&ref_p, &ref_it.n));
+        }
+        pdf
+    }
+}
+
+// barycentric coordinates and parametric distance of a ray-triangle hit
+pub struct TriangleIntersection {
+    pub b0: Float,
+    pub b1: Float,
+    pub b2: Float,
+    pub t: Float,
+}
+
+// watertight ray-triangle test, returns the hit in (0, t_max) if any
+pub fn intersect_triangle(
+    ray: &Ray,
+    t_max: Float,
+    p0: &Point3f,
+    p1: &Point3f,
+    p2: &Point3f,
+) -> Option<TriangleIntersection> {
+    // translate vertices based on ray origin
+    let mut p0t = *p0 - ray.o;
+    let mut p1t = *p1 - ray.o;
+    let mut p2t = *p2 - ray.o;
+
+    // permute components of triangle vertices and ray direction
+    let kz = ray.d.abs().max_dimension();
+    let kx = if kz + 1 == 3 { 0 } else { kz + 1 };
+    let ky = if kx + 1 == 3 { 0 } else { kx + 1 };
+    let d = ray.d.permute(kx, ky, kz);
+    p0t = p0t.permute(kx, ky, kz);
+    p1t = p1t.permute(kx, ky, kz);
+    p2t = p2t.permute(kx, ky, kz);
+
+    // apply shear transformation to translated vertex positions
+    let sx = -d.x / d.z;
+    let sy = -d.y / d.z;
+    let sz = 1.0 / d.z;
+    p0t.x += sx * p0t.z;
+    p0t.y += sy * p0t.z;
+    p1t.x += sx * p1t.z;
+    p1t.y += sy * p1t.z;
+    p2t.x += sx * p2t.z;
+    p2t.y += sy * p2t.z;
+
+    // compute edge function coefficients
+    #[allow(unused_mut)]
+    let mut e0 = p1t.x * p2t.y - p1t.y * p2t.x;
+    #[allow(unused_mut)]
+    let mut e1 = p2t.x * p0t.y - p2t.y * p0t.x;
+    #[allow(unused_mut)]
+    let mut e2 = p0t.x * p1t.y - p0t.y * p1t.x;
+
+    // fall back to double precision test at triangle edges
+    #[cfg(feature = "float-f32")]
+    if e0 == 0.0 || e1 == 0.0 || e2 == 0.0 {
+        let p2txp1ty = p2t.x as f64 * p1t.y as f64;
+        let p2typ1tx = p2t.y as f64 * p1t.x as f64;
+        e0 = (p2typ1tx - p2txp1ty) as Float;
+        let p0txp2ty = p0t.x as f64 * p2t.y as f64;
+        let p0typ2tx = p0t.y as f64 * p2t.x as f64;
+        e1 = (p0typ2tx - p0txp2ty) as Float;
+        let p1txp0ty = p1t.x as f64 * p0t.y as f64;
+        let p1typ0tx = p1t.y as f64 * p0t.x as f64;
+        e2 = (p1typ0tx - p1txp0ty) as Float;
+    }
+
+    // perform triangle edge and determinant tests
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+
+    // compute scaled hit distance to triangle and test against ray t range
+    p0t.z *= sz;
+    p1t.z *= sz;
+    p2t.z *= sz;
+    let t_scaled = e0 * p0t.z + e1 * p1t.z + e2 * p2t.z;
+    if (det < 0.0 && (t_scaled >= 0.0 || t_scaled < t_max * det))
+        || (det > 0.0 && (t_scaled <= 0.0 || t_scaled > t_max * det))
+    {
+        return None;
+    }
+
+    // compute barycentric coordinates and t value for triangle intersection
+    let inv_det = 1.0 / det;
+    let b0 = e0 * inv_det;
+    let b1 = e1 * inv_det;
+    let b2 = e2 * inv_det;
+    let t = t_scaled * inv_det;
+
+    // ensure that computed triangle t is conservatively greater than zero
+    let max_zt = Vector3f::new(p0t.z, p1t.z, p2t.z).abs().max_component();
+    let delta_z = gamma(3) * max_zt;
+    let max_xt = Vector3f::new(p0t.x, p1t.x, p2t.x).abs().max_component();
+    let max_yt = Vector3f::new(p0t.y, p1t.y, p2t.y).abs().max_component();
+    let delta_x = gamma(5) * (max_xt + max_zt);
+    let delta_y = gamma(5) * (max_yt + max_zt);
+    let delta_e = 2.0 * (gamma(2) * max_xt * max_yt + delta_y * max_xt + delta_x * max_yt);
+    let max_e = Vector3f::new(e0, e1, e2).abs().max_component();
+    let delta_t =
+        3.0 * (gamma(3) * max_e * max_zt + delta_e * max_zt + delta_z * max_e) * inv_det.abs();
+    if t <= delta_t {
+        return None;
+    }
+    Some(TriangleIntersection { b0, b1, b2, t })
+}
+
+// one Triangle per triangle of the mesh, all sharing its vertex data
+pub fn create_triangles(mesh: Arc<TriangleMesh>) -> Vec<Triangle> {
+    (0..mesh.n_triangles)
+        .map(|i| Triangle::new(mesh.clone(), i))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    // the unit square in z = 0, split along its diagonal from (0,0) to (1,1)
+    fn square(reverse_orientation: bool, n: Option<Vec<Normal3f>>) -> Vec<Triangle> {
+        let p = vec![
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(1.0, 1.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+        ];
+        let mesh = TriangleMesh::new(
+            &Transform::default(),
+            reverse_orientation,
+            vec![0, 1, 2, 0, 2, 3],
+            p,
+            n,
+            None,
+            None,
+        )
+        .unwrap();
+        create_triangles(Arc::new(mesh))
+    }
+
+    fn ray(o: Point3f, d: Vector3f) -> Ray {
+        Ray {
+            o,
+            d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_meshes() {
+        let p = vec![Point3f::default(); 3];
+        let identity = Transform::default();
+        let new = |indices: Vec<usize>, n: Option<Vec<Normal3f>>| {
+            TriangleMesh::new(&identity, false, indices, p.clone(), n, None, None)
+        };
+        assert!(new(vec![0, 1], None).is_err());
+        assert!(new(vec![0, 1, 3], None).is_err());
+        assert!(new(vec![0, 1, 2], Some(vec![Normal3f::default(); 2])).is_err());
+        assert_eq!(new(vec![0, 1, 2, 2, 1, 0], None).unwrap().n_triangles, 2);
+    }
+
+    #[test]
+    fn hit_point_and_barycentrics() {
+        let triangles = square(false, None);
+        let r = ray(Point3f::new(0.75, 0.25, 2.0), Vector3f::new(0.0, 0.0, -1.0));
+        let (t, si) = triangles[0].intersect(&r).unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+        assert!((si.p() - Point3f::new(0.75, 0.25, 0.0)).length() < 1e-6);
+        assert!(triangles[1].intersect(&r).is_none());
+        assert!(triangles[0].intersect_p(&r));
+        r.t_max.set(1.5);
+        assert!(!triangles[0].intersect_p(&r));
+        assert!((triangles[0].area() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        let triangles = square(false, None);
+        // rays through points on the shared diagonal, from skewed origins, must
+        // hit at least one of the two triangles
+        for i in 1..64 {
+            let s = i as Float / 64.0;
+            for o in [
+                Point3f::new(0.3, -0.7, 1.0),
+                Point3f::new(-2.0, 0.1, 3.0),
+                Point3f::new(1.7, 1.3, 0.4),
+            ] {
+                let r = ray(o, Point3f::new(s, s, 0.0) - o);
+                let hits = triangles.iter().filter(|t| t.intersect_p(&r)).count();
+                assert!(
+                    hits >= 1,
+                    "ray through ({s}, {s}) from {:?} fell through",
+                    o
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hits_are_oriented_like_samples() {
+        let u = Point2f::new(0.3, 0.6);
+        let down = ray(Point3f::new(0.75, 0.25, 1.0), Vector3f::new(0.0, 0.0, -1.0));
+        let up = Normal3f::new(0.0, 0.0, 1.0);
+        for (reverse, n) in [
+            (false, None),
+            (true, None),
+            (false, Some(vec![-up; 4])),
+            (true, Some(vec![up; 4])),
+        ] {
+            let triangles = square(reverse, n);
+            let (_, si) = triangles[0].intersect(&down).unwrap();
+            let (it, pdf) = triangles[0].sample_area(&u);
+            assert_eq!(si.n(), it.n);
+            assert!(si.n().dot(&si.shading.n) > 0.0);
+            assert!((pdf - 2.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn sample_ref_agrees_with_pdf_ref() {
+        let triangles = square(false, None);
+        let reference = |p: Point3f, n: Normal3f| {
+            Interaction::new(
+                Point3fi::from(p),
+                n,
+                0.0,
+                Vector3f::default(),
+                MediumInterface::default(),
+            )
+        };
+        // sampled by solid angle from nearby points, with and without the
+        // cos theta warp, and by area from far away
+        for ref_it in [
+            reference(Point3f::new(0.5, 0.3, 1.0), Normal3f::default()),
+            reference(Point3f::new(0.5, 0.3, 1.0), Normal3f::new(0.3, 0.0, -1.0)),
+            reference(Point3f::new(0.5, 0.3, 100.0), Normal3f::default()),
+        ] {
+            for u in [Point2f::new(0.2, 0.7), Point2f::new(0.6, 0.4)] {
+                let (it, pdf) = triangles[0].sample_ref(&ref_it, &u).unwrap();
+                assert!(it.p().z.abs() < 1e-6 && it.p().x >= it.p().y);
+                let wi = it.p() - ref_it.p();
+                let pdf_ref = triangles[0].pdf_ref(&ref_it, &wi);
+                assert!((pdf_ref - pdf).abs() < 1e-3 * pdf, "{} vs {}", pdf_ref, pdf);
+            }
+        }
+    }
+}